@@ -0,0 +1,1209 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+};
+
+use crate::parser::{
+    ast::{
+        CodeBlock, Context, Declaration, Expression, MatchArm, Pattern, Program, Statement, Value,
+    },
+    token::Operator,
+    types::{EFSType, EFSValueType},
+};
+
+use super::{
+    commands::{self, Part},
+    eval, resolve, selector, stdlib,
+    symbols::SymbolTable,
+    text, unroll, FunctionSignature,
+};
+
+#[derive(Debug)]
+pub struct TypeError {
+    /// Start of the statement the error is in.
+    pub at: usize,
+    pub message: String,
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "type error at {}: {}", self.at, self.message)
+    }
+}
+
+impl Error for TypeError {}
+
+/// Every type error found in a program, in source order.
+#[derive(Debug)]
+pub struct TypeErrors(pub Vec<TypeError>);
+
+impl Display for TypeErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, error) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for TypeErrors {}
+
+/// Checks that every expression in `programs` has a type that fits where it is used.
+/// Variables declared without a type get the type of their initial value.
+/// Undefined names are reported by the symbol table, which is returned so it
+/// can be queried once the program is known to be valid.
+pub fn check(programs: &[Program]) -> Result<SymbolTable, TypeErrors> {
+    let mut symbols = SymbolTable::build(programs);
+    let impls = programs
+        .iter()
+        .flat_map(|program| &program.0)
+        .filter_map(|declaration| match declaration.item() {
+            Declaration::Impl(name, functions) => Some((name, functions)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let programs = &resolve::resolve(programs);
+    let mut checker = Checker {
+        functions: HashMap::new(),
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        constants: HashMap::new(),
+        externs: HashMap::new(),
+        scores: HashMap::new(),
+        errors: std::mem::take(&mut symbols.errors),
+    };
+    let declarations = programs.iter().flat_map(|program| &program.0);
+
+    for declaration in declarations.clone() {
+        match declaration {
+            Declaration::FunctionDec {
+                name,
+                parameters,
+                return_type,
+                ..
+            } => {
+                let signature = FunctionSignature {
+                    parameters: parameters.clone(),
+                    return_type: return_type.clone(),
+                };
+                checker.functions.insert(name, signature);
+            }
+            Declaration::ExternFunction {
+                name,
+                parameters,
+                return_type,
+                ..
+            } => {
+                let signature = FunctionSignature {
+                    parameters: parameters.clone(),
+                    return_type: return_type.clone(),
+                };
+                checker.functions.insert(name, signature);
+            }
+            Declaration::ExternVariable(variable) => {
+                checker.externs.insert(&variable.name, &variable.var_type);
+            }
+            Declaration::EntityScore(name, var_type) => {
+                checker.scores.insert(name, var_type);
+            }
+            Declaration::StructDef(name, fields) => {
+                checker.structs.insert(name, fields);
+            }
+            Declaration::EnumDef(name, variants) => {
+                checker.enums.insert(name, variants);
+            }
+            _ => {}
+        }
+    }
+    for (name, functions) in impls {
+        if checker.structs.contains_key(name.as_str()) || checker.enums.contains_key(name.as_str())
+        {
+            continue;
+        }
+        let at = functions.iter().find_map(|function| match function.item() {
+            Declaration::FunctionDec { code_block, .. } => Some(code_block.end),
+            _ => None,
+        });
+        checker.errors.push(TypeError {
+            at: at.unwrap_or_default(),
+            message: format!("impl for unknown type {}", name),
+        });
+    }
+    // Constants that fail to evaluate are reported by the compiler, here they
+    // only need a type.
+    for declaration in declarations.clone() {
+        if let Declaration::ConstDec(constant) = declaration {
+            let value = eval::evaluate(&constant.var, &|name| {
+                checker.constants.get(name).map(|(_, value)| value.clone())
+            });
+            let value = match (value, &constant.var_type) {
+                (Ok(value), Some(var_type)) => eval::cast(value, var_type).ok(),
+                (value, _) => value.ok(),
+            };
+            if let Some(value) = value {
+                checker
+                    .constants
+                    .insert(constant.name.clone(), (value.get_type(), value));
+            }
+        }
+    }
+
+    for declaration in declarations {
+        if let Declaration::FunctionDec {
+            name,
+            parameters,
+            return_type,
+            code_block,
+            ..
+        } = declaration
+        {
+            let mut function = FunctionChecker {
+                checker: &mut checker,
+                name,
+                return_type,
+                scopes: vec![parameters.iter().cloned().collect()],
+                loops: 0,
+                entities: 0,
+                executors: 0,
+                statics: HashMap::new(),
+                at: code_block.end,
+            };
+            function.block(code_block);
+            if return_type != &EFSType::None && !returns(code_block) {
+                function.error(
+                    code_block.end,
+                    format!("function {} must return a {:?}", name, return_type),
+                );
+            }
+        }
+    }
+
+    if checker.errors.is_empty() {
+        return Ok(symbols);
+    }
+    checker.errors.sort_by_key(|error| error.at);
+    Err(TypeErrors(checker.errors))
+}
+
+struct Checker<'a> {
+    functions: HashMap<&'a str, FunctionSignature>,
+    structs: HashMap<&'a str, &'a HashMap<String, EFSType>>,
+    enums: HashMap<&'a str, &'a Vec<(String, Vec<EFSType>)>>,
+    constants: HashMap<String, (EFSType, EFSValueType)>,
+    /// Types of the `extern score` and `extern storage` variables.
+    externs: HashMap<&'a str, &'a EFSType>,
+    /// Types of the scores declared with `score name: type on entity;`.
+    scores: HashMap<&'a str, &'a EFSType>,
+    errors: Vec<TypeError>,
+}
+
+struct FunctionChecker<'a, 'b> {
+    checker: &'b mut Checker<'a>,
+    name: &'a str,
+    return_type: &'a EFSType,
+    scopes: Vec<HashMap<String, EFSType>>,
+    loops: usize,
+    /// Number of `as` and `at` blocks around the statement, their body runs once for
+    /// every entity.
+    entities: usize,
+    /// Number of `as` blocks around the statement, inside them `@s` is an entity.
+    executors: usize,
+    /// First value of the `static for` loops around the statement, by the index of
+    /// the scope that holds their variable.
+    statics: HashMap<usize, i32>,
+    /// Start of the statement being checked.
+    at: usize,
+}
+
+impl FunctionChecker<'_, '_> {
+    fn error(&mut self, at: usize, message: String) {
+        self.checker.errors.push(TypeError {
+            at,
+            message: format!("{} (in function {})", message, self.name),
+        });
+    }
+
+    fn block(&mut self, block: &CodeBlock) {
+        self.scopes.push(HashMap::new());
+        for (statement, at) in block.statements.iter().zip(&block.positions) {
+            self.at = *at;
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, var_type: EFSType) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), var_type);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&EFSType> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| {
+                self.checker
+                    .constants
+                    .get(name)
+                    .map(|(var_type, _)| var_type)
+            })
+            .or_else(|| self.checker.externs.get(name).copied())
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(Expression::VarDec(name, Some(var_type), value)) => {
+                self.expect_value(value, var_type);
+                self.declare(name, var_type.clone());
+            }
+            Statement::Expression(Expression::VarDec(name, None, value)) => {
+                let var_type = match self.value(value) {
+                    Some(EFSType::None) => {
+                        self.error(self.at, format!("{} has no value to be assigned", name));
+                        EFSType::None
+                    }
+                    Some(EFSType::List(element)) if *element == EFSType::None => {
+                        self.error(
+                            self.at,
+                            format!("the element type of {} can not be inferred", name),
+                        );
+                        EFSType::List(element)
+                    }
+                    Some(value_type) => value_type,
+                    None => EFSType::None,
+                };
+                self.declare(name, var_type);
+            }
+            Statement::Expression(Expression::Assign(name, value)) => {
+                let is_local = self.scopes.iter().any(|scope| scope.contains_key(name));
+                match self.lookup(name).cloned() {
+                    Some(var_type) if is_local || !self.checker.constants.contains_key(name) => {
+                        self.expect_value(value, &var_type)
+                    }
+                    Some(_) => {
+                        self.value(value);
+                        self.error(self.at, format!("cannot assign to constant {}", name));
+                    }
+                    None => {
+                        self.value(value);
+                    }
+                }
+            }
+            Statement::Expression(Expression::AssignField(target, field, op, value)) => {
+                let var_type = match self.value(target) {
+                    Some(EFSType::Selector) => self.score(target, field, false),
+                    Some(var_type) => {
+                        self.writable(target);
+                        self.field_type(&var_type, field)
+                    }
+                    None => None,
+                };
+                match (var_type, op) {
+                    (Some(var_type), Some(op)) if !var_type.is_integer() => {
+                        self.value(value);
+                        self.error(self.at, format!("cannot use {}= on {:?}", op, var_type));
+                    }
+                    (Some(var_type), _) => self.expect_value(value, &var_type),
+                    (None, _) => {
+                        self.value(value);
+                    }
+                }
+            }
+            Statement::Expression(Expression::Value(value)) => {
+                self.value(value);
+            }
+            Statement::If(condition, block) => {
+                self.condition(condition);
+                self.block(block);
+            }
+            Statement::IfLet(name, value, block) => {
+                let var_type = match self.value(value) {
+                    Some(EFSType::Option(var_type)) => *var_type,
+                    Some(var_type) => {
+                        self.error(
+                            self.at,
+                            format!("if let needs an optional value, found {:?}", var_type),
+                        );
+                        var_type
+                    }
+                    None => EFSType::None,
+                };
+                self.scopes.push(HashMap::from([(name.clone(), var_type)]));
+                self.block(block);
+                self.scopes.pop();
+            }
+            Statement::Match(value, arms) => self.match_arms(value, arms),
+            Statement::While(condition, block) => {
+                self.condition(condition);
+                self.loop_block(block);
+            }
+            Statement::For(init, condition, step, block) => {
+                let at = self.at;
+                self.scopes.push(HashMap::new());
+                self.statement(init);
+                self.condition(condition);
+                self.statement(step);
+                self.loop_block(block);
+                self.scopes.pop();
+                self.at = at;
+            }
+            Statement::ForList(item, list, block) => {
+                let element_type = match self.lookup(list).cloned() {
+                    Some(EFSType::List(element_type)) => *element_type,
+                    Some(var_type) => {
+                        self.error(
+                            self.at,
+                            format!("cannot iterate over {} of type {:?}", list, var_type),
+                        );
+                        EFSType::None
+                    }
+                    None => EFSType::None,
+                };
+                self.scopes
+                    .push(HashMap::from([(item.clone(), element_type)]));
+                self.loop_block(block);
+                self.scopes.pop();
+            }
+            Statement::StaticFor(name, start, _, _, block) => {
+                self.scopes
+                    .push(HashMap::from([(name.clone(), EFSType::Number)]));
+                self.statics.insert(self.scopes.len() - 1, *start);
+                self.block(block);
+                self.statics.remove(&(self.scopes.len() - 1));
+                self.scopes.pop();
+            }
+            Statement::Return(value) if self.entities > 0 => {
+                if let Some(value) = value {
+                    self.value(value);
+                }
+                let message = "return can not leave an as or at block, it runs once per entity";
+                self.error(self.at, message.to_string());
+            }
+            Statement::Return(value) => match (value, self.return_type) {
+                (None, EFSType::None) => {}
+                (Some(value), EFSType::None) => {
+                    self.value(value);
+                    self.error(
+                        self.at,
+                        format!("function {} does not return a value", self.name),
+                    );
+                }
+                (None, return_type) => self.error(
+                    self.at,
+                    format!("function {} must return a {:?}", self.name, return_type),
+                ),
+                (Some(value), return_type) => self.expect_value(value, return_type),
+            },
+            Statement::Break | Statement::Continue if self.loops == 0 => {
+                let keyword = match statement {
+                    Statement::Break => "break",
+                    _ => "continue",
+                };
+                self.error(self.at, format!("{} outside of a loop", keyword));
+            }
+            Statement::Break | Statement::Continue => {}
+            Statement::Execute(contexts, block) => {
+                for context in contexts {
+                    let (Context::As(selector) | Context::At(selector)) = context else {
+                        continue;
+                    };
+                    let errors = self.checker.errors.len();
+                    self.expect_value(selector, &EFSType::Selector);
+                    if self.checker.errors.len() == errors {
+                        let usage = match context {
+                            Context::As(_) => "as",
+                            _ => "at",
+                        };
+                        self.selector(selector, usage);
+                    }
+                }
+                let executes = contexts.iter().any(|c| matches!(c, Context::As(_)));
+                self.executors += executes as usize;
+                if contexts.iter().any(Context::is_loop) {
+                    // `break` and `continue` would only end the run for one entity.
+                    let loops = std::mem::take(&mut self.loops);
+                    self.entities += 1;
+                    self.block(block);
+                    self.entities -= 1;
+                    self.loops = loops;
+                } else {
+                    self.block(block);
+                }
+                self.executors -= executes as usize;
+            }
+            Statement::Commands(commands) => {
+                for command in commands {
+                    let parts =
+                        commands::check_name(command).and_then(|_| commands::parts(command));
+                    let parts = match parts {
+                        Ok(parts) => parts,
+                        Err(error) => {
+                            self.error(self.at, error.to_string());
+                            continue;
+                        }
+                    };
+                    for part in parts {
+                        let Part::Variable(name, fields) = part else {
+                            continue;
+                        };
+                        let mut var_type = self.lookup(name).cloned();
+                        for field in fields {
+                            var_type =
+                                var_type.and_then(|var_type| self.field_type(&var_type, field));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks the arms of a `match` against the variants of the enum and reports
+    /// variants no arm matches.
+    fn match_arms(&mut self, value: &Value, arms: &[MatchArm]) {
+        let at = self.at;
+        let name = match self.value(value) {
+            Some(EFSType::Enum(name) | EFSType::TaggedEnum(name)) => Some(name),
+            Some(var_type) => {
+                self.error(at, format!("cannot match on {:?}", var_type));
+                None
+            }
+            None => None,
+        };
+        let variants = name
+            .as_ref()
+            .and_then(|name| self.checker.enums.get(name.as_str()).copied());
+
+        let mut covered = HashSet::new();
+        let mut wildcard = false;
+        for arm in arms {
+            let mut scope = HashMap::new();
+            match (&arm.pattern, &name, variants) {
+                (Pattern::Wildcard, _, _) => {
+                    if wildcard || variants.is_some_and(|v| covered.len() == v.len()) {
+                        self.error(at, String::from("unreachable pattern _"));
+                    }
+                    wildcard = true;
+                }
+                (Pattern::Variant(enum_name, variant, bindings), Some(name), Some(variants)) => {
+                    let pattern = format!("{}::{}", enum_name, variant);
+                    let index = variants.iter().position(|(v, _)| v == variant);
+                    match index {
+                        _ if enum_name != name => self.error(
+                            at,
+                            format!("expected a variant of {}, found {}", name, pattern),
+                        ),
+                        None => {
+                            self.error(at, format!("enum {} has no variant {}", enum_name, variant))
+                        }
+                        Some(index) => {
+                            if wildcard || !covered.insert(index) {
+                                self.error(at, format!("unreachable pattern {}", pattern));
+                            }
+                            let payload = &variants[index].1;
+                            if bindings.len() != payload.len() {
+                                self.error(
+                                    at,
+                                    format!(
+                                        "{} has {} values but the pattern has {}",
+                                        pattern,
+                                        payload.len(),
+                                        bindings.len()
+                                    ),
+                                );
+                            }
+                            for (binding, var_type) in bindings.iter().zip(payload) {
+                                if let Some(binding) = binding {
+                                    scope.insert(binding.clone(), var_type.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            self.scopes.push(scope);
+            self.block(&arm.block);
+            self.scopes.pop();
+            self.at = at;
+        }
+
+        if let (Some(name), Some(variants), false) = (name, variants, wildcard) {
+            let missing = variants
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !covered.contains(index))
+                .map(|(_, (variant, _))| format!("{}::{}", name, variant))
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                self.error(
+                    at,
+                    format!("match on {} is missing {}", name, missing.join(", ")),
+                );
+            }
+        }
+    }
+
+    fn loop_block(&mut self, block: &CodeBlock) {
+        self.loops += 1;
+        self.block(block);
+        self.loops -= 1;
+    }
+
+    fn condition(&mut self, condition: &Value) {
+        if let Some(var_type) = self.value(condition) {
+            if var_type != EFSType::Bool {
+                self.error(
+                    self.at,
+                    format!("expected a condition of type Bool, found {:?}", var_type),
+                );
+            }
+        }
+    }
+
+    /// Checks `value` against the type it is used as. Elements of list and dict
+    /// literals are checked one by one, so constants among them can narrow too.
+    fn expect_value(&mut self, value: &Value, expected: &EFSType) {
+        match (value, expected) {
+            (Value::List(values), EFSType::List(element_type))
+                if **element_type != EFSType::None =>
+            {
+                for value in values {
+                    self.expect_value(value, element_type);
+                }
+            }
+            (Value::Dict(fields), EFSType::Dict(value_type)) if **value_type != EFSType::None => {
+                let mut keys = fields.keys().collect::<Vec<_>>();
+                keys.sort();
+                for key in keys {
+                    self.expect_value(&fields[key], value_type);
+                }
+            }
+            _ => {
+                if let Some(found) = self.value(value) {
+                    self.expect(value, &found, expected);
+                }
+            }
+        }
+    }
+
+    /// Reports `value` of type `found` not fitting into `expected`. Numbers may widen,
+    /// constant numbers also narrow as long as they fit.
+    fn expect(&mut self, value: &Value, found: &EFSType, expected: &EFSType) {
+        if assignable(found, expected) {
+            return;
+        }
+        let message = match self
+            .constant(value)
+            .map(|constant| eval::cast(constant, expected))
+        {
+            Ok(Ok(_)) => return,
+            _ if matches!(found, EFSType::Option(inner) if assignable(inner, expected)) => {
+                format!(
+                    "expected {:?}, found {:?}, unwrap it with `if let` first",
+                    expected, found
+                )
+            }
+            Ok(Err(error)) if found.is_numeric() && expected.is_numeric() => {
+                format!("{}, use `as` to convert it", error)
+            }
+            _ => format!("expected {:?}, found {:?}", expected, found),
+        };
+        self.error(self.at, message);
+    }
+
+    /// Value of `value` when it is known at compile time, locals shadow constants.
+    fn constant(&self, value: &Value) -> anyhow::Result<EFSValueType> {
+        eval::evaluate(value, &|name| {
+            if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+                return None;
+            }
+            self.checker
+                .constants
+                .get(name)
+                .map(|(_, value)| value.clone())
+        })
+    }
+
+    /// Reports writing through `target` when it does not name storage a write can
+    /// change: a constant, or a list element at an index only known at runtime.
+    fn writable(&mut self, target: &Value) {
+        match target {
+            Value::Identifier(name) => {
+                let is_local = self.scopes.iter().any(|scope| scope.contains_key(name));
+                if !is_local && self.checker.constants.contains_key(name) {
+                    self.error(self.at, format!("cannot assign to constant {}", name));
+                }
+            }
+            Value::Field(value, _) => self.writable(value),
+            Value::Index(value, index) => {
+                if self.constant(index).is_err() {
+                    let message = String::from(
+                        "list elements can only be assigned at an index known at compile time",
+                    );
+                    self.error(self.at, message);
+                }
+                self.writable(value);
+            }
+            _ => {}
+        }
+    }
+
+    /// Type of `list.method(args)`, lists have `len()` and `push(value)`.
+    fn list_method(
+        &mut self,
+        list: &Value,
+        element_type: &EFSType,
+        method: &str,
+        args: &[Value],
+    ) -> Option<EFSType> {
+        let (parameters, return_type) = match method {
+            "len" => (0, EFSType::Int),
+            "push" => {
+                self.writable(list);
+                (1, EFSType::None)
+            }
+            _ => {
+                self.error(self.at, format!("List has no method {}", method));
+                args.iter().for_each(|arg| {
+                    self.value(arg);
+                });
+                return None;
+            }
+        };
+        if args.len() != parameters {
+            self.error(
+                self.at,
+                format!(
+                    "method {} takes {} arguments but {} were given",
+                    method,
+                    parameters,
+                    args.len()
+                ),
+            );
+        }
+        for arg in args {
+            // A `List` without a type parameter takes elements of any type.
+            match element_type {
+                EFSType::None => {
+                    self.value(arg);
+                }
+                element_type => self.expect_value(arg, element_type),
+            }
+        }
+        Some(return_type)
+    }
+
+    /// Type of `field` of a struct or typed dict.
+    fn field_type(&mut self, var_type: &EFSType, field: &str) -> Option<EFSType> {
+        let field_type = match var_type {
+            EFSType::Struct(name) => self
+                .checker
+                .structs
+                .get(name.as_str())
+                .and_then(|fields| fields.get(field))
+                .cloned(),
+            EFSType::Dict(value_type) if **value_type != EFSType::None => {
+                Some(value_type.as_ref().clone())
+            }
+            EFSType::Dict(_) => {
+                let message =
+                    String::from("fields of an untyped Dict have no type, use Dict<string, V>");
+                self.error(self.at, message);
+                return None;
+            }
+            EFSType::Option(_) => {
+                let message = format!("{:?} may be None, use `?.` to access {}", var_type, field);
+                self.error(self.at, message);
+                return None;
+            }
+            _ => None,
+        };
+        if field_type.is_none() {
+            self.error(self.at, format!("{:?} has no field {}", var_type, field));
+        }
+        field_type
+    }
+
+    /// Type of the score `field` of the entities `selector` targets. `@s` only is an
+    /// entity inside an `as` block, and a score is `read` from one entity only.
+    fn score(&mut self, selector: &Value, field: &str, read: bool) -> Option<EFSType> {
+        let Some(var_type) = self.checker.scores.get(field).copied().cloned() else {
+            let message = format!(
+                "entities have no score {}, declare it with `score {}: i32 on entity;`",
+                field, field
+            );
+            self.error(self.at, message);
+            return None;
+        };
+        if let Some(text) = self.selector(selector, &format!("score {}", field)) {
+            if read && !selector::is_single(&text) {
+                let message = format!(
+                    "cannot read {} of {}, it can select more than one entity",
+                    field, text
+                );
+                self.error(self.at, message);
+            }
+            if self.executors == 0 && text.starts_with("@s") {
+                let message = format!(
+                    "{}.{} is only known inside an as block, where @s is an entity",
+                    text, field
+                );
+                self.error(self.at, message);
+            }
+        }
+        Some(var_type)
+    }
+
+    /// Text of `selector`, which `usage` needs at compile time. Selectors that depend
+    /// on a variable are reported, the variables of `static for` loops count as known.
+    /// `None` as well when the selector has an error the backend reports.
+    fn selector(&mut self, selector: &Value, usage: &str) -> Option<String> {
+        let folded = eval::fold(selector, &|name| {
+            let scope = self
+                .scopes
+                .iter()
+                .rposition(|scope| scope.contains_key(name));
+            match scope {
+                Some(scope) => self
+                    .statics
+                    .get(&scope)
+                    .map(|start| EFSValueType::Number(*start)),
+                None => self
+                    .checker
+                    .constants
+                    .get(name)
+                    .map(|(_, value)| value.clone()),
+            }
+        });
+        match folded {
+            Ok(Value::Value(EFSValueType::Selector(text) | EFSValueType::String(text))) => {
+                Some(text)
+            }
+            Ok(Value::Value(_)) | Err(_) => None,
+            Ok(_) => {
+                let message = format!("the selector of {} has to be known at compile time", usage);
+                self.error(self.at, message);
+                None
+            }
+        }
+    }
+
+    /// Checks a part of `text!` and the styles called on it.
+    fn text_part(&mut self, part: &Value) {
+        let (value, styles) = text::split(part);
+        for (style, args) in styles {
+            let parameters = text::style_parameters(style).unwrap_or_default();
+            if args.len() != parameters.len() {
+                let message = format!(
+                    "style {} takes {} arguments but {} were given",
+                    style,
+                    parameters.len(),
+                    args.len()
+                );
+                self.error(self.at, message);
+            }
+            for (arg, var_type) in args.iter().zip(parameters) {
+                self.expect_value(arg, var_type);
+            }
+        }
+        match value {
+            Value::MethodCall(receiver, method, args) if method == text::ENTITY_NBT => {
+                self.expect_value(receiver, &EFSType::Selector);
+                match args.as_slice() {
+                    [path] => self.expect_value(path, &EFSType::String),
+                    _ => {
+                        let message = format!("{} takes the path of the NBT to show", method);
+                        self.error(self.at, message);
+                    }
+                }
+            }
+            value => {
+                if self.value(value) == Some(EFSType::None) {
+                    let message = String::from("a part of text! has no value to show");
+                    self.error(self.at, message);
+                }
+            }
+        }
+    }
+
+    /// Type of `value`, `None` when an error inside it has already been reported.
+    fn value(&mut self, value: &Value) -> Option<EFSType> {
+        match value {
+            Value::Value(value) => Some(value.get_type()),
+            Value::Identifier(name) => self.lookup(name).cloned(),
+            Value::Call(name, args) => {
+                let signature = self.checker.functions.get(name.as_str()).cloned();
+                let Some(signature) = signature.or_else(|| stdlib::signature(name)) else {
+                    args.iter().for_each(|arg| {
+                        self.value(arg);
+                    });
+                    return None;
+                };
+                let FunctionSignature {
+                    parameters,
+                    return_type,
+                } = signature;
+                if args.len() != parameters.len() {
+                    self.error(
+                        self.at,
+                        format!(
+                            "function {} takes {} arguments but {} were given",
+                            name,
+                            parameters.len(),
+                            args.len()
+                        ),
+                    );
+                }
+                for (arg, (_, param_type)) in args.iter().zip(&parameters) {
+                    self.expect_value(arg, param_type);
+                }
+                Some(return_type)
+            }
+            Value::MethodCall(receiver, method, args) => {
+                let receiver_type = self.value(receiver)?;
+                if let EFSType::List(element_type) = &receiver_type {
+                    return self.list_method(receiver, element_type, method, args);
+                }
+                let signature = match &receiver_type {
+                    EFSType::Struct(name) | EFSType::Enum(name) | EFSType::TaggedEnum(name) => {
+                        let path = format!("{}::{}", name, method);
+                        match self.checker.functions.get(path.as_str()) {
+                            Some(signature) if is_method(signature) => Ok(signature.clone()),
+                            Some(_) => {
+                                Err(format!("{} is not a method, call it as {}()", path, path))
+                            }
+                            None => Err(format!("{:?} has no method {}", receiver_type, method)),
+                        }
+                    }
+                    EFSType::Selector => match selector::method_type(method) {
+                        Some(var_type) => Ok(FunctionSignature {
+                            parameters: vec![
+                                (String::from("self"), EFSType::Selector),
+                                (String::from("value"), var_type),
+                            ],
+                            return_type: EFSType::Selector,
+                        }),
+                        None => Err(format!("Selector has no method {}", method)),
+                    },
+                    EFSType::Option(_) => Err(format!(
+                        "{:?} may be None, unwrap it with `if let` to call {}",
+                        receiver_type, method
+                    )),
+                    _ => Err(format!("{:?} has no method {}", receiver_type, method)),
+                };
+                let FunctionSignature {
+                    parameters,
+                    return_type,
+                } = match signature {
+                    Ok(signature) => signature,
+                    Err(message) => {
+                        self.error(self.at, message);
+                        args.iter().for_each(|arg| {
+                            self.value(arg);
+                        });
+                        return None;
+                    }
+                };
+                if args.len() + 1 != parameters.len() {
+                    self.error(
+                        self.at,
+                        format!(
+                            "method {} takes {} arguments but {} were given",
+                            method,
+                            parameters.len() - 1,
+                            args.len()
+                        ),
+                    );
+                }
+                for (arg, (_, param_type)) in args.iter().zip(&parameters[1..]) {
+                    self.expect_value(arg, param_type);
+                }
+                Some(return_type)
+            }
+            Value::Math(math) => {
+                let left = self.value(&math.left)?;
+                let right = self.value(&math.right)?;
+                let (left, right) = (
+                    eval::operand_type(&math.left, &left, &right),
+                    eval::operand_type(&math.right, &right, &left),
+                );
+                if let Some(result) = operator_type(&math.op, &left, &right) {
+                    return Some(result);
+                }
+                let message = match (&left, &right) {
+                    // Strings are joined by `eval::fold`, there is no command for it.
+                    (EFSType::String, EFSType::String) if math.op == Operator::Plus => {
+                        if self.constant(value).is_ok() {
+                            return Some(EFSType::String);
+                        }
+                        String::from("strings can only be joined when both are known at compile time")
+                    }
+                    _ if matches!(math.op, Operator::And | Operator::Or | Operator::Xor) => {
+                        format!("bitwise {} is not supported, scores have no command for it", math.op)
+                    }
+                    (EFSType::Long, _) | (_, EFSType::Long) => format!(
+                        "cannot use {:?} on {:?} and {:?}, scores hold 32 bits so cast the Long to i32 with `as` first",
+                        math.op, left, right
+                    ),
+                    _ => format!("cannot use {:?} on {:?} and {:?}", math.op, left, right),
+                };
+                self.error(self.at, message);
+                None
+            }
+            Value::List(values) => {
+                let mut element_type = None;
+                for value in values {
+                    let Some(value_type) = self.value(value) else {
+                        continue;
+                    };
+                    match &element_type {
+                        None => element_type = Some(value_type),
+                        Some(first) if assignable(&value_type, first) => {}
+                        Some(first) if assignable(first, &value_type) => {
+                            element_type = Some(value_type)
+                        }
+                        Some(first) => {
+                            let message = format!(
+                                "list elements must have the same type, found {:?} and {:?}",
+                                first, value_type
+                            );
+                            self.error(self.at, message);
+                        }
+                    }
+                }
+                Some(EFSType::List(Box::new(
+                    element_type.unwrap_or(EFSType::None),
+                )))
+            }
+            Value::Dict(fields) => {
+                let mut keys = fields.keys().collect::<Vec<_>>();
+                keys.sort();
+                let mut value_type = None;
+                let mut mixed = false;
+                for key in keys {
+                    let Some(field_type) = self.value(&fields[key]) else {
+                        continue;
+                    };
+                    match &value_type {
+                        None => value_type = Some(field_type),
+                        Some(first) if assignable(&field_type, first) => {}
+                        Some(first) if assignable(first, &field_type) => {
+                            value_type = Some(field_type)
+                        }
+                        // Compounds may mix types, they just lose their value type.
+                        Some(_) => mixed = true,
+                    }
+                }
+                let value_type = if mixed {
+                    EFSType::None
+                } else {
+                    value_type.unwrap_or(EFSType::None)
+                };
+                Some(EFSType::Dict(Box::new(value_type)))
+            }
+            Value::Struct(name, fields) => {
+                let definition = self.checker.structs.get(name.as_str()).copied()?;
+                let mut names = fields.keys().collect::<Vec<_>>();
+                names.sort();
+                for field in names {
+                    let value = &fields[field];
+                    match definition.get(field) {
+                        Some(field_type) => self.expect_value(value, field_type),
+                        None => {
+                            self.error(self.at, format!("struct {} has no field {}", name, field))
+                        }
+                    }
+                }
+                let mut missing = definition
+                    .keys()
+                    .filter(|field| !fields.contains_key(*field))
+                    .collect::<Vec<_>>();
+                missing.sort();
+                for field in missing {
+                    self.error(
+                        self.at,
+                        format!("missing field {} of struct {}", field, name),
+                    );
+                }
+                Some(EFSType::Struct(name.clone()))
+            }
+            Value::Variant(name, variant, args) => {
+                let variants = self.checker.enums.get(name.as_str()).copied()?;
+                let Some((_, payload)) = variants.iter().find(|(v, _)| v == variant) else {
+                    self.error(self.at, format!("enum {} has no variant {}", name, variant));
+                    return None;
+                };
+                if args.len() != payload.len() {
+                    self.error(
+                        self.at,
+                        format!(
+                            "{}::{} takes {} values but {} were given",
+                            name,
+                            variant,
+                            payload.len(),
+                            args.len()
+                        ),
+                    );
+                }
+                for (arg, var_type) in args.iter().zip(payload) {
+                    self.expect_value(arg, var_type);
+                }
+                Some(resolve::enum_type(name, variants))
+            }
+            Value::Field(value, field) => match self.value(value)? {
+                EFSType::Selector => self.score(value, field, true),
+                var_type => self.field_type(&var_type, field),
+            },
+            Value::OptionalField(value, field) => match self.value(value)? {
+                EFSType::Option(var_type) => {
+                    self.field_type(&var_type, field).map(EFSType::optional)
+                }
+                var_type => {
+                    self.error(
+                        self.at,
+                        format!("{:?} is never None, use `.` to access {}", var_type, field),
+                    );
+                    None
+                }
+            },
+            Value::Index(list, index) => {
+                let list_type = self.value(list);
+                if let Some(index_type) = self.value(index) {
+                    if !assignable(&index_type, &EFSType::Int) {
+                        let message =
+                            format!("a list index must be an integer, found {:?}", index_type);
+                        self.error(self.at, message);
+                    }
+                }
+                match list_type? {
+                    EFSType::List(element_type) if *element_type == EFSType::None => {
+                        let message =
+                            String::from("elements of an untyped List have no type, use List<T>");
+                        self.error(self.at, message);
+                        None
+                    }
+                    EFSType::List(element_type) => {
+                        // A constant index into a constant list is resolved right away.
+                        if self.constant(list).is_ok() && self.constant(index).is_ok() {
+                            if let Err(error) = self.constant(value) {
+                                self.error(self.at, error.to_string());
+                            }
+                        }
+                        Some(*element_type)
+                    }
+                    var_type @ EFSType::Option(_) => {
+                        let message =
+                            format!("{:?} may be None, unwrap it with `if let` first", var_type);
+                        self.error(self.at, message);
+                        None
+                    }
+                    var_type => {
+                        self.error(self.at, format!("cannot index {:?}", var_type));
+                        None
+                    }
+                }
+            }
+            Value::Text(parts) => {
+                for part in parts {
+                    self.text_part(part);
+                }
+                Some(EFSType::Text)
+            }
+            Value::Cast(value, to) => {
+                let from = self.value(value)?;
+                let castable = from == *to
+                    || ((from.is_numeric() || from == EFSType::Bool) && to.is_numeric());
+                if !castable {
+                    self.error(self.at, format!("cannot cast {:?} to {:?}", from, to));
+                }
+                Some(to.clone())
+            }
+        }
+    }
+}
+
+/// Whether a function of an `impl` block takes `self`.
+fn is_method(signature: &FunctionSignature) -> bool {
+    signature
+        .parameters
+        .first()
+        .is_some_and(|(name, _)| name == "self")
+}
+
+/// Whether a value of type `from` can be stored in a variable of type `to`.
+fn assignable(from: &EFSType, to: &EFSType) -> bool {
+    match (from, to) {
+        _ if from == to => true,
+        (EFSType::None, EFSType::Option(_)) => true,
+        // Player names and selectors written as strings, checked when they are used.
+        (EFSType::String, EFSType::Selector) => true,
+        // A string is a text component of its own.
+        (EFSType::String, EFSType::Text) => true,
+        (EFSType::Option(from), EFSType::Option(to)) => assignable(from, to),
+        (_, EFSType::Option(to)) => assignable(from, to),
+        // `None` is an empty literal on the left and an untyped `List` or `Dict` on
+        // the right, both fit any element type.
+        (EFSType::List(from), EFSType::List(to)) | (EFSType::Dict(from), EFSType::Dict(to)) => {
+            **from == EFSType::None || **to == EFSType::None || assignable(from, to)
+        }
+        _ => from.widens_to(to),
+    }
+}
+
+/// Type of `left op right`, `None` when the operator does not apply to these types.
+fn operator_type(op: &Operator, left: &EFSType, right: &EFSType) -> Option<EFSType> {
+    // Optional values have to be unwrapped first.
+    let optional = |var_type: &EFSType| matches!(var_type, EFSType::Option(_) | EFSType::None);
+    if optional(left) || optional(right) {
+        return None;
+    }
+    // Numbers are computed in scores, which hold 32 bits, so a Long can only be
+    // compared with another Long. Floats are computed in fixed point, see
+    // `function::FLOAT_SCALE`, and bitwise operators have no command at all.
+    let long = left == &EFSType::Long || right == &EFSType::Long;
+    let numeric = left.is_numeric() && right.is_numeric() && !long;
+    match op {
+        Operator::Plus | Operator::Minus | Operator::Multi | Operator::Div if numeric => {
+            Some(left.promote(right))
+        }
+        Operator::Less | Operator::LessOrEqual | Operator::Greater | Operator::GreaterOrEqual
+            if numeric =>
+        {
+            Some(EFSType::Bool)
+        }
+        Operator::Equal | Operator::NotEqual if numeric || left == right => Some(EFSType::Bool),
+        Operator::Equal | Operator::NotEqual
+            if !left.is_numeric()
+                && !right.is_numeric()
+                && (assignable(left, right) || assignable(right, left)) =>
+        {
+            Some(EFSType::Bool)
+        }
+        Operator::LogicalAnd | Operator::LogicalOr
+            if left == &EFSType::Bool && right == &EFSType::Bool =>
+        {
+            Some(EFSType::Bool)
+        }
+        _ => None,
+    }
+}
+
+/// Whether running `block` always ends in a `return`.
+fn returns(block: &CodeBlock) -> bool {
+    block.statements.iter().any(|statement| match statement {
+        Statement::Return(_) => true,
+        // A match that misses variants is reported on its own.
+        Statement::Match(_, arms) => !arms.is_empty() && arms.iter().all(|arm| returns(&arm.block)),
+        Statement::StaticFor(_, start, end, step, block) => {
+            returns(block) && unroll::range(*start, *end, *step).is_ok_and(|r| !r.is_empty())
+        }
+        _ => false,
+    })
+}
@@ -0,0 +1,152 @@
+use anyhow::bail;
+
+/// The commands of Java Edition, a raw command has to start with one of them.
+pub const COMMANDS: &[&str] = &[
+    "advancement",
+    "attribute",
+    "ban",
+    "ban-ip",
+    "banlist",
+    "bossbar",
+    "clear",
+    "clone",
+    "damage",
+    "data",
+    "datapack",
+    "debug",
+    "defaultgamemode",
+    "deop",
+    "difficulty",
+    "effect",
+    "enchant",
+    "execute",
+    "experience",
+    "fill",
+    "fillbiome",
+    "forceload",
+    "function",
+    "gamemode",
+    "gamerule",
+    "give",
+    "help",
+    "item",
+    "jfr",
+    "kick",
+    "kill",
+    "list",
+    "locate",
+    "loot",
+    "me",
+    "msg",
+    "op",
+    "pardon",
+    "pardon-ip",
+    "particle",
+    "perf",
+    "place",
+    "playsound",
+    "publish",
+    "random",
+    "recipe",
+    "reload",
+    "return",
+    "ride",
+    "save-all",
+    "save-off",
+    "save-on",
+    "say",
+    "schedule",
+    "scoreboard",
+    "seed",
+    "setblock",
+    "setidletimeout",
+    "setworldspawn",
+    "spawnpoint",
+    "spectate",
+    "spreadplayers",
+    "stop",
+    "stopsound",
+    "summon",
+    "tag",
+    "team",
+    "teammsg",
+    "teleport",
+    "tell",
+    "tellraw",
+    "time",
+    "title",
+    "tm",
+    "tp",
+    "trigger",
+    "w",
+    "weather",
+    "whitelist",
+    "worldborder",
+    "xp",
+];
+
+/// A piece of a raw command.
+#[derive(Debug, PartialEq)]
+pub enum Part<'a> {
+    Text(&'a str),
+    /// `$(name.field.field)`, a variable and the fields read from it.
+    Variable(&'a str, Vec<&'a str>),
+}
+
+/// Splits a raw command into its text and the variables it interpolates.
+pub fn parts(command: &str) -> anyhow::Result<Vec<Part<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = command;
+    while let Some(start) = rest.find("$(") {
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let Some(length) = rest[start..].find(')') else {
+            bail!("unclosed $( in command {}", command);
+        };
+        let path = &rest[start + 2..start + length];
+        let mut segments = path.split('.');
+        let name = segments.next().unwrap_or_default();
+        let fields = segments.collect::<Vec<_>>();
+        let valid = |segment: &str| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+        };
+        if !valid(name) || !fields.iter().all(|field| valid(field)) {
+            bail!("$({}) does not name a variable", path);
+        }
+        parts.push(Part::Variable(name, fields));
+        rest = &rest[start + length + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    Ok(parts)
+}
+
+/// Checks that `command` starts with a command Minecraft knows.
+pub fn check_name(command: &str) -> anyhow::Result<()> {
+    let name = command.split_whitespace().next().unwrap_or_default();
+    if !COMMANDS.contains(&name) {
+        bail!("unknown command {}", name);
+    }
+    Ok(())
+}
+
+/// `command` with the variables renamed by `rename`.
+pub fn rename(command: &str, mut rename: impl FnMut(&str) -> String) -> anyhow::Result<String> {
+    Ok(parts(command)?
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => text.to_string(),
+            Part::Variable(name, fields) => {
+                let path = std::iter::once(rename(name))
+                    .chain(fields.into_iter().map(String::from))
+                    .collect::<Vec<_>>();
+                format!("$({})", path.join("."))
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+
+use crate::parser::{
+    ast::{Math, Value},
+    token::Operator,
+    types::{EFSType, EFSValueType},
+};
+
+use super::{selector, text};
+
+/// Replaces every part of `value` that is known at compile time with its result.
+/// `constant` resolves identifiers that name constants.
+pub fn fold(
+    value: &Value,
+    constant: &dyn Fn(&str) -> Option<EFSValueType>,
+) -> anyhow::Result<Value> {
+    Ok(match value {
+        Value::Value(_) => value.clone(),
+        Value::Identifier(name) => match constant(name) {
+            Some(value) => Value::Value(value),
+            None => value.clone(),
+        },
+        Value::Math(math) => {
+            let left = fold(&math.left, constant)?;
+            let right = fold(&math.right, constant)?;
+            match (&left, &right) {
+                (Value::Value(left), Value::Value(right)) => {
+                    Value::Value(binary(left, &math.op, right)?)
+                }
+                _ => Value::Math(Box::new(Math {
+                    left,
+                    op: math.op.clone(),
+                    right,
+                })),
+            }
+        }
+        Value::List(values) => Value::List(
+            values
+                .iter()
+                .map(|value| fold(value, constant))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Dict(fields) => Value::Dict(fold_fields(fields, constant)?),
+        Value::Struct(name, fields) => Value::Struct(name.clone(), fold_fields(fields, constant)?),
+        Value::Call(name, args) => Value::Call(
+            name.clone(),
+            args.iter()
+                .map(|arg| fold(arg, constant))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Cast(value, to) => match fold(value, constant)? {
+            Value::Value(value) => Value::Value(
+                value
+                    .convert(to)
+                    .ok_or_else(|| anyhow!("cannot cast {:?} to {:?}", value.get_type(), to))?,
+            ),
+            value => Value::Cast(Box::new(value), to.clone()),
+        },
+        Value::MethodCall(value, method, args) => {
+            let value = fold(value, constant)?;
+            let args = args
+                .iter()
+                .map(|arg| fold(arg, constant))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            match (&value, args.as_slice()) {
+                // The builder methods of a constant selector.
+                (Value::Value(EFSValueType::Selector(text)), [Value::Value(arg)])
+                    if selector::method_type(method).is_some() =>
+                {
+                    let text = selector::with_argument(text, method, arg)?;
+                    Value::Value(EFSValueType::Selector(text))
+                }
+                (Value::Value(EFSValueType::List(values)), []) if method == "len" => {
+                    Value::Value(EFSValueType::Number(values.len() as i32))
+                }
+                _ => Value::MethodCall(Box::new(value), method.clone(), args),
+            }
+        }
+        Value::Variant(name, variant, args) => Value::Variant(
+            name.clone(),
+            variant.clone(),
+            args.iter()
+                .map(|arg| fold(arg, constant))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Field(value, field) => match fold(value, constant)? {
+            // The scores of entities are only known in game.
+            Value::Value(value @ EFSValueType::Selector(_)) => {
+                Value::Field(Box::new(Value::Value(value)), field.clone())
+            }
+            Value::Value(value) => Value::Value(field_value(&value, field)?),
+            value => Value::Field(Box::new(value), field.clone()),
+        },
+        Value::OptionalField(value, field) => match fold(value, constant)? {
+            Value::Value(EFSValueType::None) => Value::Value(EFSValueType::None),
+            Value::Value(value) => Value::Value(field_value(&value, field)?),
+            value => Value::OptionalField(Box::new(value), field.clone()),
+        },
+        Value::Index(value, index) => {
+            let value = fold(value, constant)?;
+            let index = fold(index, constant)?;
+            match (&value, &index) {
+                (Value::Value(EFSValueType::List(values)), Value::Value(literal)) => {
+                    Value::Value(element(values, literal)?)
+                }
+                _ => Value::Index(Box::new(value), Box::new(index)),
+            }
+        }
+        Value::Text(parts) => {
+            let parts = parts
+                .iter()
+                .map(|part| fold(part, constant))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            match text::constant(&parts)? {
+                Some(json) => Value::Value(EFSValueType::Text(json)),
+                None => Value::Text(parts),
+            }
+        }
+    })
+}
+
+fn field_value(value: &EFSValueType, field: &str) -> anyhow::Result<EFSValueType> {
+    match value {
+        EFSValueType::Struct(_, fields) | EFSValueType::Dict(fields) => fields
+            .get(field)
+            .cloned()
+            .ok_or_else(|| anyhow!("{:?} has no field {}", value.get_type(), field)),
+        _ => bail!("cannot access field {} of {:?}", field, value.get_type()),
+    }
+}
+
+/// Element `index` of a constant list, negative indices count from the end.
+fn element(values: &[EFSValueType], index: &EFSValueType) -> anyhow::Result<EFSValueType> {
+    let index = match index.convert(&EFSType::Long) {
+        Some(EFSValueType::Long(n)) if index.get_type().is_integer() => n,
+        _ => bail!(
+            "a list index must be an integer, found {:?}",
+            index.get_type()
+        ),
+    };
+    let position = if index < 0 {
+        values.len() as i64 + index
+    } else {
+        index
+    };
+    usize::try_from(position)
+        .ok()
+        .and_then(|position| values.get(position))
+        .cloned()
+        .ok_or_else(|| {
+            anyhow!(
+                "index {} is out of range for a list of {}",
+                index,
+                values.len()
+            )
+        })
+}
+
+fn fold_fields(
+    fields: &HashMap<String, Value>,
+    constant: &dyn Fn(&str) -> Option<EFSValueType>,
+) -> anyhow::Result<HashMap<String, Value>> {
+    fields
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), fold(value, constant)?)))
+        .collect()
+}
+
+/// Evaluates `value` completely, failing on anything that needs the game to run.
+pub fn evaluate(
+    value: &Value,
+    constant: &dyn Fn(&str) -> Option<EFSValueType>,
+) -> anyhow::Result<EFSValueType> {
+    match fold(value, constant)? {
+        Value::Value(value) => Ok(value),
+        Value::Identifier(name) => bail!("{} is not a constant", name),
+        Value::Call(name, _) | Value::MethodCall(_, name, _) => {
+            bail!("cannot call {} at compile time", name)
+        }
+        Value::Math(_)
+        | Value::Cast(..)
+        | Value::Field(..)
+        | Value::OptionalField(..)
+        | Value::Index(..)
+        | Value::Variant(..)
+        | Value::Text(_) => {
+            bail!("expression is not constant")
+        }
+        Value::List(values) => {
+            let values = values
+                .iter()
+                .map(|value| evaluate(value, constant))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if let Some(first) = values.first() {
+                let element_type = first.get_type();
+                if let Some(other) = values.iter().find(|v| v.get_type() != element_type) {
+                    bail!(
+                        "list elements must have the same type, found {:?} and {:?}",
+                        element_type,
+                        other.get_type()
+                    );
+                }
+            }
+            Ok(EFSValueType::List(values))
+        }
+        Value::Dict(fields) => Ok(EFSValueType::Dict(evaluate_fields(&fields, constant)?)),
+        Value::Struct(name, fields) => Ok(EFSValueType::Struct(
+            name,
+            evaluate_fields(&fields, constant)?,
+        )),
+    }
+}
+
+fn evaluate_fields(
+    fields: &HashMap<String, Value>,
+    constant: &dyn Fn(&str) -> Option<EFSValueType>,
+) -> anyhow::Result<HashMap<String, EFSValueType>> {
+    fields
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), evaluate(value, constant)?)))
+        .collect()
+}
+
+/// Converts a constant to `to`, failing when the number does not fit instead of wrapping.
+pub fn cast(value: EFSValueType, to: &EFSType) -> anyhow::Result<EFSValueType> {
+    let found = value.get_type();
+    if &found == to {
+        return Ok(value);
+    }
+    if let EFSType::Option(var_type) = to {
+        return match value {
+            EFSValueType::None => Ok(value),
+            value => cast(value, var_type),
+        };
+    }
+    match (&value, to) {
+        (EFSValueType::List(values), EFSType::List(element_type)) => {
+            return Ok(EFSValueType::List(
+                values
+                    .iter()
+                    .map(|value| cast(value.clone(), element_type))
+                    .collect::<anyhow::Result<_>>()?,
+            ));
+        }
+        (EFSValueType::Dict(_), EFSType::Dict(value_type)) if **value_type == EFSType::None => {
+            return Ok(value);
+        }
+        (EFSValueType::Dict(fields), EFSType::Dict(value_type)) => {
+            return Ok(EFSValueType::Dict(
+                fields
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), cast(value.clone(), value_type)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ));
+        }
+        (EFSValueType::String(_), EFSType::Text) => {
+            return value
+                .convert(to)
+                .ok_or_else(|| anyhow!("cannot cast {:?} to {:?}", found, to));
+        }
+        _ => {}
+    }
+
+    let mismatch = || anyhow!("expected {:?}, found {:?}", to, found);
+    let Some(number) = Number::of(&value) else {
+        return Err(mismatch());
+    };
+    match (number, to) {
+        (Number::Int(int), EFSType::Float) => Ok(EFSValueType::Float(int as f32)),
+        (Number::Int(int), EFSType::Double) => Ok(EFSValueType::Double(int as f64)),
+        (Number::Int(int), _) => integer(int, to),
+        (Number::Float(float), EFSType::Float) => float_value(float, &EFSType::Float),
+        (Number::Float(float), EFSType::Double) => Ok(EFSValueType::Double(float)),
+        (Number::Float(_), _) => Err(mismatch()),
+    }
+}
+
+/// Type `value` of type `var_type` takes next to an operand of type `other`. Float
+/// literals are `f64` on their own but, like integer literals, take the type of the
+/// other operand, so `x + 1.5` is an `f32` when `x` is.
+pub fn operand_type(value: &Value, var_type: &EFSType, other: &EFSType) -> EFSType {
+    match (value, other) {
+        (Value::Value(EFSValueType::Double(_)), EFSType::Float) => EFSType::Float,
+        _ => var_type.clone(),
+    }
+}
+
+/// Applies `op` to two constants the way the generated commands would at runtime.
+pub fn binary(
+    left: &EFSValueType,
+    op: &Operator,
+    right: &EFSValueType,
+) -> anyhow::Result<EFSValueType> {
+    if let (EFSValueType::String(left), EFSValueType::String(right)) = (left, right) {
+        return match op {
+            Operator::Plus => Ok(EFSValueType::String(format!("{}{}", left, right))),
+            Operator::Equal => Ok(EFSValueType::Bool(left == right)),
+            Operator::NotEqual => Ok(EFSValueType::Bool(left != right)),
+            _ => bail!("cannot use {:?} on strings", op),
+        };
+    }
+    if let (EFSValueType::Bool(left), EFSValueType::Bool(right)) = (left, right) {
+        return match op {
+            Operator::LogicalAnd => Ok(EFSValueType::Bool(*left && *right)),
+            Operator::LogicalOr => Ok(EFSValueType::Bool(*left || *right)),
+            Operator::Equal => Ok(EFSValueType::Bool(left == right)),
+            Operator::NotEqual => Ok(EFSValueType::Bool(left != right)),
+            _ => bail!("cannot use {:?} on bools", op),
+        };
+    }
+    if matches!(op, Operator::Equal | Operator::NotEqual)
+        && (Number::of(left).is_none() || Number::of(right).is_none())
+    {
+        let equal = left == right;
+        return Ok(EFSValueType::Bool(equal == matches!(op, Operator::Equal)));
+    }
+
+    let (Some(left_number), Some(right_number)) = (Number::of(left), Number::of(right)) else {
+        bail!(
+            "cannot use {:?} on {:?} and {:?}",
+            op,
+            left.get_type(),
+            right.get_type()
+        );
+    };
+    let result_type = left.get_type().promote(&right.get_type());
+    match (left_number, right_number) {
+        (Number::Int(left), Number::Int(right)) if !is_float(&result_type) => {
+            let result = match op {
+                Operator::Plus => left.checked_add(right),
+                Operator::Minus => left.checked_sub(right),
+                Operator::Multi => left.checked_mul(right),
+                Operator::Div if right == 0 => bail!("division by zero"),
+                // Scoreboard division rounds towards negative infinity.
+                Operator::Div => left.checked_div(right).map(|quotient| {
+                    if left % right != 0 && (left < 0) != (right < 0) {
+                        quotient - 1
+                    } else {
+                        quotient
+                    }
+                }),
+                Operator::And => Some(left & right),
+                Operator::Or => Some(left | right),
+                Operator::Xor => Some(left ^ right),
+                _ => return compare(op, left.cmp(&right)),
+            };
+            match result {
+                Some(result) => integer(result, &result_type),
+                None => bail!("constant expression overflows {:?}", result_type),
+            }
+        }
+        (left, right) => {
+            let (left, right) = (left.as_f64(), right.as_f64());
+            let result = match op {
+                Operator::Plus => left + right,
+                Operator::Minus => left - right,
+                Operator::Multi => left * right,
+                Operator::Div if right == 0.0 => bail!("division by zero"),
+                Operator::Div => left / right,
+                Operator::And | Operator::Or | Operator::Xor => {
+                    bail!("cannot use {:?} on {:?}", op, result_type)
+                }
+                _ => match left.partial_cmp(&right) {
+                    Some(ordering) => return compare(op, ordering),
+                    None => bail!("cannot compare NaN"),
+                },
+            };
+            float_value(result, &result_type)
+        }
+    }
+}
+
+fn compare(op: &Operator, ordering: std::cmp::Ordering) -> anyhow::Result<EFSValueType> {
+    use std::cmp::Ordering::*;
+    Ok(EFSValueType::Bool(match op {
+        Operator::Equal => ordering == Equal,
+        Operator::NotEqual => ordering != Equal,
+        Operator::Less => ordering == Less,
+        Operator::LessOrEqual => ordering != Greater,
+        Operator::Greater => ordering == Greater,
+        Operator::GreaterOrEqual => ordering != Less,
+        _ => bail!("cannot use {:?} on numbers", op),
+    }))
+}
+
+fn is_float(var_type: &EFSType) -> bool {
+    matches!(var_type, EFSType::Float | EFSType::Double)
+}
+
+fn integer(value: i64, to: &EFSType) -> anyhow::Result<EFSValueType> {
+    let overflow = || anyhow!("{} overflows {:?}", value, to);
+    Ok(match to {
+        EFSType::Number => EFSValueType::Number(value.try_into().map_err(|_| overflow())?),
+        EFSType::Int => EFSValueType::Int(value.try_into().map_err(|_| overflow())?),
+        EFSType::Byte => EFSValueType::Byte(value.try_into().map_err(|_| overflow())?),
+        EFSType::Short => EFSValueType::Short(value.try_into().map_err(|_| overflow())?),
+        EFSType::Long => EFSValueType::Long(value),
+        _ => bail!("expected {:?} but found a number", to),
+    })
+}
+
+fn float_value(value: f64, to: &EFSType) -> anyhow::Result<EFSValueType> {
+    match to {
+        EFSType::Float if (value as f32).is_infinite() => {
+            bail!("constant expression overflows Float")
+        }
+        EFSType::Float => Ok(EFSValueType::Float(value as f32)),
+        _ if value.is_infinite() => bail!("constant expression overflows Double"),
+        _ => Ok(EFSValueType::Double(value)),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn of(value: &EFSValueType) -> Option<Self> {
+        Some(match value {
+            EFSValueType::Number(n) | EFSValueType::Int(n) => Number::Int(*n as i64),
+            EFSValueType::Byte(n) => Number::Int(*n as i64),
+            EFSValueType::Short(n) => Number::Int(*n as i64),
+            EFSValueType::Long(n) => Number::Int(*n),
+            EFSValueType::Float(n) => Number::Float(*n as f64),
+            EFSValueType::Double(n) => Number::Float(*n),
+            _ => return None,
+        })
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(n) => n,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::bail;
+
+use crate::{
+    compiler::{is_resource_location, selector, stdlib},
+    parser::{
+        ast::{CodeBlock, Context, Value},
+        types::EFSValueType,
+    },
+};
+
+use super::{Exits, FunctionCompiler};
+
+impl FunctionCompiler<'_> {
+    /// Lowers `as @a at @s { ... }` to `execute as @a at @s run function <block>`.
+    pub(super) fn execute(
+        &mut self,
+        contexts: &[Context],
+        block: &CodeBlock,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Exits> {
+        let mut subcommands = Vec::new();
+        for context in contexts {
+            subcommands.push(match context {
+                Context::As(selector) => format!("as {}", self.selector(selector)?),
+                Context::At(selector) => format!("at {}", self.selector(selector)?),
+                Context::Positioned(position) if stdlib::is_position(position) => {
+                    format!("positioned {}", position)
+                }
+                Context::Positioned(position) => bail!("{} is not a position", position),
+                Context::In(dimension) => {
+                    let location = match dimension.contains(':') {
+                        true => dimension.clone(),
+                        false => format!("minecraft:{}", dimension),
+                    };
+                    if !is_resource_location(&location) {
+                        bail!("{} is not a valid dimension", dimension);
+                    }
+                    format!("in {}", location)
+                }
+            });
+        }
+        let kind = match contexts.first() {
+            Some(Context::As(_)) => "as",
+            Some(Context::At(_)) => "at",
+            Some(Context::Positioned(_)) => "positioned",
+            _ => "in",
+        };
+        let (function, exits) = self.nested_block(kind, block)?;
+        commands.push(format!(
+            "execute {} run function {}",
+            subcommands.join(" "),
+            function
+        ));
+        Ok(self.unwind(exits, commands))
+    }
+
+    /// Text of a selector, which has to be known at compile time.
+    pub(super) fn selector(&self, value: &Value) -> anyhow::Result<String> {
+        match self.fold(value)? {
+            Value::Value(EFSValueType::Selector(text) | EFSValueType::String(text))
+                if selector::is_selector(&text) =>
+            {
+                Ok(text)
+            }
+            Value::Value(EFSValueType::Selector(text) | EFSValueType::String(text)) => {
+                bail!("{} is not a selector or player name", text)
+            }
+            _ => bail!("selectors have to be known at compile time"),
+        }
+    }
+}
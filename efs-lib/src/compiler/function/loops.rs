@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::parser::{
+    ast::{CodeBlock, Statement, Value},
+    types::EFSType,
+};
+
+use super::{Control, Exits, FunctionCompiler, Location};
+
+/// A loop being lowered. Every iteration runs in a helper function that calls
+/// itself again at the end, so `break` is a `return` out of the current iteration.
+pub(super) struct LoopContext {
+    /// Depth of the loop body, blocks deeper than this unwind through the control flag.
+    pub depth: usize,
+    /// Function that starts the next iteration, run by `continue`.
+    pub next: String,
+    /// Whether `next` has to clear a `continue` left in the control flag.
+    pub resets: bool,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    /// `while (condition) {}` runs the loop function `while<n>`, which checks the
+    /// condition, runs the body and calls itself.
+    pub(super) fn while_loop(
+        &mut self,
+        condition: &Value,
+        block: &CodeBlock,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Exits> {
+        let function = self.helper("while");
+        let (mut body, exits) = self.loop_body(function.clone(), |this, body| {
+            let condition = this.score_operand(condition, body)?;
+            body.push(format!(
+                "execute if score {} matches 0 run return 0",
+                condition
+            ));
+            let exits = this.block(block, body)?;
+            body.push(format!("function {}", function));
+            Ok(exits)
+        })?;
+        if exits.resets {
+            body.insert(0, self.compiler.allocator.control().set(0));
+        }
+        self.compiler
+            .datapack
+            .functions
+            .insert(function.clone(), body);
+
+        commands.push(format!("function {}", function));
+        Ok(self.unwind(exits.exits, commands))
+    }
+
+    /// `for (init; condition; step) {}` runs `init` in place and then the loop
+    /// function `for<n>`. When the body uses `continue` the step gets a function
+    /// of its own, `for<n>_step`, so `continue` can skip the rest of the body.
+    pub(super) fn for_loop(
+        &mut self,
+        init: &Statement,
+        condition: &Value,
+        step: &Statement,
+        block: &CodeBlock,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Exits> {
+        self.scopes.push(HashMap::new());
+        let exits = self.statement(init, commands)?;
+
+        let function = self.helper("for");
+        let step_function = continues(block).then(|| format!("{}_step", function));
+        let next = step_function.clone().unwrap_or_else(|| function.clone());
+
+        let (body, loop_exits) = self.loop_body(next, |this, body| {
+            let condition = this.score_operand(condition, body)?;
+            body.push(format!(
+                "execute if score {} matches 0 run return 0",
+                condition
+            ));
+            let exits = this.block(block, body)?;
+            match &step_function {
+                Some(step_function) => body.push(format!("function {}", step_function)),
+                None => {
+                    this.statement(step, body)?;
+                    body.push(format!("function {}", function));
+                }
+            }
+            Ok(exits)
+        })?;
+        if let Some(step_function) = step_function {
+            let mut step_commands = Vec::new();
+            if loop_exits.resets {
+                step_commands.push(self.compiler.allocator.control().set(0));
+            }
+            self.depth += 1;
+            let step_exits = self.statement(step, &mut step_commands);
+            self.depth -= 1;
+            step_exits?;
+            step_commands.push(format!("function {}", function));
+            self.compiler
+                .datapack
+                .functions
+                .insert(step_function, step_commands);
+        }
+        self.compiler
+            .datapack
+            .functions
+            .insert(function.clone(), body);
+        self.scopes.pop();
+
+        commands.push(format!("function {}", function));
+        Ok(exits.union(self.unwind(loop_exits.exits, commands)))
+    }
+
+    /// `for (item in list) {}` iterates over a copy of the list, the loop function
+    /// `for_in<n>` moves its first element into `item` until the copy is empty. The
+    /// copy of a constant list is written with its value.
+    pub(super) fn for_list(
+        &mut self,
+        item: &str,
+        list: &str,
+        block: &CodeBlock,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Exits> {
+        let source = Value::Identifier(list.to_string());
+        let var_type = self.value_type(&source)?;
+        let EFSType::List(element_type) = &var_type else {
+            bail!("cannot iterate over {} of type {:?}", list, var_type);
+        };
+        let element_type = element_type.as_ref().clone();
+
+        let remaining = match self.allocate(&var_type) {
+            Location::Storage(remaining) => remaining,
+            Location::Score(_) => unreachable!("lists are kept in storage"),
+        };
+        self.store(
+            &source,
+            &Location::Storage(remaining.clone()),
+            &var_type,
+            commands,
+        )?;
+
+        let function = self.helper("for_in");
+        let (mut body, exits) = self.loop_body(function.clone(), |this, body| {
+            let first = remaining.index(0);
+            body.push(format!("execute unless data {} run return 0", first));
+            let location = this.declare(item, &element_type);
+            body.extend(location.assign_from(&Location::Storage(first.clone()), &element_type));
+            body.push(first.remove());
+            let exits = this.block(block, body)?;
+            body.push(format!("function {}", function));
+            Ok(exits)
+        })?;
+        if exits.resets {
+            body.insert(0, self.compiler.allocator.control().set(0));
+        }
+        self.compiler
+            .datapack
+            .functions
+            .insert(function.clone(), body);
+
+        commands.push(format!("function {}", function));
+        Ok(self.unwind(exits.exits, commands))
+    }
+
+    pub(super) fn break_loop(&mut self, commands: &mut Vec<String>) -> anyhow::Result<Exits> {
+        let Some(context) = self.loops.last() else {
+            bail!("break outside of a loop");
+        };
+        if context.depth == self.depth {
+            commands.push(String::from("return 0"));
+            return Ok(Exits::default());
+        }
+        let control = self.compiler.allocator.control();
+        commands.push(control.set(Control::Break as i32));
+        commands.push(String::from("return 0"));
+        Ok(Exits {
+            breaks: true,
+            ..Exits::default()
+        })
+    }
+
+    pub(super) fn continue_loop(&mut self, commands: &mut Vec<String>) -> anyhow::Result<Exits> {
+        let Some(context) = self.loops.last() else {
+            bail!("continue outside of a loop");
+        };
+        if context.depth == self.depth {
+            commands.push(format!("return run function {}", context.next));
+            return Ok(Exits::default());
+        }
+        let control = self.compiler.allocator.control();
+        commands.push(control.set(Control::Continue as i32));
+        commands.push(String::from("return 0"));
+        Ok(Exits {
+            continues: true,
+            ..Exits::default()
+        })
+    }
+
+    /// Lowers the commands of a loop function one block deeper than the loop.
+    fn loop_body(
+        &mut self,
+        next: String,
+        lower: impl FnOnce(&mut Self, &mut Vec<String>) -> anyhow::Result<Exits>,
+    ) -> anyhow::Result<(Vec<String>, LoopExits)> {
+        let mut body = Vec::new();
+        self.scopes.push(HashMap::new());
+        self.depth += 1;
+        self.loops.push(LoopContext {
+            depth: self.depth,
+            next,
+            resets: false,
+        });
+        let exits = lower(self, &mut body);
+        let context = self.loops.pop().unwrap();
+        self.depth -= 1;
+        self.scopes.pop();
+
+        // `break` and `continue` never leave the loop, only `return` does.
+        let exits = Exits {
+            returns: exits?.returns,
+            ..Exits::default()
+        };
+        Ok((
+            body,
+            LoopExits {
+                exits,
+                resets: context.resets,
+            },
+        ))
+    }
+}
+
+struct LoopExits {
+    exits: Exits,
+    resets: bool,
+}
+
+/// Whether `block` has a `continue` that belongs to the loop it is the body of.
+/// `static for` and execute blocks are not loops at runtime, so one in them counts.
+fn continues(block: &CodeBlock) -> bool {
+    block.statements.iter().any(|statement| match statement {
+        Statement::Continue => true,
+        Statement::If(_, block)
+        | Statement::IfLet(_, _, block)
+        | Statement::StaticFor(_, _, _, _, block)
+        | Statement::Execute(_, block) => continues(block),
+        Statement::Match(_, arms) => arms.iter().any(|arm| continues(&arm.block)),
+        _ => false,
+    })
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::parser::{
+    ast::{MatchArm, Pattern, Value},
+    types::EFSType,
+};
+
+use super::{variant_index, Exits, FunctionCompiler, Location};
+
+impl<'a> FunctionCompiler<'a> {
+    /// `match (value) {}` copies the index of the variant into a score and runs
+    /// each arm in a helper function `match<n>` behind `execute if score matches`.
+    /// A `_` arm gets every variant no arm before it matched.
+    pub(super) fn match_arms(
+        &mut self,
+        value: &Value,
+        arms: &[MatchArm],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Exits> {
+        let (location, var_type) = self.value(value, commands)?;
+        let (EFSType::Enum(name) | EFSType::TaggedEnum(name)) = &var_type else {
+            bail!("cannot match on {:?}", var_type);
+        };
+        let variants = self.variants(name)?.to_vec();
+
+        // An arm may assign to the matched variable, so the arms after it check a copy.
+        let index = self.allocate_score();
+        match (&location, &var_type) {
+            (Location::Score(score), _) => commands.push(index.copy_from(score)),
+            (Location::Storage(path), EFSType::TaggedEnum(_)) => {
+                commands.push(path.field("variant").store_to_score(&index, 1.0))
+            }
+            (Location::Storage(path), _) => commands.push(path.store_to_score(&index, 1.0)),
+        }
+
+        let mut remaining = (0..variants.len()).collect::<Vec<_>>();
+        let mut exits = Exits::default();
+        for arm in arms {
+            self.scopes.push(HashMap::new());
+            let matched = match &arm.pattern {
+                Pattern::Wildcard => std::mem::take(&mut remaining),
+                Pattern::Variant(_, variant, bindings) => {
+                    let variant = variant_index(name, &variants, variant)?;
+                    remaining.retain(|remaining| *remaining != variant);
+                    let payload = bindings.iter().zip(&variants[variant].1).enumerate();
+                    for (position, (binding, var_type)) in payload {
+                        let (Some(binding), Location::Storage(path)) = (binding, &location) else {
+                            continue;
+                        };
+                        let source = Location::Storage(path.field(&format!("_{}", position)));
+                        let target = self.declare(binding, var_type);
+                        for command in target.assign_from(&source, var_type) {
+                            commands.push(format!(
+                                "execute if score {} matches {} run {}",
+                                index, variant, command
+                            ));
+                        }
+                    }
+                    vec![variant]
+                }
+            };
+            let nested = self.nested_block("match", &arm.block);
+            self.scopes.pop();
+            let (function, arm_exits) = nested?;
+            for range in ranges(&matched) {
+                commands.push(format!(
+                    "execute if score {} matches {} run function {}",
+                    index, range, function
+                ));
+            }
+            exits = exits.union(arm_exits);
+        }
+        Ok(self.unwind(exits, commands))
+    }
+}
+
+/// `matches` ranges covering the sorted `indices`, like `0` or `2..4`.
+fn ranges(indices: &[usize]) -> Vec<String> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < indices.len() {
+        let mut end = start;
+        while indices.get(end + 1) == Some(&(indices[end] + 1)) {
+            end += 1;
+        }
+        ranges.push(if start == end {
+            indices[start].to_string()
+        } else {
+            format!("{}..{}", indices[start], indices[end])
+        });
+        start = end + 1;
+    }
+    ranges
+}
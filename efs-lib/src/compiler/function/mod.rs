@@ -0,0 +1,1407 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::parser::{
+    ast::{CodeBlock, Expression, Math, Statement, Value},
+    token::Operator,
+    types::{EFSType, EFSValueType},
+};
+
+use super::{
+    eval, recursion, resolve, scoreboard::Score, stdlib, storage::StoragePath, unroll, Compiler,
+    Location,
+};
+
+use self::loops::LoopContext;
+
+mod execute;
+mod loops;
+mod matching;
+mod raw;
+mod text;
+
+/// Floats are computed in scores as fixed point numbers with three decimals, so
+/// their operands have to stay within about ±2 million and products within ±2000.
+/// A division scales its dividend up by another `FLOAT_SCALE` first, which no
+/// longer fits into a score once the dividend is outside of about ±2147.483.
+pub(super) const FLOAT_SCALE: f64 = 1000.0;
+
+#[derive(Clone)]
+pub struct Variable {
+    pub var_type: EFSType,
+    pub location: Location,
+}
+
+/// Lowers the body of one EFS function into its mcfunction and the helper
+/// functions generated for nested blocks.
+///
+/// Calls follow a register convention: the caller writes arguments to
+/// `Allocator::argument`, runs the callee and copies `Allocator::return_value`
+/// into a temporary. The callee copies its arguments into its own locals first,
+/// so nested calls can reuse the registers. A method that may change `self`
+/// leaves it in `Allocator::receiver`, which the caller copies back.
+pub(super) struct FunctionCompiler<'a> {
+    compiler: &'a mut Compiler,
+    name: String,
+    return_type: EFSType,
+    scopes: Vec<HashMap<String, Variable>>,
+    depth: usize,
+    blocks: usize,
+    locals: Vec<Location>,
+    loops: Vec<LoopContext>,
+}
+
+/// Values of `Allocator::control` while control flow unwinds through nested blocks.
+#[derive(Clone, Copy)]
+enum Control {
+    Return = 1,
+    Break = 2,
+    Continue = 3,
+}
+
+/// Control flow that may unwind out of a block.
+#[derive(Clone, Copy, Default)]
+struct Exits {
+    returns: bool,
+    breaks: bool,
+    continues: bool,
+}
+
+impl Exits {
+    fn union(self, other: Exits) -> Exits {
+        Exits {
+            returns: self.returns || other.returns,
+            breaks: self.breaks || other.breaks,
+            continues: self.continues || other.continues,
+        }
+    }
+}
+
+impl<'a> FunctionCompiler<'a> {
+    pub fn compile(
+        compiler: &'a mut Compiler,
+        name: &str,
+        code_block: &CodeBlock,
+    ) -> anyhow::Result<()> {
+        let signature = compiler.functions[name].clone();
+        let mut function = Self::new(compiler, name, signature.return_type);
+
+        let mut commands = Vec::new();
+        for (index, (param, param_type)) in signature.parameters.iter().enumerate() {
+            let location = function.declare(param, param_type);
+            let register = function.compiler.allocator.argument(index, param_type);
+            commands.extend(location.assign_from(&register, param_type));
+        }
+        function
+            .block(code_block, &mut commands)
+            .with_context(|| format!("in function {}", name))?;
+
+        let location = function.compiler.function_location(name);
+        let recursive = function.compiler.recursive.contains(name);
+        // `self` is handed back once the body has returned, wherever it did.
+        let write_back = match function.compiler.mutating.contains(name) {
+            true => {
+                let receiver = function.lookup("self")?.clone();
+                let register = function.compiler.allocator.receiver(&receiver.var_type);
+                register.assign_from(&receiver.location, &receiver.var_type)
+            }
+            false => Vec::new(),
+        };
+        if recursive || !write_back.is_empty() {
+            let body = function.compiler.helper_location(name, "body");
+            let stack = function.compiler.allocator.call_stack();
+
+            let mut wrapper = Vec::new();
+            if recursive {
+                wrapper.extend(recursion::push_frame(&stack, &function.locals));
+            }
+            wrapper.push(format!("function {}", body));
+            wrapper.extend(write_back);
+            if recursive {
+                wrapper.extend(recursion::pop_frame(&stack, &function.locals));
+            }
+
+            function.compiler.datapack.functions.insert(body, commands);
+            function
+                .compiler
+                .datapack
+                .functions
+                .insert(location, wrapper);
+        } else {
+            function
+                .compiler
+                .datapack
+                .functions
+                .insert(location, commands);
+        }
+        Ok(())
+    }
+
+    fn new(compiler: &'a mut Compiler, name: &str, return_type: EFSType) -> Self {
+        Self {
+            compiler,
+            name: name.to_string(),
+            return_type,
+            scopes: vec![HashMap::new()],
+            depth: 0,
+            blocks: 0,
+            locals: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, var_type: &EFSType) -> Location {
+        let location = self.compiler.allocator.allocate(var_type);
+        self.locals.push(location.clone());
+        location
+    }
+
+    fn allocate_score(&mut self) -> Score {
+        let score = self.compiler.allocator.scoreboard.allocate();
+        self.locals.push(Location::Score(score.clone()));
+        score
+    }
+
+    fn declare(&mut self, name: &str, var_type: &EFSType) -> Location {
+        let location = self.allocate(var_type);
+        self.scopes.last_mut().unwrap().insert(
+            name.to_string(),
+            Variable {
+                var_type: var_type.clone(),
+                location: location.clone(),
+            },
+        );
+        location
+    }
+
+    /// Folds the parts of `value` known at compile time, constants shadowed by a
+    /// local are left alone.
+    fn fold(&self, value: &Value) -> anyhow::Result<Value> {
+        eval::fold(value, &|name| {
+            if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+                return None;
+            }
+            self.compiler.constants.get(name).cloned()
+        })
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<&Variable> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.compiler.bindings.get(name))
+            .ok_or_else(|| anyhow!("unknown variable {}", name))
+    }
+
+    /// Lowers `block` in place, returns the control flow that may unwind out of it.
+    fn block(&mut self, block: &CodeBlock, commands: &mut Vec<String>) -> anyhow::Result<Exits> {
+        let mut exits = Exits::default();
+        for statement in &block.statements {
+            exits = exits.union(self.statement(statement, commands)?);
+        }
+        Ok(exits)
+    }
+
+    /// Name of the next helper function generated for this function.
+    fn helper(&mut self, kind: &str) -> String {
+        let location = self
+            .compiler
+            .helper_location(&self.name, &format!("{}{}", kind, self.blocks));
+        self.blocks += 1;
+        location
+    }
+
+    /// Lowers `block` into a helper function of its own and returns its resource location.
+    fn nested_block(&mut self, kind: &str, block: &CodeBlock) -> anyhow::Result<(String, Exits)> {
+        let location = self.helper(kind);
+
+        let mut commands = Vec::new();
+        self.scopes.push(HashMap::new());
+        self.depth += 1;
+        let exits = self.block(block, &mut commands);
+        self.depth -= 1;
+        self.scopes.pop();
+
+        self.compiler
+            .datapack
+            .functions
+            .insert(location.clone(), commands);
+        Ok((location, exits?))
+    }
+
+    /// Checks the control flag after a helper function returned. Control flow that
+    /// belongs to the current block is finished here, the rest keeps unwinding.
+    fn unwind(&mut self, exits: Exits, commands: &mut Vec<String>) -> Exits {
+        let control = self.compiler.allocator.control();
+        let at_loop = self.loops.last().is_some_and(|l| l.depth == self.depth);
+        let mut unwinding = Exits::default();
+
+        if exits.returns {
+            if self.depth == 0 {
+                commands.push(format!(
+                    "execute if score {} matches {} run return run {}",
+                    control,
+                    Control::Return as i32,
+                    control.set(0)
+                ));
+            } else {
+                commands.push(format!(
+                    "execute if score {} matches {} run return 0",
+                    control,
+                    Control::Return as i32
+                ));
+                unwinding.returns = true;
+            }
+        }
+        if exits.breaks {
+            if at_loop {
+                commands.push(format!(
+                    "execute if score {} matches {} run return run {}",
+                    control,
+                    Control::Break as i32,
+                    control.set(0)
+                ));
+            } else {
+                commands.push(format!(
+                    "execute if score {} matches {} run return 0",
+                    control,
+                    Control::Break as i32
+                ));
+                unwinding.breaks = true;
+            }
+        }
+        if exits.continues {
+            if at_loop {
+                // The flag is cleared by the first command of `next`.
+                let context = self.loops.last_mut().unwrap();
+                context.resets = true;
+                commands.push(format!(
+                    "execute if score {} matches {} run return run function {}",
+                    control,
+                    Control::Continue as i32,
+                    context.next
+                ));
+            } else {
+                commands.push(format!(
+                    "execute if score {} matches {} run return 0",
+                    control,
+                    Control::Continue as i32
+                ));
+                unwinding.continues = true;
+            }
+        }
+        unwinding
+    }
+
+    fn statement(
+        &mut self,
+        statement: &Statement,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Exits> {
+        match statement {
+            Statement::Expression(Expression::VarDec(name, var_type, value)) => {
+                let var_type = match var_type {
+                    Some(var_type) => var_type.clone(),
+                    None => self.value_type(value)?,
+                };
+                let location = self.allocate(&var_type);
+                self.store(value, &location, &var_type, commands)?;
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.clone(), Variable { var_type, location });
+            }
+            Statement::Expression(Expression::Assign(name, value)) => {
+                let is_local = self.scopes.iter().any(|scope| scope.contains_key(name));
+                if !is_local && self.compiler.constants.contains_key(name) {
+                    bail!("cannot assign to constant {}", name);
+                }
+                let variable = self.lookup(name)?.clone();
+                self.store(value, &variable.location, &variable.var_type, commands)?;
+            }
+            Statement::Expression(Expression::AssignField(target, field, op, value))
+                if self.value_type(target)? == EFSType::Selector =>
+            {
+                let (score, var_type) = self.entity_score(target, field)?;
+                match op {
+                    Some(op) => {
+                        let value = self.fold(value)?;
+                        self.operate(&score, op, &value, commands)?;
+                    }
+                    None => self.store(value, &Location::Score(score), &var_type, commands)?,
+                }
+            }
+            Statement::Expression(Expression::AssignField(target, field, op, value)) => {
+                let (path, var_type) = self.field(target, field, commands)?;
+                let var_type = self.field_type(&var_type, field)?;
+                match op {
+                    // Integer fields are updated through a score.
+                    Some(op) => {
+                        let value = self.fold(value)?;
+                        let score = self.allocate_score();
+                        commands.push(path.store_to_score(&score, 1.0));
+                        self.operate(&score, op, &value, commands)?;
+                        let nbt_type = var_type.store_type().unwrap_or("int");
+                        commands.push(path.store_from_score(&score, nbt_type, 1.0));
+                    }
+                    None => self.store(value, &Location::Storage(path), &var_type, commands)?,
+                }
+            }
+            Statement::Expression(Expression::Value(Value::Call(name, args))) => {
+                self.call(name, args, commands)?;
+            }
+            Statement::Expression(Expression::Value(Value::MethodCall(receiver, method, args))) => {
+                self.method_call(receiver, method, args, commands)?;
+            }
+            Statement::Expression(Expression::Value(value)) => {
+                self.value(value, commands)?;
+            }
+            Statement::If(condition, block) => {
+                let condition = self.score_operand(condition, commands)?;
+                let (function, exits) = self.nested_block("if", block)?;
+                commands.push(format!(
+                    "execute unless score {} matches 0 run function {}",
+                    condition, function
+                ));
+                return Ok(self.unwind(exits, commands));
+            }
+            Statement::IfLet(name, value, block) => {
+                let (source, var_type) = self.value(value, commands)?;
+                let EFSType::Option(var_type) = var_type else {
+                    bail!("if let needs an optional value, found {:?}", var_type);
+                };
+                self.scopes.push(HashMap::new());
+                let location = self.declare(name, &var_type);
+                commands.push(format!(
+                    "execute {} run {}",
+                    source.present(),
+                    location.copy_from(&source, &var_type)
+                ));
+                let nested = self.nested_block("if", block);
+                self.scopes.pop();
+                let (function, exits) = nested?;
+                commands.push(format!(
+                    "execute {} run function {}",
+                    source.present(),
+                    function
+                ));
+                return Ok(self.unwind(exits, commands));
+            }
+            Statement::Match(value, arms) => return self.match_arms(value, arms, commands),
+            Statement::Return(value) => {
+                match (value, &self.return_type) {
+                    (None, EFSType::None) => {}
+                    (Some(value), return_type) if return_type != &EFSType::None => {
+                        let return_type = return_type.clone();
+                        let register = self.compiler.allocator.return_value(&return_type);
+                        self.store(value, &register, &return_type, commands)?;
+                    }
+                    (Some(_), _) => bail!("function {} does not return a value", self.name),
+                    (None, return_type) => {
+                        bail!("function {} must return a {:?}", self.name, return_type)
+                    }
+                }
+                if self.depth > 0 {
+                    let control = self.compiler.allocator.control();
+                    commands.push(control.set(Control::Return as i32));
+                }
+                commands.push(String::from("return 0"));
+                return Ok(Exits {
+                    returns: true,
+                    ..Exits::default()
+                });
+            }
+            Statement::Commands(raw) => self.raw_commands(raw, commands)?,
+            Statement::Execute(contexts, block) => return self.execute(contexts, block, commands),
+            Statement::Break => return self.break_loop(commands),
+            Statement::Continue => return self.continue_loop(commands),
+            Statement::While(condition, block) => {
+                return self.while_loop(condition, block, commands)
+            }
+            Statement::For(init, condition, step, block) => {
+                return self.for_loop(init, condition, step, block, commands)
+            }
+            Statement::ForList(item, list, block) => {
+                return self.for_list(item, list, block, commands)
+            }
+            Statement::StaticFor(name, start, end, step, block) => {
+                let mut exits = Exits::default();
+                for value in unroll::range(*start, *end, *step)? {
+                    let block = unroll::substitute(block, name, value)?;
+                    self.scopes.push(HashMap::new());
+                    let block_exits = self.block(&block, commands);
+                    self.scopes.pop();
+                    exits = exits.union(block_exits?);
+                }
+                return Ok(exits);
+            }
+        }
+        Ok(Exits::default())
+    }
+
+    /// Emits a call to `name`, returning where its result was copied to.
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Option<(Location, EFSType)>> {
+        if stdlib::is_builtin(name) {
+            self.builtin(name, args, commands)?;
+            return Ok(None);
+        }
+        let signature = self
+            .compiler
+            .functions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown function {}", name))?;
+        if args.len() != signature.parameters.len() {
+            bail!(
+                "function {} takes {} arguments but {} were given",
+                name,
+                signature.parameters.len(),
+                args.len()
+            );
+        }
+
+        // An `extern fn` takes its arguments as macro arguments and returns its
+        // result as the return value of the function command.
+        let external = self.compiler.externs.get(name).cloned();
+        let registers = signature
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(index, (param, param_type))| match external {
+                Some(_) => {
+                    Location::Storage(self.compiler.allocator.macro_arguments().field(param))
+                }
+                None => self.compiler.allocator.argument(index, param_type),
+            })
+            .collect::<Vec<_>>();
+
+        // Arguments that call functions themselves would clobber the registers of
+        // earlier arguments, so those are evaluated into temporaries first.
+        if args.iter().any(contains_call) {
+            let mut temps = Vec::new();
+            for arg in args {
+                temps.push(self.value(arg, commands)?.0);
+            }
+            for ((temp, register), (_, param_type)) in
+                temps.iter().zip(&registers).zip(&signature.parameters)
+            {
+                commands.extend(register.assign_from(temp, param_type));
+            }
+        } else {
+            for ((arg, register), (_, param_type)) in
+                args.iter().zip(&registers).zip(&signature.parameters)
+            {
+                self.store(arg, register, param_type, commands)?;
+            }
+        }
+
+        let register = self.compiler.allocator.return_value(&signature.return_type);
+        let call = match &external {
+            Some(location) if args.is_empty() => format!("function {}", location),
+            Some(location) => format!(
+                "function {} with {}",
+                location,
+                self.compiler.allocator.macro_arguments()
+            ),
+            None => format!("function {}", self.compiler.function_location(name)),
+        };
+        match (&external, &register) {
+            (Some(_), Location::Score(score)) if signature.return_type != EFSType::None => {
+                commands.push(format!("execute store result score {} run {}", score, call))
+            }
+            _ => commands.push(call),
+        }
+
+        if signature.return_type == EFSType::None {
+            return Ok(None);
+        }
+        let temp = self.allocate(&signature.return_type);
+        commands.extend(temp.assign_from(&register, &signature.return_type));
+        Ok(Some((temp, signature.return_type)))
+    }
+
+    /// Emits the command a `std::cmd` function lowers to. Arguments only known at
+    /// runtime are passed like those of an `extern fn`, the command then runs in a
+    /// helper function as a macro.
+    fn builtin(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let signature =
+            stdlib::signature(name).ok_or_else(|| anyhow!("unknown function {}", name))?;
+        if args.len() != signature.parameters.len() {
+            bail!(
+                "function {} takes {} arguments but {} were given",
+                name,
+                signature.parameters.len(),
+                args.len()
+            );
+        }
+        let mut values = Vec::new();
+        let mut runtime = Vec::new();
+        for (arg, (param, param_type)) in args.iter().zip(&signature.parameters) {
+            let value = match self.fold(arg)? {
+                Value::Value(value) => value,
+                Value::Text(parts) => EFSValueType::Text(self.text(&parts, commands)?),
+                value if param_type == &EFSType::Text => {
+                    EFSValueType::Text(self.runtime_text(&value, commands)?)
+                }
+                value => {
+                    let (location, _) = self.value(&value, commands)?;
+                    runtime.push((param, param_type, location));
+                    values.push(None);
+                    continue;
+                }
+            };
+            values.push(Some(value));
+        }
+        let command = stdlib::lower(name, &values)?;
+        if runtime.is_empty() {
+            commands.push(command);
+            return Ok(());
+        }
+
+        // Every argument is evaluated before the first is written, so a call in a
+        // later one can not overwrite the macro arguments.
+        let arguments = self.compiler.allocator.macro_arguments();
+        for (param, param_type, location) in runtime {
+            let register = Location::Storage(arguments.field(param));
+            commands.extend(register.assign_from(&location, param_type));
+        }
+        let helper = self.helper(name.rsplit("::").next().unwrap_or(name));
+        self.compiler
+            .datapack
+            .functions
+            .insert(helper.clone(), vec![format!("${}", command)]);
+        commands.push(format!("function {} with {}", helper, arguments));
+        Ok(())
+    }
+
+    /// Emits `receiver.method(args)`, a call with the receiver as the first argument.
+    /// A receiver the method may change is written back when it names a variable.
+    fn method_call(
+        &mut self,
+        receiver: &Value,
+        method: &str,
+        args: &[Value],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Option<(Location, EFSType)>> {
+        if let EFSType::List(element_type) = self.value_type(receiver)? {
+            return self.list_method(receiver, &element_type, method, args, commands);
+        }
+        let name = self.method_name(receiver, method)?;
+        let args = std::iter::once(receiver.clone())
+            .chain(args.iter().cloned())
+            .collect::<Vec<_>>();
+        let result = self.call(&name, &args, commands)?;
+        if self.compiler.mutating.contains(&name) && is_place(receiver) {
+            let (location, var_type) = self.value(receiver, commands)?;
+            let register = self.compiler.allocator.receiver(&var_type);
+            commands.extend(location.assign_from(&register, &var_type));
+        }
+        Ok(result)
+    }
+
+    /// Emits `list.len()` or `list.push(value)`, which work on the list in place.
+    fn list_method(
+        &mut self,
+        list: &Value,
+        element_type: &EFSType,
+        method: &str,
+        args: &[Value],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Option<(Location, EFSType)>> {
+        let Location::Storage(path) = self.value(list, commands)?.0 else {
+            unreachable!("lists are kept in storage")
+        };
+        match (method, args) {
+            ("len", []) => {
+                let score = self.allocate_score();
+                commands.push(path.store_length(&score));
+                Ok(Some((Location::Score(score), EFSType::Int)))
+            }
+            ("push", [value]) => {
+                self.append(&path, value, element_type, commands)?;
+                Ok(None)
+            }
+            _ => bail!(
+                "List has no method {} taking {} arguments",
+                method,
+                args.len()
+            ),
+        }
+    }
+
+    /// Name of the function `receiver.method()` calls, `Type::method`.
+    fn method_name(&self, receiver: &Value, method: &str) -> anyhow::Result<String> {
+        match self.value_type(receiver)? {
+            EFSType::Struct(name) | EFSType::Enum(name) | EFSType::TaggedEnum(name) => {
+                Ok(format!("{}::{}", name, method))
+            }
+            // Constant selectors are built by `eval::fold`.
+            EFSType::Selector => bail!(
+                "Selector::{} needs a selector and an argument known at compile time",
+                method
+            ),
+            var_type => bail!("{:?} has no method {}", var_type, method),
+        }
+    }
+
+    fn value_type(&self, value: &Value) -> anyhow::Result<EFSType> {
+        let value = &self.fold(value)?;
+        Ok(match value {
+            Value::Value(value) => value.get_type(),
+            Value::Text(_) => EFSType::Text,
+            Value::Identifier(name) => self.lookup(name)?.var_type.clone(),
+            Value::Call(name, _) if stdlib::is_builtin(name) => EFSType::None,
+            Value::Call(name, _) => self
+                .compiler
+                .functions
+                .get(name)
+                .ok_or_else(|| anyhow!("unknown function {}", name))?
+                .return_type
+                .clone(),
+            Value::Math(math) => match math.op {
+                Operator::Plus | Operator::Minus | Operator::Multi | Operator::Div => {
+                    let (left, right) = self.operand_types(math)?;
+                    left.promote(&right)
+                }
+                _ => EFSType::Bool,
+            },
+            Value::MethodCall(receiver, method, _) => {
+                if let EFSType::List(_) = self.value_type(receiver)? {
+                    return Ok(match method.as_str() {
+                        "len" => EFSType::Int,
+                        _ => EFSType::None,
+                    });
+                }
+                let name = self.method_name(receiver, method)?;
+                self.compiler
+                    .functions
+                    .get(&name)
+                    .ok_or_else(|| anyhow!("unknown function {}", name))?
+                    .return_type
+                    .clone()
+            }
+            Value::Cast(_, var_type) => var_type.clone(),
+            Value::List(values) => EFSType::List(Box::new(match values.first() {
+                Some(value) => self.value_type(value)?,
+                None => EFSType::None,
+            })),
+            Value::Dict(fields) => {
+                let mut types = fields.values().map(|value| self.value_type(value));
+                let first = types.next().transpose()?.unwrap_or(EFSType::None);
+                let mut same = true;
+                for var_type in types {
+                    same &= var_type? == first;
+                }
+                EFSType::Dict(Box::new(if same { first } else { EFSType::None }))
+            }
+            Value::Struct(name, _) => EFSType::Struct(name.clone()),
+            Value::Variant(name, ..) => resolve::enum_type(name, self.variants(name)?),
+            Value::Field(value, field) => self.field_type(&self.value_type(value)?, field)?,
+            Value::Index(list, _) => match self.value_type(list)? {
+                EFSType::List(element_type) => *element_type,
+                var_type => bail!("cannot index {:?}", var_type),
+            },
+            Value::OptionalField(value, field) => match self.value_type(value)? {
+                EFSType::Option(var_type) => self.field_type(&var_type, field)?.optional(),
+                var_type => bail!("{:?} is never None, use `.` to access {}", var_type, field),
+            },
+        })
+    }
+
+    /// Types of both sides of `math`, see `eval::operand_type`.
+    fn operand_types(&self, math: &Math) -> anyhow::Result<(EFSType, EFSType)> {
+        let left = self.value_type(&math.left)?;
+        let right = self.value_type(&math.right)?;
+        Ok((
+            eval::operand_type(&math.left, &left, &right),
+            eval::operand_type(&math.right, &right, &left),
+        ))
+    }
+
+    fn variants(&self, name: &str) -> anyhow::Result<&[(String, Vec<EFSType>)]> {
+        self.compiler
+            .enums
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| anyhow!("unknown enum {}", name))
+    }
+
+    /// Type of `field` of a struct or typed dict.
+    fn field_type(&self, var_type: &EFSType, field: &str) -> anyhow::Result<EFSType> {
+        match var_type {
+            EFSType::Struct(name) => self
+                .compiler
+                .structs
+                .get(name)
+                .and_then(|fields| fields.get(field))
+                .cloned()
+                .ok_or_else(|| anyhow!("{:?} has no field {}", var_type, field)),
+            EFSType::Dict(value_type) if **value_type != EFSType::None => {
+                Ok(value_type.as_ref().clone())
+            }
+            EFSType::Selector => self
+                .compiler
+                .scores
+                .get(field)
+                .cloned()
+                .ok_or_else(|| anyhow!("entities have no score {}", field)),
+            _ => bail!("cannot access field {} of {:?}", field, var_type),
+        }
+    }
+
+    /// The score `field` of the entities `selector` targets.
+    fn entity_score(&self, selector: &Value, field: &str) -> anyhow::Result<(Score, EFSType)> {
+        let var_type = self.field_type(&EFSType::Selector, field)?;
+        let holder = self.selector(selector)?;
+        let score = Score::new(holder, self.compiler.score_objective(field));
+        Ok((score, var_type))
+    }
+
+    /// Path of `field` of the struct or dict `value` evaluates to.
+    fn field(
+        &mut self,
+        value: &Value,
+        field: &str,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<(StoragePath, EFSType)> {
+        match self.value(value, commands)? {
+            (Location::Storage(path), var_type) => Ok((path.field(field), var_type)),
+            (_, var_type) => bail!("cannot access field {} of {:?}", field, var_type),
+        }
+    }
+
+    /// Element `index` of the list `list` evaluates to. A constant index is read in
+    /// place, one only known at runtime is passed to a macro that copies the element
+    /// to a temporary.
+    fn index(
+        &mut self,
+        list: &Value,
+        index: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<(Location, EFSType)> {
+        let (location, var_type) = self.value(list, commands)?;
+        let (Location::Storage(path), EFSType::List(element_type)) = (location, &var_type) else {
+            bail!("cannot index {:?}", var_type);
+        };
+        let element_type = element_type.as_ref().clone();
+        if let Value::Value(literal) = self.fold(index)? {
+            let Some(EFSValueType::Int(index)) = literal.convert(&EFSType::Int) else {
+                bail!(
+                    "a list index must be an integer, found {}",
+                    literal.to_snbt()
+                );
+            };
+            return Ok((Location::Storage(path.index(index)), element_type));
+        }
+
+        let index = self.score_operand(index, commands)?;
+        let arguments = self.compiler.allocator.macro_arguments();
+        commands.push(
+            arguments
+                .field("index")
+                .store_from_score(&index, "int", 1.0),
+        );
+        let temp = self.compiler.allocator.storage.allocate();
+        self.locals.push(Location::Storage(temp.clone()));
+        let element = StoragePath::new(path.storage.clone(), format!("{}[$(index)]", path.path));
+        let helper = self.helper("index");
+        self.compiler.datapack.functions.insert(
+            helper.clone(),
+            vec![format!("${}", temp.set_from(&element))],
+        );
+        commands.push(format!("function {} with {}", helper, arguments));
+        Ok((Location::Storage(temp), element_type))
+    }
+
+    /// Evaluates `value`, variables are returned in place and everything else in a temporary.
+    fn value(
+        &mut self,
+        value: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<(Location, EFSType)> {
+        let value = &self.fold(value)?;
+        match value {
+            Value::Identifier(name) => {
+                let variable = self.lookup(name)?;
+                Ok((variable.location.clone(), variable.var_type.clone()))
+            }
+            Value::Call(name, args) => self
+                .call(name, args, commands)?
+                .ok_or_else(|| anyhow!("function {} does not return a value", name)),
+            Value::MethodCall(receiver, method, args) => self
+                .method_call(receiver, method, args, commands)?
+                .ok_or_else(|| anyhow!("method {} does not return a value", method)),
+            Value::Math(math) => {
+                let var_type = self.value_type(value)?;
+                let result = self.math(math, commands)?;
+                if !is_float(&var_type) {
+                    return Ok((Location::Score(result), var_type));
+                }
+                let temp = self.allocate(&var_type);
+                let Location::Storage(path) = &temp else {
+                    unreachable!("floats are kept in storage")
+                };
+                let nbt_type = var_type.store_type().unwrap();
+                commands.push(path.store_from_score(&result, nbt_type, 1.0 / FLOAT_SCALE));
+                Ok((temp, var_type))
+            }
+            Value::Field(inner, field) if self.value_type(inner)? == EFSType::Selector => {
+                let (score, var_type) = self.entity_score(inner, field)?;
+                Ok((Location::Score(score), var_type))
+            }
+            Value::Field(inner, field) => {
+                let (path, var_type) = self.field(inner, field, commands)?;
+                Ok((Location::Storage(path), self.field_type(&var_type, field)?))
+            }
+            Value::Index(list, index) => self.index(list, index, commands),
+            Value::OptionalField(inner, field) => {
+                let var_type = self.value_type(value)?;
+                let (path, _) = self.field(inner, field, commands)?;
+                let temp = self.allocate(&var_type);
+                commands.extend(temp.assign_from(&Location::Storage(path), &var_type));
+                Ok((temp, var_type))
+            }
+            Value::Cast(inner, var_type) => {
+                let (source, source_type) = self.value(inner, commands)?;
+                let temp = self.allocate(var_type);
+                self.cast(&source, &source_type, &temp, var_type, commands);
+                Ok((temp, var_type.clone()))
+            }
+            _ => {
+                let var_type = self.value_type(value)?;
+                let temp = self.allocate(&var_type);
+                self.store(value, &temp, &var_type, commands)?;
+                Ok((temp, var_type))
+            }
+        }
+    }
+
+    /// Copies the number at `source` to `target`, converting it like `EFSValueType::convert`.
+    /// Scores hold 32 bits whatever their type, so narrowing into one goes through
+    /// `Allocator::scratch` where `execute store` wraps the value.
+    fn cast(
+        &mut self,
+        source: &Location,
+        source_type: &EFSType,
+        target: &Location,
+        target_type: &EFSType,
+        commands: &mut Vec<String>,
+    ) {
+        let scratch = self.compiler.allocator.scratch();
+        let narrows = matches!(target_type, EFSType::Byte | EFSType::Short)
+            && !source_type.widens_to(target_type);
+        match (source, target) {
+            (Location::Score(from), Location::Score(to)) if narrows => {
+                let nbt_type = target_type.store_type().unwrap();
+                commands.push(scratch.store_from_score(from, nbt_type, 1.0));
+                commands.push(scratch.store_to_score(to, 1.0));
+            }
+            (Location::Storage(from), Location::Score(to)) if narrows => {
+                commands.push(format!(
+                    "execute store result {} {} 1 run data get {} 1",
+                    scratch,
+                    target_type.store_type().unwrap(),
+                    from
+                ));
+                commands.push(scratch.store_to_score(to, 1.0));
+            }
+            (Location::Storage(from), Location::Storage(to))
+                if target_type.is_integer() && source_type != target_type =>
+            {
+                commands.push(format!(
+                    "execute store result {} {} 1 run data get {} 1",
+                    to,
+                    target_type.store_type().unwrap(),
+                    from
+                ));
+            }
+            _ => commands.push(target.copy_from(source, target_type)),
+        }
+    }
+
+    /// Writes `value` to `target`, converting it to `target_type`.
+    fn store(
+        &mut self,
+        value: &Value,
+        target: &Location,
+        target_type: &EFSType,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let value = &self.fold(value)?;
+        match (value, target) {
+            (Value::Value(EFSValueType::None), _) => commands.push(target.clear()),
+            (Value::Text(parts), _) => {
+                let json = EFSValueType::Text(self.text(parts, commands)?);
+                self.store(&Value::Value(json), target, target_type, commands)?;
+            }
+            (Value::Variant(name, variant, args), _) => {
+                let variants = self.variants(name)?.to_vec();
+                let index = variant_index(name, &variants, variant)?;
+                match target {
+                    Location::Score(score) => commands.push(score.set(index as i32)),
+                    Location::Storage(path) => {
+                        let tag = (String::from("variant"), EFSValueType::Int(index as i32));
+                        commands.push(path.set_value(&EFSValueType::Dict(HashMap::from([tag]))));
+                        let payload = args.iter().zip(&variants[index].1).enumerate();
+                        for (position, (arg, var_type)) in payload {
+                            let field = Location::Storage(path.field(&format!("_{}", position)));
+                            self.store(arg, &field, var_type, commands)?;
+                        }
+                    }
+                }
+            }
+            (Value::Value(literal), _) => {
+                let converted = literal.convert(target_type).ok_or_else(|| {
+                    anyhow!("expected a {:?}, found {}", target_type, literal.to_snbt())
+                })?;
+                match target {
+                    Location::Score(score) => commands.push(score.set(score_literal(&converted))),
+                    Location::Storage(path) => commands.push(path.set_value(&converted)),
+                }
+            }
+            (Value::List(values), Location::Storage(path)) => {
+                let element_type = match target_type {
+                    EFSType::List(element_type) => element_type.as_ref().clone(),
+                    _ => bail!("expected a {:?}, found a list", target_type),
+                };
+                commands.push(path.set_value(&EFSValueType::List(Vec::new())));
+                for value in values {
+                    self.append(path, value, &element_type, commands)?;
+                }
+            }
+            (Value::Dict(values) | Value::Struct(_, values), Location::Storage(path)) => {
+                commands.push(path.set_value(&EFSValueType::Dict(HashMap::new())));
+                let compound_type = match target_type {
+                    EFSType::Option(var_type) => var_type.as_ref(),
+                    var_type => var_type,
+                };
+                let mut keys = values.keys().collect::<Vec<_>>();
+                keys.sort();
+                for key in keys {
+                    let value_type = match compound_type {
+                        EFSType::Dict(value_type) if **value_type == EFSType::None => {
+                            self.value_type(&values[key])?
+                        }
+                        var_type => self.field_type(var_type, key)?,
+                    };
+                    let field = Location::Storage(path.field(key));
+                    self.store(&values[key], &field, &value_type, commands)?;
+                }
+            }
+            _ => {
+                let (location, _) = self.value(value, commands)?;
+                if &location != target {
+                    commands.extend(target.assign_from(&location, target_type));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends `value` to the list at `path`, a `List` without a type parameter takes
+    /// the type of the value.
+    fn append(
+        &mut self,
+        path: &StoragePath,
+        value: &Value,
+        element_type: &EFSType,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let value = &self.fold(value)?;
+        let element_type = match element_type {
+            EFSType::None => self.value_type(value)?,
+            _ => element_type.clone(),
+        };
+        if let Value::Value(literal) = value {
+            let converted = literal.convert(&element_type).ok_or_else(|| {
+                anyhow!("expected a {:?}, found {}", element_type, literal.to_snbt())
+            })?;
+            commands.push(path.append_value(&converted));
+            return Ok(());
+        }
+        match self.value(value, commands)?.0 {
+            Location::Storage(source) => commands.push(path.append_from(&source)),
+            Location::Score(score) => {
+                let zero = EFSValueType::Int(0)
+                    .convert(&element_type)
+                    .ok_or_else(|| anyhow!("expected a {:?}, found a number", element_type))?;
+                commands.push(path.append_value(&zero));
+                commands.push(path.index(-1).store_from_score(
+                    &score,
+                    element_type.store_type().unwrap_or("int"),
+                    1.0,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates a numeric or boolean value into a score.
+    fn score_operand(
+        &mut self,
+        value: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Score> {
+        let (location, var_type) = self.value(value, commands)?;
+        if !var_type.is_score_type() {
+            bail!("expected a number, found a {:?}", var_type);
+        }
+        match location {
+            Location::Score(score) => Ok(score),
+            Location::Storage(path) => {
+                let score = self.allocate_score();
+                commands.push(path.store_to_score(&score, 1.0));
+                Ok(score)
+            }
+        }
+    }
+
+    /// Evaluates a number into a score holding it times `FLOAT_SCALE`.
+    fn float_operand(
+        &mut self,
+        value: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<Score> {
+        let value = self.fold(value)?;
+        // Float math is already in fixed point.
+        if let Value::Math(math) = &value {
+            if is_float(&self.value_type(&value)?) {
+                return self.math(math, commands);
+            }
+        }
+        let score = self.allocate_score();
+        if let Value::Value(literal) = &value {
+            let Some(EFSValueType::Double(n)) = literal.convert(&EFSType::Double) else {
+                bail!("expected a number, found {}", literal.to_snbt());
+            };
+            commands.push(score.set((n * FLOAT_SCALE).round() as i32));
+            return Ok(score);
+        }
+        let (location, var_type) = self.value(&value, commands)?;
+        if !var_type.is_numeric() {
+            bail!("expected a number, found a {:?}", var_type);
+        }
+        match location {
+            Location::Storage(path) => commands.push(path.store_to_score(&score, FLOAT_SCALE)),
+            Location::Score(from) => {
+                let scratch = self.compiler.allocator.scratch();
+                commands.push(scratch.store_from_score(&from, "int", 1.0));
+                commands.push(scratch.store_to_score(&score, FLOAT_SCALE));
+            }
+        }
+        Ok(score)
+    }
+
+    /// Both sides of `math` in scores, in fixed point when either is a float.
+    fn operands(
+        &mut self,
+        math: &Math,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<(Score, Score)> {
+        let (left_type, right_type) = self.operand_types(math)?;
+        if is_float(&left_type) || is_float(&right_type) {
+            let left = self.float_operand(&math.left, commands)?;
+            let right = self.float_operand(&math.right, commands)?;
+            return Ok((left, right));
+        }
+        let left = self.score_operand(&math.left, commands)?;
+        let right = self.score_operand(&math.right, commands)?;
+        Ok((left, right))
+    }
+
+    /// Multiplies `score` by `scale` through `Allocator::scratch`, truncating it.
+    fn rescale(&self, score: &Score, scale: f64, commands: &mut Vec<String>) {
+        let scratch = self.compiler.allocator.scratch();
+        commands.push(scratch.store_from_score(score, "double", scale));
+        commands.push(scratch.store_to_score(score, 1.0));
+    }
+
+    /// Applies `+`, `-`, `*` or `/` with `right` to `target` in place.
+    fn operate(
+        &mut self,
+        target: &Score,
+        op: &Operator,
+        right: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let operator = match op {
+            Operator::Plus => "+=",
+            Operator::Minus => "-=",
+            Operator::Multi => "*=",
+            Operator::Div => "/=",
+            op => bail!("cannot use {}= on a score", op),
+        };
+        match (op, right) {
+            (Operator::Plus, Value::Value(literal)) if literal.get_type().is_score_type() => {
+                commands.push(format!(
+                    "scoreboard players add {} {}",
+                    target,
+                    score_literal(literal)
+                ));
+            }
+            (Operator::Minus, Value::Value(literal)) if literal.get_type().is_score_type() => {
+                commands.push(format!(
+                    "scoreboard players remove {} {}",
+                    target,
+                    score_literal(literal)
+                ));
+            }
+            (_, right) => {
+                let right = self.score_operand(right, commands)?;
+                commands.push(format!(
+                    "scoreboard players operation {} {} {}",
+                    target, operator, right
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates `math` into a fresh score so the target of an assignment can
+    /// appear on both sides. Float results are in fixed point, see `FLOAT_SCALE`.
+    fn math(&mut self, math: &Math, commands: &mut Vec<String>) -> anyhow::Result<Score> {
+        let result = self.allocate_score();
+        let (left_type, right_type) = self.operand_types(math)?;
+        let float = is_float(&left_type) || is_float(&right_type);
+        match math.op {
+            Operator::Plus | Operator::Minus | Operator::Multi | Operator::Div if float => {
+                let (left, right) = self.operands(math, commands)?;
+                commands.push(result.copy_from(&left));
+                // Both sides are scaled, so a product has the scale twice and a
+                // quotient not at all.
+                if math.op == Operator::Div {
+                    self.rescale(&result, FLOAT_SCALE, commands);
+                }
+                commands.push(format!(
+                    "scoreboard players operation {} {}= {}",
+                    result, math.op, right
+                ));
+                if math.op == Operator::Multi {
+                    self.rescale(&result, 1.0 / FLOAT_SCALE, commands);
+                }
+            }
+            Operator::Plus | Operator::Minus | Operator::Multi | Operator::Div => {
+                let left = self.score_operand(&math.left, commands)?;
+                commands.push(result.copy_from(&left));
+                self.operate(&result, &math.op, &math.right, commands)?;
+            }
+            Operator::Equal | Operator::NotEqual
+                if !float && (!left_type.is_score_type() || !right_type.is_score_type()) =>
+            {
+                // Storage values are compared by copying one over the other, the copy
+                // only succeeds when they differ.
+                let var_type = self.value_type(&math.left)?;
+                let (left, _) = self.value(&math.left, commands)?;
+                let (right, _) = self.value(&math.right, commands)?;
+                let Location::Storage(temp) = self.allocate(&EFSType::String) else {
+                    unreachable!()
+                };
+                commands.push(Location::Storage(temp.clone()).copy_from(&left, &var_type));
+                commands.push(format!(
+                    "execute store success score {} run {}",
+                    result,
+                    Location::Storage(temp).copy_from(&right, &var_type)
+                ));
+                if math.op == Operator::Equal {
+                    commands.push(format!(
+                        "execute store success score {} if score {} matches 0",
+                        result, result
+                    ));
+                }
+            }
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessOrEqual
+            | Operator::Greater
+            | Operator::GreaterOrEqual => {
+                let (left, right) = self.operands(math, commands)?;
+                let (condition, operator) = match math.op {
+                    Operator::Equal => ("if", "="),
+                    Operator::NotEqual => ("unless", "="),
+                    Operator::Less => ("if", "<"),
+                    Operator::LessOrEqual => ("if", "<="),
+                    Operator::Greater => ("if", ">"),
+                    _ => ("if", ">="),
+                };
+                commands.push(format!(
+                    "execute store success score {} {} score {} {} {}",
+                    result, condition, left, operator, right
+                ));
+            }
+            Operator::LogicalAnd | Operator::LogicalOr => {
+                let left = self.score_operand(&math.left, commands)?;
+                commands.push(format!(
+                    "execute store success score {} unless score {} matches 0",
+                    result, left
+                ));
+                // The right side only runs while the left one leaves the result open,
+                // in a helper function when it takes more than one command.
+                let mut right_commands = Vec::new();
+                let right = self.score_operand(&math.right, &mut right_commands)?;
+                right_commands.push(format!(
+                    "execute store success score {} unless score {} matches 0",
+                    result, right
+                ));
+                let (kind, open) = if math.op == Operator::LogicalAnd {
+                    ("and", "1..")
+                } else {
+                    ("or", "0")
+                };
+                let run = match right_commands.as_slice() {
+                    [command] => command.clone(),
+                    _ => {
+                        let helper = self.helper(kind);
+                        self.compiler
+                            .datapack
+                            .functions
+                            .insert(helper.clone(), right_commands);
+                        format!("function {}", helper)
+                    }
+                };
+                commands.push(format!(
+                    "execute if score {} matches {} run {}",
+                    result, open, run
+                ));
+            }
+            Operator::Or | Operator::And | Operator::Xor => {
+                bail!("bitwise {} is not supported on scores", math.op)
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Index of `variant` among the variants of enum `name`, the value it has at runtime.
+fn variant_index(
+    name: &str,
+    variants: &[(String, Vec<EFSType>)],
+    variant: &str,
+) -> anyhow::Result<usize> {
+    variants
+        .iter()
+        .position(|(v, _)| v == variant)
+        .ok_or_else(|| anyhow!("enum {} has no variant {}", name, variant))
+}
+
+fn is_float(var_type: &EFSType) -> bool {
+    matches!(var_type, EFSType::Float | EFSType::Double)
+}
+
+fn score_literal(literal: &EFSValueType) -> i32 {
+    match literal.convert(&EFSType::Int) {
+        Some(EFSValueType::Int(n)) => n,
+        _ => 0,
+    }
+}
+
+/// Whether `value` is a variable or a part of one, which a write can change.
+fn is_place(value: &Value) -> bool {
+    match value {
+        Value::Identifier(_) => true,
+        Value::Field(value, _) | Value::Index(value, _) => is_place(value),
+        _ => false,
+    }
+}
+
+/// Whether `value` is `self` or a part of it.
+fn is_self(value: &Value) -> bool {
+    match value {
+        Value::Identifier(name) => name == "self",
+        Value::Field(value, _) | Value::Index(value, _) => is_self(value),
+        _ => false,
+    }
+}
+
+/// Whether a method with the body `block` may change `self`: it assigns `self` or a
+/// part of it, calls a method on it or names it in raw commands.
+pub(super) fn writes_self(block: &CodeBlock) -> bool {
+    block.statements.iter().any(statement_writes_self)
+}
+
+fn statement_writes_self(statement: &Statement) -> bool {
+    match statement {
+        Statement::For(init, condition, step, block) => {
+            statement_writes_self(init)
+                || value_writes_self(condition)
+                || statement_writes_self(step)
+                || writes_self(block)
+        }
+        Statement::ForList(_, _, block) | Statement::StaticFor(_, _, _, _, block) => {
+            writes_self(block)
+        }
+        Statement::While(value, block)
+        | Statement::If(value, block)
+        | Statement::IfLet(_, value, block) => value_writes_self(value) || writes_self(block),
+        Statement::Match(value, arms) => {
+            value_writes_self(value) || arms.iter().any(|arm| writes_self(&arm.block))
+        }
+        Statement::Return(value) => value.as_ref().is_some_and(value_writes_self),
+        Statement::Break | Statement::Continue => false,
+        Statement::Commands(commands) => commands.iter().any(|c| c.contains("$(self")),
+        Statement::Execute(contexts, block) => {
+            contexts
+                .iter()
+                .filter_map(|context| context.selector())
+                .any(value_writes_self)
+                || writes_self(block)
+        }
+        Statement::Expression(Expression::Assign(name, value)) => {
+            name == "self" || value_writes_self(value)
+        }
+        Statement::Expression(Expression::AssignField(target, _, _, value)) => {
+            is_self(target) || value_writes_self(target) || value_writes_self(value)
+        }
+        Statement::Expression(Expression::VarDec(_, _, value) | Expression::Value(value)) => {
+            value_writes_self(value)
+        }
+    }
+}
+
+fn value_writes_self(value: &Value) -> bool {
+    match value {
+        Value::MethodCall(receiver, _, args) => {
+            is_self(receiver) || value_writes_self(receiver) || args.iter().any(value_writes_self)
+        }
+        Value::Math(math) => value_writes_self(&math.left) || value_writes_self(&math.right),
+        Value::List(values)
+        | Value::Text(values)
+        | Value::Call(_, values)
+        | Value::Variant(_, _, values) => values.iter().any(value_writes_self),
+        Value::Dict(values) | Value::Struct(_, values) => values.values().any(value_writes_self),
+        Value::Cast(value, _) | Value::Field(value, _) | Value::OptionalField(value, _) => {
+            value_writes_self(value)
+        }
+        Value::Index(value, index) => value_writes_self(value) || value_writes_self(index),
+        Value::Value(_) | Value::Identifier(_) => false,
+    }
+}
+
+fn contains_call(value: &Value) -> bool {
+    match value {
+        Value::Call(..) | Value::MethodCall(..) => true,
+        Value::Math(math) => contains_call(&math.left) || contains_call(&math.right),
+        Value::List(values) | Value::Variant(_, _, values) => values.iter().any(contains_call),
+        Value::Dict(values) | Value::Struct(_, values) => values.values().any(contains_call),
+        Value::Cast(value, _) | Value::Field(value, _) | Value::OptionalField(value, _) => {
+            contains_call(value)
+        }
+        Value::Index(value, index) => contains_call(value) || contains_call(index),
+        Value::Text(parts) => parts
+            .iter()
+            .any(|part| contains_call(crate::compiler::text::split(part).0)),
+        Value::Value(_) | Value::Identifier(_) => false,
+    }
+}
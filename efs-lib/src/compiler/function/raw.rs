@@ -0,0 +1,67 @@
+use anyhow::bail;
+
+use crate::{
+    compiler::commands::{self, Part},
+    parser::types::EFSValueType,
+};
+
+use super::{FunctionCompiler, Location};
+
+impl FunctionCompiler<'_> {
+    /// Emits raw commands with every `$(name)` replaced by where the variable is,
+    /// `<holder> <objective>` for a score and `storage <id> <path>` for storage.
+    /// Constants are replaced by their value.
+    pub(super) fn raw_commands(
+        &mut self,
+        raw: &[String],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        for command in raw {
+            commands::check_name(command)?;
+            let mut text = String::new();
+            for part in commands::parts(command)? {
+                match part {
+                    Part::Text(part) => text.push_str(part),
+                    Part::Variable(name, fields) => {
+                        text.push_str(&self.interpolate(name, &fields)?)
+                    }
+                }
+            }
+            commands.push(text);
+        }
+        Ok(())
+    }
+
+    fn interpolate(&self, name: &str, fields: &[&str]) -> anyhow::Result<String> {
+        let is_local = self.scopes.iter().any(|scope| scope.contains_key(name));
+        if let (false, Some(value)) = (is_local, self.compiler.constants.get(name)) {
+            if !fields.is_empty() {
+                bail!("cannot read a field of the constant {} in a command", name);
+            }
+            return Ok(literal_text(value));
+        }
+        match (&self.lookup(name)?.location, fields) {
+            (Location::Score(score), []) => Ok(score.to_string()),
+            (Location::Storage(path), fields) => Ok(fields
+                .iter()
+                .fold(path.clone(), |path, field| path.field(field))
+                .to_string()),
+            (Location::Score(_), _) => bail!("{} is a score and has no fields", name),
+        }
+    }
+}
+
+/// A constant as it is written in a command, numbers without their NBT suffix and
+/// strings without quotes.
+fn literal_text(value: &EFSValueType) -> String {
+    match value {
+        EFSValueType::Number(n) | EFSValueType::Int(n) => n.to_string(),
+        EFSValueType::Byte(n) => n.to_string(),
+        EFSValueType::Short(n) => n.to_string(),
+        EFSValueType::Long(n) => n.to_string(),
+        EFSValueType::Float(n) => n.to_string(),
+        EFSValueType::Double(n) => n.to_string(),
+        EFSValueType::String(string) => string.clone(),
+        value => value.to_snbt(),
+    }
+}
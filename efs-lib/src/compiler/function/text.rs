@@ -0,0 +1,81 @@
+use anyhow::bail;
+
+use crate::{
+    compiler::{text, Location},
+    parser::{
+        ast::Value,
+        types::{EFSType, EFSValueType},
+    },
+};
+
+use super::FunctionCompiler;
+
+impl FunctionCompiler<'_> {
+    /// The JSON of `text!(parts)`. Variables become `score` and `nbt` components that
+    /// the game reads when the text is shown, anything else is computed into a
+    /// temporary by `commands` first.
+    pub(super) fn text(
+        &mut self,
+        parts: &[Value],
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<String> {
+        let mut components = Vec::new();
+        for part in parts {
+            let (value, styles) = text::split(part);
+            let mut component = self.component(value, commands)?;
+            for (style, args) in styles {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(match self.fold(arg)? {
+                        Value::Value(value) => value,
+                        Value::Text(parts) => EFSValueType::Text(self.text(&parts, commands)?),
+                        _ => bail!("the arguments of {} must be known at compile time", style),
+                    });
+                }
+                text::style(&mut component, style, &values)?;
+            }
+            components.push(component);
+        }
+        text::join(components)
+    }
+
+    /// A message only known at runtime as a text component reading it, a macro would
+    /// paste it into the JSON unescaped.
+    pub(super) fn runtime_text(
+        &mut self,
+        value: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<String> {
+        let component = self.component(value, commands)?;
+        text::join(vec![component])
+    }
+
+    fn component(
+        &mut self,
+        value: &Value,
+        commands: &mut Vec<String>,
+    ) -> anyhow::Result<text::Component> {
+        match self.fold(value)? {
+            Value::Value(value) => text::literal(&value),
+            Value::Text(parts) => text::literal(&EFSValueType::Text(self.text(&parts, commands)?)),
+            Value::MethodCall(receiver, method, args) if method == text::ENTITY_NBT => {
+                let entity = self.selector(&receiver)?;
+                match args.as_slice() {
+                    [Value::Value(EFSValueType::String(path))] => {
+                        Ok(text::entity_nbt(&entity, path))
+                    }
+                    _ => bail!(
+                        "the path of {} must be a string known at compile time",
+                        method
+                    ),
+                }
+            }
+            value => Ok(match self.value(&value, commands)? {
+                (Location::Score(score), _) => text::score(&score.holder, &score.objective),
+                (Location::Storage(path), var_type) => {
+                    text::storage_nbt(&path.storage, &path.path, var_type == EFSType::Text)
+                }
+            }),
+        }
+    }
+}
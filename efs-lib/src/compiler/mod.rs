@@ -0,0 +1,672 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+
+use crate::parser::{
+    ast::{Declaration, ExternKind, ExternVariable, Program},
+    types::{EFSType, EFSValueType},
+};
+
+use self::{
+    function::{FunctionCompiler, Variable},
+    scoreboard::{Score, Scoreboard, ABSENT},
+    storage::{Storage, StoragePath},
+    symbols::SymbolTable,
+};
+
+pub mod check;
+pub mod commands;
+pub mod eval;
+mod function;
+pub mod modules;
+pub mod recursion;
+pub mod resolve;
+pub mod scoreboard;
+pub mod selector;
+pub mod setup;
+pub mod stdlib;
+pub mod storage;
+pub mod symbols;
+pub mod text;
+pub mod unroll;
+
+/// Attributes a function can have, `#[load]`, `#[tick]`, `#[tag("ns:name")]` and
+/// `#[export("ns:path")]`.
+const ATTRIBUTES: &[&str] = &["load", "tick", "tag", "export"];
+
+/// The generated functions, keyed by resource location.
+#[derive(Default)]
+pub struct Datapack {
+    pub functions: BTreeMap<String, Vec<String>>,
+    /// Function tags and the functions they list. Sorted so the output does not
+    /// depend on the order the source files were read in, except that the
+    /// compiler's own load function always runs first.
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+impl Datapack {
+    /// Writes the functions and function tags below the `data` folder of the datapack at `path`.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        for (location, commands) in &self.functions {
+            let file = data_file(path, location, "functions", "mcfunction")?;
+            fs::create_dir_all(file.parent().unwrap())?;
+            fs::write(file, commands.join("\n") + "\n")?;
+        }
+        for tag in self.tags.keys() {
+            let file = data_file(path, tag, "tags/functions", "json")?;
+            fs::create_dir_all(file.parent().unwrap())?;
+            fs::write(file, self.tag_json(tag))?;
+        }
+        Ok(())
+    }
+
+    /// Contents of the json file for the function tag `tag`.
+    pub fn tag_json(&self, tag: &str) -> String {
+        let values = self.tags.get(tag).into_iter().flatten().collect::<Vec<_>>();
+        serde_json::to_string_pretty(&serde_json::json!({ "values": values })).unwrap()
+    }
+}
+
+/// `data/<namespace>/<folder>/<path>.<extension>` inside the datapack at `root`.
+fn data_file(
+    root: &Path,
+    location: &str,
+    folder: &str,
+    extension: &str,
+) -> anyhow::Result<PathBuf> {
+    let Some((namespace, path)) = location.split_once(':') else {
+        bail!("{} is not a resource location", location);
+    };
+    Ok(root
+        .join("data")
+        .join(namespace)
+        .join(folder)
+        .join(format!("{}.{}", path, extension)))
+}
+
+#[derive(Clone)]
+pub struct FunctionSignature {
+    pub parameters: Vec<(String, EFSType)>,
+    pub return_type: EFSType,
+}
+
+pub struct Compiler {
+    pub namespace: String,
+    pub allocator: Allocator,
+    pub functions: HashMap<String, FunctionSignature>,
+    /// Functions that save their locals on the call stack because they recurse.
+    pub recursive: HashSet<String>,
+    /// Methods that may change `self` and hand it back in `Allocator::receiver`.
+    pub mutating: HashSet<String>,
+    /// Resource locations of the functions pinned with `#[export("ns:path")]`.
+    pub exports: HashMap<String, String>,
+    /// Functions declared `pub`, the others compile to `setup::PRIVATE`.
+    pub public: HashSet<String>,
+    /// Resource locations of the `extern fn` declarations.
+    pub externs: HashMap<String, String>,
+    /// Whether to generate `setup::UNINSTALL_FUNCTION`.
+    pub uninstall: bool,
+    pub datapack: Datapack,
+    /// Names of the compiled program and where they are used.
+    pub symbols: SymbolTable,
+    /// Values of the `const` declarations, inlined wherever they are used.
+    constants: HashMap<String, EFSValueType>,
+    /// Field types of the `struct` definitions.
+    structs: HashMap<String, HashMap<String, EFSType>>,
+    /// Variants of the `enum` definitions with the types of their payloads.
+    enums: HashMap<String, Vec<(String, Vec<EFSType>)>>,
+    /// The `extern score` and `extern storage` variables.
+    bindings: HashMap<String, Variable>,
+    /// Types of the scores declared with `score name: type on entity;`.
+    scores: HashMap<String, EFSType>,
+}
+
+impl Compiler {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            allocator: Allocator::new(namespace),
+            functions: HashMap::new(),
+            recursive: HashSet::new(),
+            mutating: HashSet::new(),
+            exports: HashMap::new(),
+            public: HashSet::new(),
+            externs: HashMap::new(),
+            uninstall: false,
+            datapack: Datapack::default(),
+            symbols: SymbolTable::default(),
+            constants: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            bindings: HashMap::new(),
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Lowers every function in `programs`. Signatures are collected first so
+    /// functions can call each other regardless of declaration order.
+    pub fn compile(&mut self, programs: &[Program]) -> anyhow::Result<()> {
+        self.symbols = check::check(programs)?;
+        self.public = programs
+            .iter()
+            .flat_map(|program| &program.0)
+            .flat_map(public_functions)
+            .collect();
+        let programs = &resolve::resolve(programs);
+
+        let mut locations = HashMap::new();
+        for declaration in programs.iter().flat_map(|program| &program.0) {
+            if let Declaration::FunctionDec {
+                name, attributes, ..
+            } = declaration
+            {
+                if let Some(export) = self.export_location(name, attributes)? {
+                    if export.starts_with(&self.resource_location(&format!("{}/", setup::RESERVED)))
+                    {
+                        bail!(
+                            "function {} can not compile to {}, {} is reserved for the compiler",
+                            name,
+                            export,
+                            setup::RESERVED
+                        );
+                    }
+                    self.exports.insert(name.clone(), export);
+                }
+                let location = self.function_location(name);
+                if let Some(other) = locations.insert(location.clone(), name) {
+                    if other != name {
+                        bail!(
+                            "functions {} and {} both compile to {}",
+                            other,
+                            name,
+                            location
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut call_graph = HashMap::new();
+        let mut constants = Vec::new();
+        for declaration in programs.iter().flat_map(|program| &program.0) {
+            if let Declaration::ConstDec(constant) = declaration {
+                constants.push(constant);
+            }
+            if let Declaration::StructDef(name, fields) = declaration {
+                self.structs.insert(name.clone(), fields.clone());
+            }
+            if let Declaration::EnumDef(name, variants) = declaration {
+                self.enums.insert(name.clone(), variants.clone());
+            }
+            if let Declaration::ExternFunction {
+                name,
+                parameters,
+                return_type,
+                location,
+            } = declaration
+            {
+                if !is_resource_location(location) {
+                    bail!("{} is not a valid resource location", location);
+                }
+                if !(return_type == &EFSType::None || return_type.is_score_type()) {
+                    bail!(
+                        "extern function {} can only return a score type, found {:?}",
+                        name,
+                        return_type
+                    );
+                }
+                let signature = FunctionSignature {
+                    parameters: parameters.clone(),
+                    return_type: return_type.clone(),
+                };
+                if self.functions.insert(name.clone(), signature).is_some() {
+                    bail!("function {} is defined more than once", name);
+                }
+                self.externs.insert(name.clone(), location.clone());
+            }
+            if let Declaration::ExternVariable(variable) = declaration {
+                let binding = Variable {
+                    var_type: variable.var_type.clone(),
+                    location: extern_location(variable)?,
+                };
+                self.bindings.insert(variable.name.clone(), binding);
+            }
+            if let Declaration::EntityScore(name, var_type) = declaration {
+                if !var_type.is_score_type() {
+                    bail!(
+                        "score {} must have a score type, found {:?}",
+                        name,
+                        var_type
+                    );
+                }
+                if self.score_objective(name) == self.allocator.scoreboard.objective() {
+                    bail!(
+                        "score {} can not be declared, the compiler keeps its variables on {}",
+                        name,
+                        self.score_objective(name)
+                    );
+                }
+                if self.scores.insert(name.clone(), var_type.clone()).is_some() {
+                    bail!("score {} is declared more than once", name);
+                }
+            }
+            if let Declaration::FunctionDec {
+                attributes,
+                name,
+                parameters,
+                return_type,
+                code_block,
+                ..
+            } = declaration
+            {
+                for tag in self.function_tags(name, attributes)? {
+                    if !parameters.is_empty() {
+                        bail!(
+                            "function {} is in the tag {} but takes arguments",
+                            name,
+                            tag
+                        );
+                    }
+                    let function = self.function_location(name);
+                    self.datapack.tags.entry(tag).or_default().push(function);
+                }
+                call_graph.insert(name.clone(), recursion::calls(code_block));
+                let is_method = parameters.first().is_some_and(|(param, _)| param == "self");
+                if is_method && function::writes_self(code_block) {
+                    self.mutating.insert(name.clone());
+                }
+                let signature = FunctionSignature {
+                    parameters: parameters.clone(),
+                    return_type: return_type.clone(),
+                };
+                if self.functions.insert(name.clone(), signature).is_some() {
+                    bail!("function {} is defined more than once", name);
+                }
+            }
+        }
+        self.recursive = recursion::recursive_functions(&call_graph);
+        for functions in self.datapack.tags.values_mut() {
+            functions.sort();
+            functions.dedup();
+        }
+
+        for constant in constants {
+            let value = eval::evaluate(&constant.var, &|name| self.constants.get(name).cloned())
+                .and_then(|value| match &constant.var_type {
+                    Some(var_type) => eval::cast(value, var_type),
+                    None => Ok(value),
+                })
+                .with_context(|| format!("in constant {}", constant.name))?;
+            if self
+                .constants
+                .insert(constant.name.clone(), value)
+                .is_some()
+            {
+                bail!("constant {} is defined more than once", constant.name);
+            }
+        }
+
+        for declaration in programs.iter().flat_map(|program| &program.0) {
+            if let Declaration::FunctionDec {
+                name, code_block, ..
+            } = declaration
+            {
+                FunctionCompiler::compile(self, name, code_block)?;
+            }
+        }
+
+        let load_function = self.resource_location(setup::LOAD_FUNCTION);
+        self.datapack
+            .tags
+            .entry(String::from("minecraft:load"))
+            .or_default()
+            .insert(0, load_function.clone());
+        let mut objectives = self
+            .scores
+            .keys()
+            .map(|name| self.score_objective(name))
+            .collect::<Vec<_>>();
+        objectives.sort();
+        self.datapack
+            .functions
+            .insert(load_function, setup::load(&self.allocator, &objectives));
+        if self.uninstall {
+            let uninstall = setup::uninstall(&self.allocator, &objectives);
+            self.datapack
+                .functions
+                .insert(self.resource_location(setup::UNINSTALL_FUNCTION), uninstall);
+        }
+        Ok(())
+    }
+
+    /// Function tags a function is added to by its `#[load]`, `#[tick]` and
+    /// `#[tag("ns:name")]` attributes. Tags without a namespace use the project's.
+    fn function_tags(
+        &self,
+        name: &str,
+        attributes: &HashMap<String, Vec<String>>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut tags = Vec::new();
+        for (attribute, args) in attributes {
+            match attribute.as_str() {
+                "export" => {}
+                "load" | "tick" => {
+                    if !args.is_empty() {
+                        bail!("#[{}] on function {} takes no arguments", attribute, name);
+                    }
+                    tags.push(format!("minecraft:{}", attribute));
+                }
+                "tag" => {
+                    if args.is_empty() {
+                        bail!("#[tag] on function {} needs the name of a tag", name);
+                    }
+                    for tag in args {
+                        let tag = if tag.contains(':') {
+                            tag.clone()
+                        } else {
+                            self.resource_location(tag)
+                        };
+                        if !is_resource_location(&tag) {
+                            bail!("{} is not a valid function tag", tag);
+                        }
+                        tags.push(tag);
+                    }
+                }
+                _ => bail!(
+                    "unknown attribute #[{}] on function {}, expected one of {}",
+                    attribute,
+                    name,
+                    ATTRIBUTES.join(", ")
+                ),
+            }
+        }
+        Ok(tags)
+    }
+
+    /// The location `#[export("ns:path")]` pins the function `name` to. Like tags
+    /// the namespace defaults to the project's.
+    fn export_location(
+        &self,
+        name: &str,
+        attributes: &HashMap<String, Vec<String>>,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(args) = attributes.get("export") else {
+            return Ok(None);
+        };
+        let [export] = args.as_slice() else {
+            bail!("#[export] on function {} needs exactly one location", name);
+        };
+        let export = if export.contains(':') {
+            export.clone()
+        } else {
+            self.resource_location(export)
+        };
+        if !is_resource_location(&export) {
+            bail!("{} is not a valid resource location", export);
+        }
+        Ok(Some(export))
+    }
+
+    pub fn resource_location(&self, path: &str) -> String {
+        format!("{}:{}", self.namespace, path)
+    }
+
+    /// Objective of the score `name` every entity has, `<namespace>.<name>`.
+    pub fn score_objective(&self, name: &str) -> String {
+        format!("{}.{}", self.namespace, name)
+    }
+
+    /// Resource location of the function `name`, see `function_path`, unless it is
+    /// pinned by `#[export]`. Functions that are not `pub` are kept in `setup::PRIVATE`.
+    pub fn function_location(&self, name: &str) -> String {
+        match self.exports.get(name) {
+            Some(export) => export.clone(),
+            None if self.public.contains(name) => self.resource_location(&function_path(name)),
+            None => self.resource_location(&format!("{}/{}", setup::PRIVATE, function_path(name))),
+        }
+    }
+
+    /// Resource location of a function generated for part of the function `name`.
+    /// They are kept apart from the functions of the program in the reserved folder,
+    /// `main`'s first `if` is `<namespace>:_efs/main/if0`.
+    pub fn helper_location(&self, name: &str, helper: &str) -> String {
+        self.resource_location(&format!(
+            "{}/{}/{}",
+            setup::RESERVED,
+            function_path(name),
+            helper
+        ))
+    }
+}
+
+pub fn compile(namespace: &str, programs: &[Program]) -> anyhow::Result<Datapack> {
+    let mut compiler = Compiler::new(namespace);
+    compiler.compile(programs)?;
+    Ok(compiler.datapack)
+}
+
+/// Where an `extern` variable is, `"<holder> <objective>"` for a score and
+/// `"<namespace:id> <path>"` for storage.
+fn extern_location(variable: &ExternVariable) -> anyhow::Result<Location> {
+    let parts = variable.location.split_whitespace().collect::<Vec<_>>();
+    let [first, second] = parts.as_slice() else {
+        bail!(
+            "the location of extern {} must be two words, found \"{}\"",
+            variable.name,
+            variable.location
+        );
+    };
+    Ok(match variable.kind {
+        ExternKind::Score => {
+            if !variable.var_type.is_score_type() {
+                bail!(
+                    "extern score {} must have a score type, found {:?}",
+                    variable.name,
+                    variable.var_type
+                );
+            }
+            Location::Score(Score::new(*first, *second))
+        }
+        ExternKind::Storage => {
+            if !is_resource_location(first) {
+                bail!("{} is not a valid storage", first);
+            }
+            Location::Storage(StoragePath::new(*first, *second))
+        }
+    })
+}
+
+/// Names of the `pub` functions of `declaration`, `Type::function` inside `impl Type`.
+fn public_functions(declaration: &Declaration) -> Vec<String> {
+    let public = |declaration: &Declaration| match declaration {
+        Declaration::Public(function) => match function.item() {
+            Declaration::FunctionDec { name, .. } => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match declaration.item() {
+        Declaration::Impl(type_name, functions) => functions
+            .iter()
+            .filter_map(public)
+            .map(|name| format!("{}::{}", type_name, name))
+            .collect(),
+        _ => public(declaration).into_iter().collect(),
+    }
+}
+
+/// Path of the function `name` inside the namespace. Modules and types become
+/// folders and every segment is converted to snake case, so `util::MyPoint::new`
+/// is `util/my_point/new`. A run of capitals is one word, `HTTPServer` is `http_server`.
+pub fn function_path(name: &str) -> String {
+    name.split("::")
+        .map(|segment| {
+            let chars = segment.chars().collect::<Vec<_>>();
+            let mut path = String::new();
+            for (index, c) in chars.iter().enumerate() {
+                if c.is_ascii_uppercase() {
+                    let previous = index.checked_sub(1).map(|index| chars[index]);
+                    let next = chars.get(index + 1);
+                    let starts_word = previous.is_some_and(|previous| {
+                        !previous.is_ascii_uppercase()
+                            || next.is_some_and(|next| next.is_ascii_lowercase())
+                    });
+                    if starts_word && !path.ends_with('_') {
+                        path.push('_');
+                    }
+                    path.push(c.to_ascii_lowercase());
+                } else {
+                    path.push(*c);
+                }
+            }
+            path
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether `location` is a `namespace:path` Minecraft accepts.
+pub fn is_resource_location(location: &str) -> bool {
+    let Some((namespace, path)) = location.split_once(':') else {
+        return false;
+    };
+    let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-.".contains(c);
+    !namespace.is_empty()
+        && !path.is_empty()
+        && namespace.chars().all(valid)
+        && path.chars().all(|c| valid(c) || c == '/')
+}
+
+/// Where the value of a variable lives at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Location {
+    Score(Score),
+    Storage(StoragePath),
+}
+
+impl Location {
+    /// Copies the value at `source` into this location, converting between scores
+    /// and storage with `execute store` when the two differ.
+    pub fn copy_from(&self, source: &Location, var_type: &EFSType) -> String {
+        match (self, source) {
+            (Location::Score(to), Location::Score(from)) => to.copy_from(from),
+            (Location::Storage(to), Location::Storage(from)) => to.set_from(from),
+            (Location::Score(to), Location::Storage(from)) => from.store_to_score(to, 1.0),
+            (Location::Storage(to), Location::Score(from)) => {
+                to.store_from_score(from, var_type.store_type().unwrap_or("int"), 1.0)
+            }
+        }
+    }
+
+    /// Like `copy_from`, but an optional that is `None` at `source` ends up `None` here
+    /// too instead of leaving the old value.
+    pub fn assign_from(&self, source: &Location, var_type: &EFSType) -> Vec<String> {
+        let copy = self.copy_from(source, var_type);
+        match (self, source, var_type) {
+            (Location::Score(_), Location::Score(_), _) => vec![copy],
+            (_, _, EFSType::Option(_)) => vec![
+                self.clear(),
+                format!("execute {} run {}", source.present(), copy),
+            ],
+            _ => vec![copy],
+        }
+    }
+
+    /// Makes the optional at this location `None`.
+    pub fn clear(&self) -> String {
+        match self {
+            Location::Score(score) => score.set(ABSENT),
+            Location::Storage(path) => path.remove(),
+        }
+    }
+
+    /// `execute` condition that holds when the optional at this location is not `None`.
+    pub fn present(&self) -> String {
+        match self {
+            Location::Score(score) => format!("unless score {} matches {}", score, ABSENT),
+            Location::Storage(path) => format!("if data {}", path),
+        }
+    }
+}
+
+/// Assigns a runtime location to every variable: numbers and bools get a score,
+/// everything else gets a path in `<namespace>:vars`.
+pub struct Allocator {
+    pub scoreboard: Scoreboard,
+    pub storage: Storage,
+}
+
+impl Allocator {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            scoreboard: Scoreboard::new(namespace),
+            storage: Storage::new(namespace),
+        }
+    }
+
+    pub fn allocate(&mut self, var_type: &EFSType) -> Location {
+        if var_type.is_score_type() {
+            Location::Score(self.scoreboard.allocate())
+        } else {
+            Location::Storage(self.storage.allocate())
+        }
+    }
+
+    /// Register the `index`th argument of a call is passed in: `$arg<index>` for
+    /// numbers, `frame.arg<index>` in storage for everything else.
+    pub fn argument(&self, index: usize, var_type: &EFSType) -> Location {
+        if var_type.is_score_type() {
+            Location::Score(Score::new(
+                format!("$arg{}", index),
+                self.scoreboard.objective(),
+            ))
+        } else {
+            Location::Storage(self.storage.path(format!("frame.arg{}", index)))
+        }
+    }
+
+    /// Register a function leaves its return value in, `$ret` or `frame.ret`.
+    pub fn return_value(&self, var_type: &EFSType) -> Location {
+        if var_type.is_score_type() {
+            Location::Score(Score::new("$ret", self.scoreboard.objective()))
+        } else {
+            Location::Storage(self.storage.path("frame.ret"))
+        }
+    }
+
+    /// Register a method that may change `self` leaves it in for the caller to copy
+    /// back into the receiver, `$self` or `frame.self`.
+    pub fn receiver(&self, var_type: &EFSType) -> Location {
+        if var_type.is_score_type() {
+            Location::Score(Score::new("$self", self.scoreboard.objective()))
+        } else {
+            Location::Storage(self.storage.path("frame.self"))
+        }
+    }
+
+    /// Compound an `extern fn` gets its arguments from, as macro arguments
+    /// named after its parameters.
+    pub fn macro_arguments(&self) -> StoragePath {
+        self.storage.path("extern")
+    }
+
+    /// Where `as` casts wrap numbers that are narrowed into a score.
+    pub fn scratch(&self) -> StoragePath {
+        self.storage.path("scratch")
+    }
+
+    /// Frames of recursive functions, see `recursion::push_frame`.
+    pub fn call_stack(&self) -> StoragePath {
+        self.storage.path("stack")
+    }
+
+    /// Set while a `return`, `break` or `continue` inside a nested block unwinds to
+    /// the function or loop it belongs to.
+    pub fn control(&self) -> Score {
+        Score::new("$control", self.scoreboard.objective())
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::parser::{
+    ast::{
+        self, CodeBlock, Declaration, Expression, MatchArm, Math, Pattern, Program, Statement,
+        Value,
+    },
+    types::EFSType,
+};
+
+use super::{commands, stdlib};
+
+/// A source file, other files know it by its module path: `src/util/math.efs` is `util::math`.
+pub struct Module {
+    pub name: String,
+    pub file: PathBuf,
+    pub program: Program,
+}
+
+impl Module {
+    /// Module path of `file` in the source directory `src`.
+    pub fn name_of(src: &Path, file: &Path) -> anyhow::Result<String> {
+        let relative = file
+            .strip_prefix(src)
+            .with_context(|| format!("{} is not in {}", file.display(), src.display()))?;
+        Ok(relative
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("::"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ItemKind {
+    Function,
+    Type,
+    /// A `const` or an `extern` variable, used by name as a value.
+    Constant,
+}
+
+struct Item {
+    kind: ItemKind,
+    public: bool,
+}
+
+/// Where a name in the scope of a module comes from.
+#[derive(PartialEq)]
+enum Source {
+    Local,
+    Use,
+    /// `use "path"` of the module.
+    File(String),
+}
+
+/// Joins `modules` into programs the compiler takes as a whole. Items are renamed to
+/// `module::item` and every name is looked up in the module it is used in: its own items,
+/// what it imports with `use`, or a `module::item` path. Fails on missing files and
+/// modules, private items used from other modules and import cycles. `pub` stays on
+/// the items for the compiler, see `Compiler::public`. `std::cmd` is there without a
+/// file, see `stdlib`.
+pub fn link(modules: &[Module]) -> anyhow::Result<Vec<Program>> {
+    let mut items = HashMap::new();
+    for module in modules {
+        if module.name == "std" || module.name.starts_with("std::") {
+            bail!(
+                "module {} can not be defined, std is the standard library",
+                module.name
+            );
+        }
+        items.insert(module.name.as_str(), module_items(&module.program));
+    }
+    let builtins = stdlib::functions()
+        .map(|name| {
+            let item = Item {
+                kind: ItemKind::Function,
+                public: true,
+            };
+            (name.to_string(), item)
+        })
+        .collect();
+    items.insert(stdlib::MODULE, builtins);
+
+    let mut scopes = Vec::new();
+    let mut imports = HashMap::new();
+    for module in modules {
+        let (scope, aliases, uses) = module_scope(module, modules, &items)
+            .with_context(|| format!("in module {}", module.name))?;
+        scopes.push((scope, aliases));
+        imports.insert(module.name.as_str(), uses);
+    }
+    if let Some(cycle) = import_cycle(&imports) {
+        bail!("import cycle: {}", cycle.join(" -> "));
+    }
+
+    let mut programs = Vec::new();
+    for (module, (scope, aliases)) in modules.iter().zip(&scopes) {
+        let mut linker = Linker {
+            module: &module.name,
+            items: &items,
+            scope,
+            aliases,
+            locals: Vec::new(),
+        };
+        let mut declarations = Vec::new();
+        for declaration in &module.program.0 {
+            let linked = linker
+                .declaration(declaration)
+                .with_context(|| format!("in module {}", module.name))?;
+            declarations.extend(linked);
+        }
+        programs.push(Program(declarations, module.program.1.clone()));
+    }
+    Ok(programs)
+}
+
+/// The items a module declares. Functions of `impl` blocks are `Type::function`.
+fn module_items(program: &Program) -> HashMap<String, Item> {
+    let mut items = HashMap::new();
+    for declaration in &program.0 {
+        let public = matches!(declaration, Declaration::Public(_));
+        let (name, kind) = match declaration.item() {
+            Declaration::FunctionDec { name, .. } | Declaration::ExternFunction { name, .. } => {
+                (name.clone(), ItemKind::Function)
+            }
+            Declaration::StructDef(name, _) | Declaration::EnumDef(name, _) => {
+                (name.clone(), ItemKind::Type)
+            }
+            Declaration::ConstDec(constant) => (constant.name.clone(), ItemKind::Constant),
+            Declaration::ExternVariable(variable) => (variable.name.clone(), ItemKind::Constant),
+            Declaration::Impl(type_name, functions) => {
+                for function in functions {
+                    if let Declaration::FunctionDec { name, .. } = function.item() {
+                        let public = matches!(function, Declaration::Public(_));
+                        let kind = ItemKind::Function;
+                        items.insert(format!("{}::{}", type_name, name), Item { kind, public });
+                    }
+                }
+                continue;
+            }
+            // Scores of entities are shared by all modules, like the objective they are on.
+            Declaration::UseFile(_)
+            | Declaration::Use(_)
+            | Declaration::Public(_)
+            | Declaration::EntityScore(..) => continue,
+        };
+        items.insert(name, Item { kind, public });
+    }
+    items
+}
+
+type Scope = HashMap<String, (String, ItemKind, Source)>;
+
+/// Names `module` can use without a path, the modules it imports under their last
+/// segment with `use module;`, and every module it imports from.
+fn module_scope(
+    module: &Module,
+    modules: &[Module],
+    items: &HashMap<&str, HashMap<String, Item>>,
+) -> anyhow::Result<(Scope, HashMap<String, String>, Vec<String>)> {
+    let mut scope = Scope::new();
+    for (name, item) in &items[module.name.as_str()] {
+        let qualified = format!("{}::{}", module.name, name);
+        scope.insert(name.clone(), (qualified, item.kind, Source::Local));
+    }
+    let mut aliases = HashMap::new();
+    let mut uses = Vec::new();
+
+    for declaration in &module.program.0 {
+        match declaration {
+            Declaration::Use(path) => {
+                let full = path.join("::");
+                if items.contains_key(full.as_str()) {
+                    aliases.insert(path.last().unwrap().clone(), full.clone());
+                    uses.push(full);
+                    continue;
+                }
+                let (name, prefix) = path.split_last().unwrap();
+                let from = prefix.join("::");
+                let Some(module_items) = items.get(from.as_str()) else {
+                    bail!("unknown module {}", full);
+                };
+                let item = module_items
+                    .get(name)
+                    .ok_or_else(|| anyhow!("module {} has no item {}", from, name))?;
+                if !item.public && from != module.name {
+                    bail!("{} is private to module {}", name, from);
+                }
+                match scope.get(name) {
+                    Some((qualified, ..)) if *qualified == full => {}
+                    Some((_, _, Source::Local)) => {
+                        bail!("use of {} conflicts with the {} defined here", full, name)
+                    }
+                    Some((_, _, Source::File(_))) | None => {}
+                    Some(_) => bail!("{} is imported more than once", name),
+                }
+                scope.insert(name.clone(), (full, item.kind, Source::Use));
+                uses.push(from);
+            }
+            Declaration::UseFile(path) => {
+                let file = use_file(&module.file, path);
+                let target = modules
+                    .iter()
+                    .find(|module| module.file == file)
+                    .ok_or_else(|| anyhow!("cannot find {}", path.display()))?;
+                for (name, item) in &items[target.name.as_str()] {
+                    if !item.public || name.contains("::") {
+                        continue;
+                    }
+                    let qualified = format!("{}::{}", target.name, name);
+                    match scope.get(name) {
+                        Some((existing, ..)) if *existing == qualified => {}
+                        Some((_, _, Source::File(other))) => bail!(
+                            "{} is imported from both {} and {}",
+                            name,
+                            other,
+                            target.name
+                        ),
+                        // Items of the module itself and single imports win over whole files.
+                        Some(_) => {}
+                        None => {
+                            let source = Source::File(target.name.clone());
+                            scope.insert(name.clone(), (qualified, item.kind, source));
+                        }
+                    }
+                }
+                uses.push(target.name.clone());
+            }
+            _ => {}
+        }
+    }
+    Ok((scope, aliases, uses))
+}
+
+/// File a `use "path"` in `from` refers to, relative to the directory of `from`.
+fn use_file(from: &Path, path: &Path) -> PathBuf {
+    let mut file = from.parent().unwrap_or(Path::new("")).join(path);
+    if file.extension().is_none() {
+        file.set_extension("efs");
+    }
+    let mut normalized = PathBuf::new();
+    for component in file.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The first cycle in the `use` graph, as the modules along it ending where it started.
+fn import_cycle(imports: &HashMap<&str, Vec<String>>) -> Option<Vec<String>> {
+    fn visit<'a>(
+        module: &'a str,
+        imports: &'a HashMap<&str, Vec<String>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|m| *m == module) {
+            let mut cycle = path[start..]
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>();
+            cycle.push(module.to_string());
+            return Some(cycle);
+        }
+        if !done.insert(module) {
+            return None;
+        }
+        path.push(module);
+        for import in imports.get(module).into_iter().flatten() {
+            if let Some(cycle) = visit(import, imports, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    let mut names = imports.keys().copied().collect::<Vec<_>>();
+    names.sort();
+    let mut done = HashSet::new();
+    names
+        .into_iter()
+        .find_map(|name| visit(name, imports, &mut Vec::new(), &mut done))
+}
+
+/// `#[export]` gives a function a name other packs rely on, so it has to be `pub`.
+fn private_export(declaration: &Declaration) -> anyhow::Result<()> {
+    match declaration {
+        Declaration::FunctionDec {
+            name, attributes, ..
+        } if attributes.contains_key("export") => {
+            bail!("#[export] on function {} needs it to be pub", name)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rewrites the declarations of one module to use qualified names.
+struct Linker<'a> {
+    module: &'a str,
+    items: &'a HashMap<&'a str, HashMap<String, Item>>,
+    scope: &'a Scope,
+    aliases: &'a HashMap<String, String>,
+    /// Parameters and `let`s, they shadow constants of the same name.
+    locals: Vec<HashSet<String>>,
+}
+
+impl Linker<'_> {
+    fn declaration(&mut self, declaration: &Declaration) -> anyhow::Result<Option<Declaration>> {
+        match declaration {
+            Declaration::Public(declaration) => Ok(self
+                .linked(declaration)?
+                .map(|declaration| Declaration::Public(Box::new(declaration)))),
+            declaration => {
+                private_export(declaration)?;
+                self.linked(declaration)
+            }
+        }
+    }
+
+    fn linked(&mut self, declaration: &Declaration) -> anyhow::Result<Option<Declaration>> {
+        Ok(Some(match declaration {
+            Declaration::Public(declaration) => return self.linked(declaration),
+            Declaration::UseFile(_) | Declaration::Use(_) => return Ok(None),
+            Declaration::FunctionDec { name, .. } => {
+                let qualified = format!("{}::{}", self.module, name);
+                self.function(declaration, qualified)
+                    .with_context(|| format!("in function {}", name))?
+            }
+            Declaration::Impl(type_name, functions) => {
+                let mut linked = Vec::new();
+                for function in functions {
+                    if !matches!(function, Declaration::Public(_)) {
+                        private_export(function)?;
+                    }
+                    if let Declaration::FunctionDec { name, .. } = function.item() {
+                        let linked_function = self
+                            .function(function.item(), name.clone())
+                            .with_context(|| format!("in function {}::{}", type_name, name))?;
+                        linked.push(match function {
+                            Declaration::Public(_) => {
+                                Declaration::Public(Box::new(linked_function))
+                            }
+                            _ => linked_function,
+                        });
+                    }
+                }
+                Declaration::Impl(self.type_name(type_name)?, linked)
+            }
+            Declaration::StructDef(name, fields) => Declaration::StructDef(
+                format!("{}::{}", self.module, name),
+                fields
+                    .iter()
+                    .map(|(field, var_type)| Ok((field.clone(), self.var_type(var_type)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Declaration::EnumDef(name, variants) => Declaration::EnumDef(
+                format!("{}::{}", self.module, name),
+                variants
+                    .iter()
+                    .map(|(variant, payload)| {
+                        let payload = payload
+                            .iter()
+                            .map(|var_type| self.var_type(var_type))
+                            .collect::<anyhow::Result<_>>()?;
+                        Ok((variant.clone(), payload))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Declaration::ConstDec(constant) => {
+                self.locals = Vec::new();
+                let mut constant = constant.clone();
+                constant.name = format!("{}::{}", self.module, constant.name);
+                constant.var_type = constant
+                    .var_type
+                    .map(|var_type| self.var_type(&var_type))
+                    .transpose()?;
+                constant.var = self.value(&constant.var)?;
+                Declaration::ConstDec(constant)
+            }
+            Declaration::ExternFunction {
+                name,
+                parameters,
+                return_type,
+                location,
+            } => Declaration::ExternFunction {
+                name: format!("{}::{}", self.module, name),
+                parameters: parameters
+                    .iter()
+                    .map(|(name, var_type)| Ok((name.clone(), self.var_type(var_type)?)))
+                    .collect::<anyhow::Result<_>>()?,
+                return_type: self.var_type(return_type)?,
+                location: location.clone(),
+            },
+            Declaration::ExternVariable(variable) => {
+                let mut variable = variable.clone();
+                variable.name = format!("{}::{}", self.module, variable.name);
+                variable.var_type = self.var_type(&variable.var_type)?;
+                Declaration::ExternVariable(variable)
+            }
+            Declaration::EntityScore(name, var_type) => {
+                Declaration::EntityScore(name.clone(), self.var_type(var_type)?)
+            }
+        }))
+    }
+
+    fn function(&mut self, function: &Declaration, name: String) -> anyhow::Result<Declaration> {
+        let Declaration::FunctionDec {
+            is_static,
+            attributes,
+            parameters,
+            return_type,
+            code_block,
+            ..
+        } = function
+        else {
+            unreachable!()
+        };
+        self.locals = vec![parameters.iter().map(|(name, _)| name.clone()).collect()];
+        Ok(Declaration::FunctionDec {
+            is_static: *is_static,
+            attributes: attributes.clone(),
+            name,
+            parameters: parameters
+                .iter()
+                .map(|(name, var_type)| Ok((name.clone(), self.var_type(var_type)?)))
+                .collect::<anyhow::Result<_>>()?,
+            return_type: self.var_type(return_type)?,
+            code_block: self.block(code_block)?,
+        })
+    }
+
+    /// `path` as a module, either imported by `use module;` or written out in full.
+    fn module_path(&self, path: &str) -> Option<String> {
+        let (first, rest) = match path.split_once("::") {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (path, None),
+        };
+        let module = match (self.aliases.get(first), rest) {
+            (Some(alias), Some(rest)) => format!("{}::{}", alias, rest),
+            (Some(alias), None) => alias.clone(),
+            (None, _) => path.to_string(),
+        };
+        self.items.contains_key(module.as_str()).then_some(module)
+    }
+
+    /// Qualified name of `name` in `module`, failing when this module may not use it.
+    fn item(&self, module: &str, name: &str) -> anyhow::Result<(String, ItemKind)> {
+        let item = self.items[module]
+            .get(name)
+            .ok_or_else(|| anyhow!("module {} has no item {}", module, name))?;
+        if !item.public && module != self.module {
+            bail!("{} is private to module {}", name, module);
+        }
+        Ok((format!("{}::{}", module, name), item.kind))
+    }
+
+    /// Qualified name of the struct or enum `name`, unknown types are left to the checker.
+    fn type_name(&self, name: &str) -> anyhow::Result<String> {
+        Ok(self.find_type(name)?.unwrap_or_else(|| name.to_string()))
+    }
+
+    fn find_type(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let Some((prefix, last)) = name.rsplit_once("::") else {
+            return Ok(match self.scope.get(name) {
+                Some((qualified, ItemKind::Type, _)) => Some(qualified.clone()),
+                _ => None,
+            });
+        };
+        let Some(module) = self.module_path(prefix) else {
+            return Ok(None);
+        };
+        match self.item(&module, last)? {
+            (qualified, ItemKind::Type) => Ok(Some(qualified)),
+            _ => Ok(None),
+        }
+    }
+
+    fn var_type(&self, var_type: &EFSType) -> anyhow::Result<EFSType> {
+        Ok(match var_type {
+            EFSType::Struct(name) => EFSType::Struct(self.type_name(name)?),
+            EFSType::List(element_type) => EFSType::List(Box::new(self.var_type(element_type)?)),
+            EFSType::Dict(value_type) => EFSType::Dict(Box::new(self.var_type(value_type)?)),
+            EFSType::Option(var_type) => EFSType::Option(Box::new(self.var_type(var_type)?)),
+            _ => var_type.clone(),
+        })
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.locals.iter().any(|scope| scope.contains(name))
+    }
+
+    fn declare(&mut self, name: &str) {
+        self.locals.last_mut().unwrap().insert(name.to_string());
+    }
+
+    fn scoped<T>(
+        &mut self,
+        names: impl IntoIterator<Item = String>,
+        link: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        self.locals.push(names.into_iter().collect());
+        let result = link(self);
+        self.locals.pop();
+        result
+    }
+
+    fn block(&mut self, block: &CodeBlock) -> anyhow::Result<CodeBlock> {
+        self.scoped([], |this| {
+            Ok(CodeBlock {
+                statements: block
+                    .statements
+                    .iter()
+                    .map(|statement| this.statement(statement))
+                    .collect::<anyhow::Result<_>>()?,
+                positions: block.positions.clone(),
+                names: block.names.clone(),
+                end: block.end,
+            })
+        })
+    }
+
+    fn statement(&mut self, statement: &Statement) -> anyhow::Result<Statement> {
+        Ok(match statement {
+            Statement::For(init, condition, step, block) => self.scoped([], |this| {
+                Ok(Statement::For(
+                    Box::new(this.statement(init)?),
+                    this.value(condition)?,
+                    Box::new(this.statement(step)?),
+                    this.block(block)?,
+                ))
+            })?,
+            Statement::ForList(item, list, block) => {
+                let list = self.variable(list);
+                let block = self.scoped([item.clone()], |this| this.block(block))?;
+                Statement::ForList(item.clone(), list, block)
+            }
+            Statement::StaticFor(name, start, end, step, block) => {
+                let block = self.scoped([name.clone()], |this| this.block(block))?;
+                Statement::StaticFor(name.clone(), *start, *end, *step, block)
+            }
+            Statement::While(condition, block) => {
+                Statement::While(self.value(condition)?, self.block(block)?)
+            }
+            Statement::If(condition, block) => {
+                Statement::If(self.value(condition)?, self.block(block)?)
+            }
+            Statement::IfLet(name, value, block) => {
+                let value = self.value(value)?;
+                let block = self.scoped([name.clone()], |this| this.block(block))?;
+                Statement::IfLet(name.clone(), value, block)
+            }
+            Statement::Match(value, arms) => {
+                let value = self.value(value)?;
+                let mut linked = Vec::new();
+                for arm in arms {
+                    let (pattern, bindings) = match &arm.pattern {
+                        Pattern::Wildcard => (Pattern::Wildcard, Vec::new()),
+                        Pattern::Variant(name, variant, bindings) => (
+                            Pattern::Variant(
+                                self.type_name(name)?,
+                                variant.clone(),
+                                bindings.clone(),
+                            ),
+                            bindings.iter().flatten().cloned().collect(),
+                        ),
+                    };
+                    let block = self.scoped(bindings, |this| this.block(&arm.block))?;
+                    linked.push(MatchArm { pattern, block });
+                }
+                Statement::Match(value, linked)
+            }
+            Statement::Return(value) => {
+                Statement::Return(value.as_ref().map(|value| self.value(value)).transpose()?)
+            }
+            Statement::Commands(commands) => Statement::Commands(
+                commands
+                    .iter()
+                    // A command that does not parse is reported by the type checker.
+                    .map(|command| {
+                        commands::rename(command, |name| self.variable(name))
+                            .unwrap_or_else(|_| command.clone())
+                    })
+                    .collect(),
+            ),
+            Statement::Execute(contexts, block) => {
+                let mut linked = Vec::new();
+                for context in contexts {
+                    linked.push(match context {
+                        ast::Context::As(selector) => ast::Context::As(self.value(selector)?),
+                        ast::Context::At(selector) => ast::Context::At(self.value(selector)?),
+                        context => context.clone(),
+                    });
+                }
+                Statement::Execute(linked, self.block(block)?)
+            }
+            Statement::Break => Statement::Break,
+            Statement::Continue => Statement::Continue,
+            Statement::Expression(Expression::VarDec(name, var_type, value)) => {
+                let statement = Statement::Expression(Expression::VarDec(
+                    name.clone(),
+                    var_type
+                        .as_ref()
+                        .map(|var_type| self.var_type(var_type))
+                        .transpose()?,
+                    self.value(value)?,
+                ));
+                self.declare(name);
+                statement
+            }
+            Statement::Expression(Expression::Assign(name, value)) => {
+                Statement::Expression(Expression::Assign(self.variable(name), self.value(value)?))
+            }
+            Statement::Expression(Expression::AssignField(target, field, op, value)) => {
+                Statement::Expression(Expression::AssignField(
+                    self.value(target)?,
+                    field.clone(),
+                    op.clone(),
+                    self.value(value)?,
+                ))
+            }
+            Statement::Expression(Expression::Value(value)) => {
+                Statement::Expression(Expression::Value(self.value(value)?))
+            }
+        })
+    }
+
+    /// `name` as a variable, constants of the module and its imports get their qualified name.
+    fn variable(&self, name: &str) -> String {
+        if self.is_local(name) {
+            return name.to_string();
+        }
+        match self.scope.get(name) {
+            Some((qualified, ItemKind::Constant, _)) => qualified.clone(),
+            _ => name.to_string(),
+        }
+    }
+
+    fn values(&mut self, values: &[Value]) -> anyhow::Result<Vec<Value>> {
+        values.iter().map(|value| self.value(value)).collect()
+    }
+
+    fn value(&mut self, value: &Value) -> anyhow::Result<Value> {
+        Ok(match value {
+            Value::Value(_) => value.clone(),
+            Value::Identifier(name) => Value::Identifier(self.variable(name)),
+            Value::Math(math) => Value::Math(Box::new(Math {
+                left: self.value(&math.left)?,
+                op: math.op.clone(),
+                right: self.value(&math.right)?,
+            })),
+            Value::List(values) => Value::List(self.values(values)?),
+            Value::Dict(fields) => Value::Dict(
+                fields
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), self.value(value)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Value::Struct(name, fields) => Value::Struct(
+                self.type_name(name)?,
+                fields
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), self.value(value)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Value::Call(name, args) => {
+                let name = match self.scope.get(name) {
+                    Some((qualified, ItemKind::Function, _)) => qualified.clone(),
+                    _ => name.clone(),
+                };
+                Value::Call(name, self.values(args)?)
+            }
+            Value::MethodCall(value, method, args) => Value::MethodCall(
+                Box::new(self.value(value)?),
+                method.clone(),
+                self.values(args)?,
+            ),
+            Value::Text(parts) => Value::Text(self.values(parts)?),
+            Value::Cast(value, var_type) => {
+                Value::Cast(Box::new(self.value(value)?), self.var_type(var_type)?)
+            }
+            Value::Field(value, field) => Value::Field(Box::new(self.value(value)?), field.clone()),
+            Value::OptionalField(value, field) => {
+                Value::OptionalField(Box::new(self.value(value)?), field.clone())
+            }
+            Value::Index(value, index) => {
+                Value::Index(Box::new(self.value(value)?), Box::new(self.value(index)?))
+            }
+            Value::Variant(prefix, member, args) => self.path(prefix, member, args)?,
+        })
+    }
+
+    /// `prefix::member`, an enum variant or function of a type, or an item of a module.
+    fn path(&mut self, prefix: &str, member: &str, args: &[Value]) -> anyhow::Result<Value> {
+        let args = self.values(args)?;
+        if let Some(type_name) = self.find_type(prefix)? {
+            let (module, short) = type_name.rsplit_once("::").unwrap();
+            let function = format!("{}::{}", short, member);
+            if self.items[module].contains_key(&function) {
+                self.item(module, &function)?;
+            }
+            return Ok(Value::Variant(type_name, member.to_string(), args));
+        }
+        let Some(module) = self.module_path(prefix) else {
+            return Ok(Value::Variant(prefix.to_string(), member.to_string(), args));
+        };
+        match self.item(&module, member)? {
+            (qualified, ItemKind::Function) => Ok(Value::Call(qualified, args)),
+            (qualified, ItemKind::Constant) if args.is_empty() => Ok(Value::Identifier(qualified)),
+            (qualified, ItemKind::Constant) => bail!("{} is a constant, not a function", qualified),
+            (qualified, ItemKind::Type) => bail!("{} is a type, not a value", qualified),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{
+    ast::{CodeBlock, Context, Expression, Statement, Value},
+    types::EFSValueType,
+};
+
+use super::{storage::StoragePath, Location};
+
+/// Functions that can end up calling themselves, found as the cycles of the call graph.
+/// A `::method` call may reach the method of that name of every type.
+pub fn recursive_functions(call_graph: &HashMap<String, HashSet<String>>) -> HashSet<String> {
+    let call_graph = &call_graph
+        .iter()
+        .map(|(name, callees)| {
+            let callees = callees
+                .iter()
+                .flat_map(|callee| match callee.strip_prefix("::") {
+                    Some(method) => call_graph
+                        .keys()
+                        .filter(|name| name.rsplit_once("::").is_some_and(|(_, m)| m == method))
+                        .cloned()
+                        .collect(),
+                    None => vec![callee.clone()],
+                })
+                .collect();
+            (name.clone(), callees)
+        })
+        .collect();
+    let mut tarjan = Tarjan {
+        call_graph,
+        index: HashMap::new(),
+        low_link: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        recursive: HashSet::new(),
+    };
+
+    let mut names = call_graph.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        if !tarjan.index.contains_key(name.as_str()) {
+            tarjan.visit(name);
+        }
+    }
+    tarjan.recursive
+}
+
+/// Tarjan's strongly connected components, a component is recursive when it has
+/// more than one function or a function that calls itself.
+struct Tarjan<'a> {
+    call_graph: &'a HashMap<String, HashSet<String>>,
+    index: HashMap<&'a str, usize>,
+    low_link: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    recursive: HashSet<String>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, name: &'a str) {
+        let index = self.index.len();
+        self.index.insert(name, index);
+        self.low_link.insert(name, index);
+        self.stack.push(name);
+        self.on_stack.insert(name);
+
+        for callee in self.call_graph.get(name).into_iter().flatten() {
+            if !self.call_graph.contains_key(callee) {
+                continue;
+            }
+            if !self.index.contains_key(callee.as_str()) {
+                self.visit(callee);
+                let low_link = self.low_link[name].min(self.low_link[callee.as_str()]);
+                self.low_link.insert(name, low_link);
+            } else if self.on_stack.contains(callee.as_str()) {
+                let low_link = self.low_link[name].min(self.index[callee.as_str()]);
+                self.low_link.insert(name, low_link);
+            }
+        }
+
+        if self.low_link[name] == self.index[name] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member);
+                if member == name {
+                    break;
+                }
+            }
+            let calls_itself = self.call_graph[name].contains(name);
+            if component.len() > 1 || calls_itself {
+                self.recursive
+                    .extend(component.into_iter().map(String::from));
+            }
+        }
+    }
+}
+
+/// Names of the functions called anywhere in `block`. The type of a receiver is not
+/// known here, so `value.method()` is recorded as `::method`, see `recursive_functions`.
+pub fn calls(block: &CodeBlock) -> HashSet<String> {
+    let mut calls = HashSet::new();
+    block_calls(block, &mut calls);
+    calls
+}
+
+fn block_calls(block: &CodeBlock, calls: &mut HashSet<String>) {
+    for statement in &block.statements {
+        statement_calls(statement, calls);
+    }
+}
+
+fn statement_calls(statement: &Statement, calls: &mut HashSet<String>) {
+    match statement {
+        Statement::For(init, condition, step, block) => {
+            statement_calls(init, calls);
+            value_calls(condition, calls);
+            statement_calls(step, calls);
+            block_calls(block, calls);
+        }
+        Statement::ForList(_, _, block) | Statement::StaticFor(_, _, _, _, block) => {
+            block_calls(block, calls)
+        }
+        Statement::While(condition, block)
+        | Statement::If(condition, block)
+        | Statement::IfLet(_, condition, block) => {
+            value_calls(condition, calls);
+            block_calls(block, calls);
+        }
+        Statement::Match(value, arms) => {
+            value_calls(value, calls);
+            arms.iter().for_each(|arm| block_calls(&arm.block, calls));
+        }
+        Statement::Return(value) => value.iter().for_each(|value| value_calls(value, calls)),
+        Statement::Execute(contexts, block) => {
+            contexts
+                .iter()
+                .filter_map(Context::selector)
+                .for_each(|selector| value_calls(selector, calls));
+            block_calls(block, calls);
+        }
+        Statement::Break | Statement::Continue | Statement::Commands(_) => {}
+        Statement::Expression(Expression::VarDec(_, _, value))
+        | Statement::Expression(Expression::Assign(_, value))
+        | Statement::Expression(Expression::Value(value)) => value_calls(value, calls),
+        Statement::Expression(Expression::AssignField(target, _, _, value)) => {
+            value_calls(target, calls);
+            value_calls(value, calls);
+        }
+    }
+}
+
+fn value_calls(value: &Value, calls: &mut HashSet<String>) {
+    match value {
+        Value::Call(name, args) => {
+            calls.insert(name.clone());
+            args.iter().for_each(|arg| value_calls(arg, calls));
+        }
+        Value::MethodCall(value, method, args) => {
+            calls.insert(format!("::{}", method));
+            value_calls(value, calls);
+            args.iter().for_each(|arg| value_calls(arg, calls));
+        }
+        Value::Variant(_, _, args) => args.iter().for_each(|arg| value_calls(arg, calls)),
+        Value::Math(math) => {
+            value_calls(&math.left, calls);
+            value_calls(&math.right, calls);
+        }
+        Value::List(values) | Value::Text(values) => {
+            values.iter().for_each(|value| value_calls(value, calls))
+        }
+        Value::Dict(values) | Value::Struct(_, values) => {
+            values.values().for_each(|value| value_calls(value, calls))
+        }
+        Value::Cast(value, _) | Value::Field(value, _) | Value::OptionalField(value, _) => {
+            value_calls(value, calls)
+        }
+        Value::Index(value, index) => {
+            value_calls(value, calls);
+            value_calls(index, calls);
+        }
+        Value::Value(_) | Value::Identifier(_) => {}
+    }
+}
+
+/// Saves `locals` as a new frame on top of `stack`.
+pub fn push_frame(stack: &StoragePath, locals: &[Location]) -> Vec<String> {
+    let mut commands = vec![stack.append_value(&EFSValueType::Dict(HashMap::new()))];
+    for (index, local) in locals.iter().enumerate() {
+        let slot = stack.index(-1).field(&format!("l{}", index));
+        commands.push(match local {
+            Location::Score(score) => slot.store_from_score(score, "int", 1.0),
+            Location::Storage(path) => slot.set_from(path),
+        });
+    }
+    commands
+}
+
+/// Restores `locals` from the frame on top of `stack` and drops it. A storage local
+/// is removed first, so one that was `None` when the frame was pushed is again.
+pub fn pop_frame(stack: &StoragePath, locals: &[Location]) -> Vec<String> {
+    let mut commands = Vec::new();
+    for (index, local) in locals.iter().enumerate() {
+        let slot = stack.index(-1).field(&format!("l{}", index));
+        match local {
+            Location::Score(score) => commands.push(slot.store_to_score(score, 1.0)),
+            Location::Storage(path) => commands.extend([path.remove(), path.set_from(&slot)]),
+        }
+    }
+    commands.push(stack.index(-1).remove());
+    commands
+}
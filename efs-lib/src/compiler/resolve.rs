@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{
+    ast::{CodeBlock, Declaration, Expression, MatchArm, Math, Program, Statement, Value},
+    types::EFSType,
+};
+
+/// Copy of `programs` where every named type that is an enum has become
+/// `EFSType::Enum` or `EFSType::TaggedEnum`, the parser reads all of them as structs.
+/// The functions of `impl` blocks become plain functions named `Type::function`, and
+/// `pub` is dropped, see `modules::link` and `Compiler::public` for where it matters.
+pub fn resolve(programs: &[Program]) -> Vec<Program> {
+    let mut resolver = Resolver {
+        enums: HashMap::new(),
+        variants: HashSet::new(),
+        functions: HashSet::new(),
+    };
+    for declaration in programs.iter().flat_map(|program| &program.0) {
+        match declaration.item() {
+            Declaration::EnumDef(name, variants) => {
+                resolver
+                    .enums
+                    .insert(name.clone(), enum_type(name, variants));
+                resolver.variants.extend(
+                    variants
+                        .iter()
+                        .map(|(variant, _)| format!("{}::{}", name, variant)),
+                );
+            }
+            Declaration::Impl(name, functions) => {
+                resolver.functions.extend(
+                    functions
+                        .iter()
+                        .filter_map(|function| function_name(function.item()))
+                        .map(|function| format!("{}::{}", name, function)),
+                );
+            }
+            _ => {}
+        }
+    }
+    programs
+        .iter()
+        .map(|program| {
+            Program(
+                program
+                    .0
+                    .iter()
+                    .flat_map(|declaration| match declaration.item() {
+                        Declaration::Impl(name, functions) => functions
+                            .iter()
+                            .map(|function| resolver.method(name, function.item()))
+                            .collect(),
+                        declaration => vec![resolver.declaration(declaration)],
+                    })
+                    .collect(),
+                program.1.clone(),
+            )
+        })
+        .collect()
+}
+
+fn function_name(declaration: &Declaration) -> Option<&String> {
+    match declaration {
+        Declaration::FunctionDec { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// Type of the values of an enum with `variants`.
+pub fn enum_type(name: &str, variants: &[(String, Vec<EFSType>)]) -> EFSType {
+    if variants.iter().any(|(_, payload)| !payload.is_empty()) {
+        EFSType::TaggedEnum(name.to_string())
+    } else {
+        EFSType::Enum(name.to_string())
+    }
+}
+
+struct Resolver {
+    enums: HashMap<String, EFSType>,
+    /// Every `Enum::Variant`.
+    variants: HashSet<String>,
+    /// Every `Type::function` of the `impl` blocks.
+    functions: HashSet<String>,
+}
+
+impl Resolver {
+    fn method(&self, type_name: &str, function: &Declaration) -> Declaration {
+        let mut function = self.declaration(function);
+        if let Declaration::FunctionDec { name, .. } = &mut function {
+            *name = format!("{}::{}", type_name, name);
+        }
+        function
+    }
+
+    fn declaration(&self, declaration: &Declaration) -> Declaration {
+        match declaration {
+            Declaration::FunctionDec {
+                is_static,
+                attributes,
+                name,
+                parameters,
+                return_type,
+                code_block,
+            } => Declaration::FunctionDec {
+                is_static: *is_static,
+                attributes: attributes.clone(),
+                name: name.clone(),
+                parameters: parameters
+                    .iter()
+                    .map(|(name, var_type)| (name.clone(), self.var_type(var_type)))
+                    .collect(),
+                return_type: self.var_type(return_type),
+                code_block: self.block(code_block),
+            },
+            Declaration::ConstDec(constant) => {
+                let mut constant = constant.clone();
+                constant.var_type = constant.var_type.map(|t| self.var_type(&t));
+                constant.var = self.value(&constant.var);
+                Declaration::ConstDec(constant)
+            }
+            Declaration::ExternFunction {
+                name,
+                parameters,
+                return_type,
+                location,
+            } => Declaration::ExternFunction {
+                name: name.clone(),
+                parameters: parameters
+                    .iter()
+                    .map(|(name, var_type)| (name.clone(), self.var_type(var_type)))
+                    .collect(),
+                return_type: self.var_type(return_type),
+                location: location.clone(),
+            },
+            Declaration::ExternVariable(variable) => {
+                let mut variable = variable.clone();
+                variable.var_type = self.var_type(&variable.var_type);
+                Declaration::ExternVariable(variable)
+            }
+            Declaration::EntityScore(name, var_type) => {
+                Declaration::EntityScore(name.clone(), self.var_type(var_type))
+            }
+            Declaration::UseFile(path) => Declaration::UseFile(path.clone()),
+            Declaration::Use(path) => Declaration::Use(path.clone()),
+            Declaration::Public(declaration) => {
+                Declaration::Public(Box::new(self.declaration(declaration)))
+            }
+            Declaration::StructDef(name, fields) => Declaration::StructDef(
+                name.clone(),
+                fields
+                    .iter()
+                    .map(|(field, var_type)| (field.clone(), self.var_type(var_type)))
+                    .collect(),
+            ),
+            Declaration::EnumDef(name, variants) => Declaration::EnumDef(
+                name.clone(),
+                variants
+                    .iter()
+                    .map(|(variant, payload)| {
+                        (
+                            variant.clone(),
+                            payload.iter().map(|t| self.var_type(t)).collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+            Declaration::Impl(name, functions) => Declaration::Impl(
+                name.clone(),
+                functions.iter().map(|f| self.declaration(f)).collect(),
+            ),
+        }
+    }
+
+    fn var_type(&self, var_type: &EFSType) -> EFSType {
+        match var_type {
+            EFSType::Struct(name) => self.enums.get(name).unwrap_or(var_type).clone(),
+            EFSType::List(element_type) => EFSType::List(Box::new(self.var_type(element_type))),
+            EFSType::Dict(value_type) => EFSType::Dict(Box::new(self.var_type(value_type))),
+            EFSType::Option(var_type) => EFSType::Option(Box::new(self.var_type(var_type))),
+            _ => var_type.clone(),
+        }
+    }
+
+    fn block(&self, block: &CodeBlock) -> CodeBlock {
+        CodeBlock {
+            statements: block.statements.iter().map(|s| self.statement(s)).collect(),
+            positions: block.positions.clone(),
+            names: block.names.clone(),
+            end: block.end,
+        }
+    }
+
+    fn statement(&self, statement: &Statement) -> Statement {
+        match statement {
+            Statement::For(init, condition, step, block) => Statement::For(
+                Box::new(self.statement(init)),
+                self.value(condition),
+                Box::new(self.statement(step)),
+                self.block(block),
+            ),
+            Statement::ForList(item, list, block) => {
+                Statement::ForList(item.clone(), list.clone(), self.block(block))
+            }
+            Statement::StaticFor(name, start, end, step, block) => {
+                Statement::StaticFor(name.clone(), *start, *end, *step, self.block(block))
+            }
+            Statement::While(condition, block) => {
+                Statement::While(self.value(condition), self.block(block))
+            }
+            Statement::If(condition, block) => {
+                Statement::If(self.value(condition), self.block(block))
+            }
+            Statement::IfLet(name, value, block) => {
+                Statement::IfLet(name.clone(), self.value(value), self.block(block))
+            }
+            Statement::Match(value, arms) => Statement::Match(
+                self.value(value),
+                arms.iter()
+                    .map(|arm| MatchArm {
+                        pattern: arm.pattern.clone(),
+                        block: self.block(&arm.block),
+                    })
+                    .collect(),
+            ),
+            Statement::Return(value) => {
+                Statement::Return(value.as_ref().map(|value| self.value(value)))
+            }
+            Statement::Commands(commands) => Statement::Commands(commands.clone()),
+            Statement::Execute(contexts, block) => Statement::Execute(
+                contexts
+                    .iter()
+                    .map(|context| context.map_selector(|selector| self.value(selector)))
+                    .collect(),
+                self.block(block),
+            ),
+            Statement::Break => Statement::Break,
+            Statement::Continue => Statement::Continue,
+            Statement::Expression(Expression::VarDec(name, var_type, value)) => {
+                Statement::Expression(Expression::VarDec(
+                    name.clone(),
+                    var_type.as_ref().map(|t| self.var_type(t)),
+                    self.value(value),
+                ))
+            }
+            Statement::Expression(Expression::Assign(name, value)) => {
+                Statement::Expression(Expression::Assign(name.clone(), self.value(value)))
+            }
+            Statement::Expression(Expression::AssignField(target, field, op, value)) => {
+                Statement::Expression(Expression::AssignField(
+                    self.value(target),
+                    field.clone(),
+                    op.clone(),
+                    self.value(value),
+                ))
+            }
+            Statement::Expression(Expression::Value(value)) => {
+                Statement::Expression(Expression::Value(self.value(value)))
+            }
+        }
+    }
+
+    fn value(&self, value: &Value) -> Value {
+        let values = |values: &[Value]| values.iter().map(|v| self.value(v)).collect();
+        let fields = |fields: &HashMap<String, Value>| {
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), self.value(value)))
+                .collect()
+        };
+        match value {
+            Value::Value(_) | Value::Identifier(_) => value.clone(),
+            Value::Math(math) => Value::Math(Box::new(Math {
+                left: self.value(&math.left),
+                op: math.op.clone(),
+                right: self.value(&math.right),
+            })),
+            Value::List(list) => Value::List(values(list)),
+            Value::Text(parts) => Value::Text(values(parts)),
+            Value::Dict(dict) => Value::Dict(fields(dict)),
+            Value::Struct(name, values) => Value::Struct(name.clone(), fields(values)),
+            Value::Call(name, args) => Value::Call(name.clone(), values(args)),
+            Value::Cast(value, var_type) => {
+                Value::Cast(Box::new(self.value(value)), self.var_type(var_type))
+            }
+            Value::Field(value, field) => Value::Field(Box::new(self.value(value)), field.clone()),
+            Value::OptionalField(value, field) => {
+                Value::OptionalField(Box::new(self.value(value)), field.clone())
+            }
+            Value::Index(value, index) => {
+                Value::Index(Box::new(self.value(value)), Box::new(self.value(index)))
+            }
+            Value::Variant(name, member, args) => {
+                let path = format!("{}::{}", name, member);
+                let is_function = !self.enums.contains_key(name)
+                    || (self.functions.contains(&path) && !self.variants.contains(&path));
+                if is_function {
+                    Value::Call(path, values(args))
+                } else {
+                    Value::Variant(name.clone(), member.clone(), values(args))
+                }
+            }
+            Value::MethodCall(value, method, args) => {
+                Value::MethodCall(Box::new(self.value(value)), method.clone(), values(args))
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt::Display;
+
+/// Score of an optional that is `None`, so `i32::MIN` itself can not be in an optional score.
+pub const ABSENT: i32 = i32::MIN;
+
+/// A fake player on a scoreboard objective, written as `<holder> <objective>` in commands.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score {
+    pub holder: String,
+    pub objective: String,
+}
+
+impl Score {
+    pub fn new(holder: impl Into<String>, objective: impl Into<String>) -> Self {
+        Self {
+            holder: holder.into(),
+            objective: objective.into(),
+        }
+    }
+
+    pub fn set(&self, value: i32) -> String {
+        format!("scoreboard players set {} {}", self, value)
+    }
+
+    pub fn get(&self) -> String {
+        format!("scoreboard players get {}", self)
+    }
+
+    pub fn copy_from(&self, other: &Score) -> String {
+        format!("scoreboard players operation {} = {}", self, other)
+    }
+
+    pub fn reset(&self) -> String {
+        format!("scoreboard players reset {}", self)
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.holder, self.objective)
+    }
+}
+
+/// Hands out compiler owned score holders on the `<namespace>.vars` objective.
+pub struct Scoreboard {
+    objective: String,
+    next_id: usize,
+}
+
+impl Scoreboard {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            objective: format!("{}.vars", namespace),
+            next_id: 0,
+        }
+    }
+
+    pub fn objective(&self) -> &str {
+        &self.objective
+    }
+
+    pub fn allocate(&mut self) -> Score {
+        let score = Score::new(format!("$v{}", self.next_id), self.objective.clone());
+        self.next_id += 1;
+        score
+    }
+}
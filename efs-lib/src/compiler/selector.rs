@@ -0,0 +1,100 @@
+use anyhow::bail;
+
+use crate::parser::types::{EFSType, EFSValueType};
+
+/// Builder methods of `Selector`, the selector argument each one adds and the type
+/// of its value. `@e.type("pig").limit(1)` is `@e[type=pig,limit=1]`.
+const METHODS: &[(&str, EFSType)] = &[
+    ("tag", EFSType::String),
+    ("type", EFSType::String),
+    ("name", EFSType::String),
+    ("team", EFSType::String),
+    ("sort", EFSType::String),
+    ("distance", EFSType::String),
+    ("limit", EFSType::Int),
+];
+
+const SORTS: &[&str] = &["nearest", "furthest", "random", "arbitrary"];
+
+/// Whether `text` is `@a`, `@e[type=pig]` and so on, or the name of a player.
+pub fn is_selector(text: &str) -> bool {
+    let Some(rest) = text.strip_prefix('@') else {
+        return (1..=16).contains(&text.len())
+            && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    };
+    let mut chars = rest.chars();
+    if !chars.next().is_some_and(|c| "parsen".contains(c)) {
+        return false;
+    }
+    let arguments = chars.as_str();
+    arguments.is_empty() || (arguments.starts_with('[') && arguments.ends_with(']'))
+}
+
+/// Whether `text` selects one entity at most: the name of a player, `@s`, `@p`, `@r` or
+/// `@n` without a larger `limit`, or any selector with `limit=1`.
+pub fn is_single(text: &str) -> bool {
+    let Some(rest) = text.strip_prefix('@') else {
+        return true;
+    };
+    let limit = rest
+        .get(1..)
+        .and_then(|arguments| arguments.strip_prefix('['))
+        .and_then(|arguments| arguments.strip_suffix(']'))
+        .and_then(|arguments| {
+            arguments
+                .split(',')
+                .find_map(|argument| argument.trim().strip_prefix("limit="))
+        });
+    match limit {
+        Some(limit) => limit.trim() == "1",
+        None => !rest.starts_with(['a', 'e']),
+    }
+}
+
+/// Type of the value the builder method `method` takes.
+pub fn method_type(method: &str) -> Option<EFSType> {
+    METHODS
+        .iter()
+        .find(|(name, _)| *name == method)
+        .map(|(_, var_type)| var_type.clone())
+}
+
+/// `selector` with the argument of the builder method `method` added.
+pub fn with_argument(selector: &str, method: &str, value: &EFSValueType) -> anyhow::Result<String> {
+    if !selector.starts_with('@') {
+        bail!(
+            "the player name {} can not take selector arguments",
+            selector
+        );
+    }
+    let value = match value {
+        EFSValueType::Number(n) | EFSValueType::Int(n) if method == "limit" && *n > 0 => {
+            n.to_string()
+        }
+        EFSValueType::Number(n) | EFSValueType::Int(n) if method == "limit" => {
+            bail!("the limit of a selector must be positive, found {}", n)
+        }
+        EFSValueType::String(sort) if method == "sort" && !SORTS.contains(&sort.as_str()) => {
+            bail!("expected one of {}, found {}", SORTS.join(", "), sort)
+        }
+        EFSValueType::String(text)
+            if text
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-.+:!/#".contains(c)) =>
+        {
+            text.clone()
+        }
+        EFSValueType::String(text) => format!("{:?}", text),
+        value => bail!(
+            "selector method {} does not take a {:?}",
+            method,
+            value.get_type()
+        ),
+    };
+    let argument = format!("{}={}", method, value);
+    Ok(match selector.strip_suffix(']') {
+        Some(start) if start.ends_with('[') => format!("{}{}]", start, argument),
+        Some(start) => format!("{},{}]", start, argument),
+        None => format!("{}[{}]", selector, argument),
+    })
+}
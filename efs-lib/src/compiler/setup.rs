@@ -0,0 +1,58 @@
+use crate::parser::types::EFSValueType;
+
+use super::Allocator;
+
+/// Folder of the functions the compiler generates, no function of the program can
+/// compile to a location inside it.
+pub const RESERVED: &str = "_efs";
+/// Folder of the functions that are not `pub`, so they can not be called by name from
+/// outside the pack or clash with what the compiler generates.
+pub const PRIVATE: &str = "_efs/fn";
+/// Runs before every other `#[load]` function and sets up what the generated code relies on.
+pub const LOAD_FUNCTION: &str = "_efs/load";
+/// Removes everything `LOAD_FUNCTION` created, generated when `Compiler::uninstall` is set.
+pub const UNINSTALL_FUNCTION: &str = "_efs/uninstall";
+
+/// The load function: the objective for score variables and the `objectives` of the
+/// scores of entities, a cleared control flag and empty storage roots. Constants are
+/// inlined so they need no setup.
+pub fn load(allocator: &Allocator, objectives: &[String]) -> Vec<String> {
+    let mut commands = vec![format!(
+        "scoreboard objectives add {} dummy",
+        allocator.scoreboard.objective()
+    )];
+    commands.extend(
+        objectives
+            .iter()
+            .map(|objective| format!("scoreboard objectives add {} dummy", objective)),
+    );
+    commands.extend([
+        allocator.control().set(0),
+        allocator
+            .call_stack()
+            .set_value(&EFSValueType::List(Vec::new())),
+        allocator
+            .storage
+            .path("frame")
+            .set_value(&EFSValueType::Dict(Default::default())),
+    ]);
+    commands
+}
+
+pub fn uninstall(allocator: &Allocator, objectives: &[String]) -> Vec<String> {
+    let mut commands = vec![format!(
+        "scoreboard objectives remove {}",
+        allocator.scoreboard.objective()
+    )];
+    commands.extend(
+        objectives
+            .iter()
+            .map(|objective| format!("scoreboard objectives remove {}", objective)),
+    );
+    commands.push(allocator.call_stack().remove());
+    commands.push(allocator.storage.path("frame").remove());
+    commands.push(allocator.scratch().remove());
+    commands.push(allocator.macro_arguments().remove());
+    commands.extend(allocator.storage.allocated().map(|path| path.remove()));
+    commands
+}
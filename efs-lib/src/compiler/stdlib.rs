@@ -0,0 +1,299 @@
+use anyhow::{anyhow, bail};
+
+use crate::parser::types::{EFSType, EFSValueType};
+
+use super::{is_resource_location, selector::is_selector, FunctionSignature};
+
+/// Module of the command wrappers, `std::cmd::say("hi")` compiles to `say hi`.
+pub const MODULE: &str = "std::cmd";
+
+/// Sound categories of `playsound`.
+const SOUND_SOURCES: &[&str] = &[
+    "master", "music", "record", "weather", "block", "hostile", "neutral", "player", "ambient",
+    "voice",
+];
+
+const GAME_MODES: &[&str] = &["survival", "creative", "adventure", "spectator"];
+
+/// What an argument of a wrapper has to look like, checked when the call is lowered.
+#[derive(Clone, Copy)]
+enum Arg {
+    /// Text written into the command as it is.
+    Text,
+    /// A JSON text component built by `text!`, or a string sent as one.
+    Component,
+    /// A target selector like `@a[tag=x]` or a player name.
+    Selector,
+    /// Three coordinates, each absolute, relative `~1` or local `^1`.
+    Position,
+    /// A resource location, the namespace defaults to `minecraft`.
+    Resource,
+    /// A scoreboard tag.
+    Tag,
+    /// One of the given words.
+    Choice(&'static [&'static str]),
+    Int,
+}
+
+/// A function of `std::cmd` and the command it lowers to, `{parameter}` in
+/// `command` is replaced by the argument.
+struct Wrapper {
+    name: &'static str,
+    parameters: &'static [(&'static str, Arg)],
+    command: &'static str,
+}
+
+const WRAPPERS: &[Wrapper] = &[
+    Wrapper {
+        name: "say",
+        parameters: &[("message", Arg::Text)],
+        command: "say {message}",
+    },
+    Wrapper {
+        name: "tellraw",
+        parameters: &[("targets", Arg::Selector), ("message", Arg::Component)],
+        command: "tellraw {targets} {message}",
+    },
+    Wrapper {
+        name: "title",
+        parameters: &[("targets", Arg::Selector), ("message", Arg::Component)],
+        command: "title {targets} title {message}",
+    },
+    Wrapper {
+        name: "subtitle",
+        parameters: &[("targets", Arg::Selector), ("message", Arg::Component)],
+        command: "title {targets} subtitle {message}",
+    },
+    Wrapper {
+        name: "actionbar",
+        parameters: &[("targets", Arg::Selector), ("message", Arg::Component)],
+        command: "title {targets} actionbar {message}",
+    },
+    Wrapper {
+        name: "give",
+        parameters: &[
+            ("targets", Arg::Selector),
+            ("item", Arg::Resource),
+            ("count", Arg::Int),
+        ],
+        command: "give {targets} {item} {count}",
+    },
+    Wrapper {
+        name: "summon",
+        parameters: &[("entity", Arg::Resource), ("pos", Arg::Position)],
+        command: "summon {entity} {pos}",
+    },
+    Wrapper {
+        name: "tp",
+        parameters: &[("targets", Arg::Selector), ("pos", Arg::Position)],
+        command: "tp {targets} {pos}",
+    },
+    Wrapper {
+        name: "effect",
+        parameters: &[
+            ("targets", Arg::Selector),
+            ("effect", Arg::Resource),
+            ("seconds", Arg::Int),
+            ("amplifier", Arg::Int),
+        ],
+        command: "effect give {targets} {effect} {seconds} {amplifier}",
+    },
+    Wrapper {
+        name: "playsound",
+        parameters: &[
+            ("sound", Arg::Resource),
+            ("source", Arg::Choice(SOUND_SOURCES)),
+            ("targets", Arg::Selector),
+        ],
+        command: "playsound {sound} {source} {targets}",
+    },
+    Wrapper {
+        name: "particle",
+        parameters: &[("particle", Arg::Resource), ("pos", Arg::Position)],
+        command: "particle {particle} {pos}",
+    },
+    Wrapper {
+        name: "setblock",
+        parameters: &[("pos", Arg::Position), ("block", Arg::Resource)],
+        command: "setblock {pos} {block}",
+    },
+    Wrapper {
+        name: "fill",
+        parameters: &[
+            ("from", Arg::Position),
+            ("to", Arg::Position),
+            ("block", Arg::Resource),
+        ],
+        command: "fill {from} {to} {block}",
+    },
+    Wrapper {
+        name: "kill",
+        parameters: &[("targets", Arg::Selector)],
+        command: "kill {targets}",
+    },
+    Wrapper {
+        name: "tag",
+        parameters: &[("targets", Arg::Selector), ("tag", Arg::Tag)],
+        command: "tag {targets} add {tag}",
+    },
+    Wrapper {
+        name: "untag",
+        parameters: &[("targets", Arg::Selector), ("tag", Arg::Tag)],
+        command: "tag {targets} remove {tag}",
+    },
+    Wrapper {
+        name: "gamemode",
+        parameters: &[
+            ("mode", Arg::Choice(GAME_MODES)),
+            ("targets", Arg::Selector),
+        ],
+        command: "gamemode {mode} {targets}",
+    },
+];
+
+fn wrapper(name: &str) -> Option<&'static Wrapper> {
+    let name = name.strip_prefix(MODULE)?.strip_prefix("::")?;
+    WRAPPERS.iter().find(|wrapper| wrapper.name == name)
+}
+
+/// Names of the functions in `std::cmd`, without the module.
+pub fn functions() -> impl Iterator<Item = &'static str> {
+    WRAPPERS.iter().map(|wrapper| wrapper.name)
+}
+
+/// Whether `name` is a function of `std::cmd`.
+pub fn is_builtin(name: &str) -> bool {
+    wrapper(name).is_some()
+}
+
+/// Signature of the `std::cmd` function `name`.
+pub fn signature(name: &str) -> Option<FunctionSignature> {
+    let wrapper = wrapper(name)?;
+    Some(FunctionSignature {
+        parameters: wrapper
+            .parameters
+            .iter()
+            .map(|(parameter, arg)| {
+                let var_type = match arg {
+                    Arg::Int => EFSType::Int,
+                    Arg::Selector => EFSType::Selector,
+                    Arg::Component => EFSType::Text,
+                    _ => EFSType::String,
+                };
+                (parameter.to_string(), var_type)
+            })
+            .collect(),
+        return_type: EFSType::None,
+    })
+}
+
+/// The command a call of the `std::cmd` function `name` lowers to. Constant arguments
+/// are checked and written into the command, `None` is an argument only known at
+/// runtime, which becomes the macro argument `$(parameter)`.
+pub fn lower(name: &str, args: &[Option<EFSValueType>]) -> anyhow::Result<String> {
+    let wrapper = wrapper(name).ok_or_else(|| anyhow!("unknown function {}", name))?;
+    if args.len() != wrapper.parameters.len() {
+        bail!(
+            "function {} takes {} arguments but {} were given",
+            name,
+            wrapper.parameters.len(),
+            args.len()
+        );
+    }
+    let mut values = Vec::new();
+    for ((parameter, arg), value) in wrapper.parameters.iter().zip(args) {
+        let text = match value {
+            Some(value) => argument(*arg, value)
+                .map_err(|error| anyhow!("argument {} of {}: {}", parameter, name, error))?,
+            None => format!("$({})", parameter),
+        };
+        values.push((*parameter, text));
+    }
+    Ok(wrapper
+        .command
+        .split(' ')
+        .map(|word| {
+            match word
+                .strip_prefix('{')
+                .and_then(|word| word.strip_suffix('}'))
+            {
+                Some(parameter) => values
+                    .iter()
+                    .find(|(name, _)| *name == parameter)
+                    .map(|(_, text)| text.as_str())
+                    .unwrap_or(word),
+                None => word,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// `value` as it is written in the command.
+fn argument(arg: Arg, value: &EFSValueType) -> anyhow::Result<String> {
+    if let Arg::Int = arg {
+        return match value {
+            EFSValueType::Number(n) | EFSValueType::Int(n) => Ok(n.to_string()),
+            EFSValueType::Byte(n) => Ok(n.to_string()),
+            EFSValueType::Short(n) => Ok(n.to_string()),
+            value => bail!("expected an integer, found {:?}", value.get_type()),
+        };
+    }
+    let text = match (value, arg) {
+        (EFSValueType::Text(json), Arg::Component) => return Ok(json.clone()),
+        (EFSValueType::String(text), _) | (EFSValueType::Selector(text), Arg::Selector) => text,
+        (value, _) => bail!("expected a string, found {:?}", value.get_type()),
+    };
+    if text.contains('\n') {
+        bail!("{:?} spans more than one line", text);
+    }
+    let valid = match arg {
+        Arg::Text => !text.is_empty(),
+        Arg::Component => return Ok(serde_json::to_string(text)?),
+        Arg::Selector => is_selector(text),
+        Arg::Position => is_position(text),
+        Arg::Resource if text.contains(':') => is_resource_location(text),
+        Arg::Resource => is_resource_location(&format!("minecraft:{}", text)),
+        Arg::Tag => {
+            !text.is_empty()
+                && text
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.+".contains(c))
+        }
+        Arg::Choice(words) => words.contains(&text.as_str()),
+        Arg::Int => unreachable!(),
+    };
+    match (valid, arg) {
+        (true, _) => Ok(text.clone()),
+        (false, Arg::Text) => bail!("the message is empty"),
+        (false, Arg::Selector) => bail!("{} is not a selector or player name", text),
+        (false, Arg::Position) => bail!("{} is not a position", text),
+        (false, Arg::Resource) => bail!("{} is not a valid resource location", text),
+        (false, Arg::Tag) => bail!("{} is not a valid tag", text),
+        (false, Arg::Choice(words)) => {
+            bail!("expected one of {}, found {}", words.join(", "), text)
+        }
+        (false, _) => unreachable!(),
+    }
+}
+
+/// Whether `text` is three coordinates, local `^` ones can not be mixed with others.
+pub fn is_position(text: &str) -> bool {
+    let coordinates = text.split_whitespace().collect::<Vec<_>>();
+    let coordinate = |coordinate: &str| {
+        let number = coordinate.strip_prefix(['~', '^']).unwrap_or(coordinate);
+        match number {
+            "" => number.len() < coordinate.len(),
+            number => {
+                number
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || c == '.' || c == '-')
+                    && number.parse::<f64>().is_ok()
+            }
+        }
+    };
+    let local = coordinates.iter().filter(|c| c.starts_with('^')).count();
+    coordinates.len() == 3
+        && coordinates.iter().all(|c| coordinate(c))
+        && (local == 0 || local == 3)
+}
@@ -0,0 +1,123 @@
+use std::fmt::Display;
+
+use crate::parser::types::EFSValueType;
+
+use super::scoreboard::Score;
+
+/// A path inside a command storage, written as `storage <id> <path>` in commands.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoragePath {
+    pub storage: String,
+    pub path: String,
+}
+
+impl StoragePath {
+    pub fn new(storage: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            storage: storage.into(),
+            path: path.into(),
+        }
+    }
+
+    /// Path to a named entry of the compound at this path.
+    pub fn field(&self, name: &str) -> Self {
+        let key = if name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+')
+        {
+            name.to_string()
+        } else {
+            format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+        };
+        Self::new(self.storage.clone(), format!("{}.{}", self.path, key))
+    }
+
+    /// Path to an element of the list at this path, negative indices count from the end.
+    pub fn index(&self, index: i32) -> Self {
+        Self::new(self.storage.clone(), format!("{}[{}]", self.path, index))
+    }
+
+    pub fn set_value(&self, value: &EFSValueType) -> String {
+        format!("data modify {} set value {}", self, value.to_snbt())
+    }
+
+    pub fn set_from(&self, source: &StoragePath) -> String {
+        format!("data modify {} set from {}", self, source)
+    }
+
+    pub fn append_value(&self, value: &EFSValueType) -> String {
+        format!("data modify {} append value {}", self, value.to_snbt())
+    }
+
+    pub fn append_from(&self, source: &StoragePath) -> String {
+        format!("data modify {} append from {}", self, source)
+    }
+
+    pub fn remove(&self) -> String {
+        format!("data remove {}", self)
+    }
+
+    /// Stores the length of the list, string or compound at this path in `score`.
+    pub fn store_length(&self, score: &Score) -> String {
+        format!("execute store result score {} run data get {}", score, self)
+    }
+
+    /// Reads the number at this path into `score`, multiplied by `scale` and truncated.
+    pub fn store_to_score(&self, score: &Score, scale: f64) -> String {
+        format!(
+            "execute store result score {} run data get {} {}",
+            score, self, scale
+        )
+    }
+
+    /// Writes `score` multiplied by `scale` to this path as the NBT type `nbt_type`.
+    pub fn store_from_score(&self, score: &Score, nbt_type: &str, scale: f64) -> String {
+        format!(
+            "execute store result {} {} {} run {}",
+            self,
+            nbt_type,
+            scale,
+            score.get()
+        )
+    }
+}
+
+impl Display for StoragePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "storage {} {}", self.storage, self.path)
+    }
+}
+
+/// Hands out compiler owned paths in the `<namespace>:vars` storage.
+pub struct Storage {
+    id: String,
+    next_id: usize,
+}
+
+impl Storage {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            id: format!("{}:vars", namespace),
+            next_id: 0,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn path(&self, path: impl Into<String>) -> StoragePath {
+        StoragePath::new(self.id.clone(), path)
+    }
+
+    pub fn allocate(&mut self) -> StoragePath {
+        let path = self.path(format!("v{}", self.next_id));
+        self.next_id += 1;
+        path
+    }
+
+    /// Every path handed out by `allocate` so far.
+    pub fn allocated(&self) -> impl Iterator<Item = StoragePath> + '_ {
+        (0..self.next_id).map(|id| self.path(format!("v{}", id)))
+    }
+}
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+
+use crate::parser::{
+    ast::{CodeBlock, Context, Declaration, Expression, Pattern, Program, Statement, Value},
+    types::EFSType,
+};
+
+use super::{
+    check::TypeError,
+    commands::{self, Part},
+    resolve, stdlib,
+};
+
+/// What a name is bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Constant,
+    Function,
+    Struct,
+    Enum,
+    Parameter,
+    /// An `extern score` or `extern storage` variable.
+    Extern,
+    /// A `let`, the binding of an `if let`, `for` or `match` arm, or the
+    /// variable of a `static for`.
+    Local,
+}
+
+impl SymbolKind {
+    fn describe(self) -> &'static str {
+        match self {
+            SymbolKind::Constant => "constant",
+            SymbolKind::Function => "function",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Extern => "extern variable",
+            SymbolKind::Local => "variable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Function a parameter or local belongs to, `None` for globals.
+    pub function: Option<String>,
+    /// Where the name is declared in the source, `None` for the functions of the
+    /// standard library.
+    pub at: Option<usize>,
+}
+
+/// Every name declared in a program and where it is used. Symbols are referred to by
+/// their index in `symbols`.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    /// Where each symbol is used in the source, by symbol.
+    uses: Vec<Vec<usize>>,
+    /// Undefined names and names defined twice in the same scope.
+    pub errors: Vec<TypeError>,
+    /// Names that hide a constant or a variable of an outer block.
+    pub warnings: Vec<TypeError>,
+}
+
+impl SymbolTable {
+    /// Binds every name used in `programs` to its declaration. Methods are left to
+    /// the type checker, which one is called depends on the type of the receiver.
+    pub fn build(programs: &[Program]) -> Self {
+        let programs = &resolve::resolve(programs);
+        let mut table = Self::default();
+        let mut globals = Globals::default();
+        let declarations = programs.iter().flat_map(|program| &program.0);
+
+        for (declaration, declared) in programs.iter().flat_map(|program| {
+            program
+                .0
+                .iter()
+                .map(move |declaration| (declaration, &program.1))
+        }) {
+            let (name, kind, at) = match declaration {
+                Declaration::FunctionDec {
+                    name, code_block, ..
+                } => (name, SymbolKind::Function, code_block.end),
+                Declaration::ExternFunction { name, .. } => (name, SymbolKind::Function, 0),
+                Declaration::ExternVariable(variable) => (&variable.name, SymbolKind::Extern, 0),
+                Declaration::StructDef(name, _) => (name, SymbolKind::Struct, 0),
+                Declaration::EnumDef(name, _) => (name, SymbolKind::Enum, 0),
+                Declaration::ConstDec(constant) => (&constant.name, SymbolKind::Constant, 0),
+                _ => continue,
+            };
+            let namespace = match kind {
+                SymbolKind::Function => &mut globals.functions,
+                SymbolKind::Constant | SymbolKind::Extern => &mut globals.constants,
+                _ => &mut globals.types,
+            };
+            if namespace.contains_key(name) {
+                table.errors.push(TypeError {
+                    at,
+                    message: format!("{} {} is defined more than once", kind.describe(), name),
+                });
+                continue;
+            }
+            namespace.insert(name.clone(), table.symbols.len());
+            table.add(Symbol {
+                name: name.clone(),
+                kind,
+                function: None,
+                at: declared.item(name),
+            });
+        }
+
+        for declaration in declarations.clone() {
+            let (name, types) = match declaration {
+                Declaration::StructDef(name, fields) => {
+                    let mut fields = fields.iter().collect::<Vec<_>>();
+                    fields.sort_by_key(|(field, _)| *field);
+                    (
+                        name,
+                        fields.into_iter().map(|(_, var_type)| var_type).collect(),
+                    )
+                }
+                Declaration::EnumDef(name, variants) => (
+                    name,
+                    variants
+                        .iter()
+                        .flat_map(|(_, payload)| payload)
+                        .collect::<Vec<_>>(),
+                ),
+                Declaration::ExternFunction {
+                    name,
+                    parameters,
+                    return_type,
+                    ..
+                } => (
+                    name,
+                    parameters
+                        .iter()
+                        .map(|(_, var_type)| var_type)
+                        .chain([return_type])
+                        .collect(),
+                ),
+                Declaration::ExternVariable(variable) => (&variable.name, vec![&variable.var_type]),
+                Declaration::EntityScore(name, var_type) => (name, vec![var_type]),
+                _ => continue,
+            };
+            for var_type in types {
+                if let Err(unknown) = named_types(&globals, var_type) {
+                    table.errors.push(TypeError {
+                        at: 0,
+                        message: format!("unknown type {} (in {})", unknown, name),
+                    });
+                }
+            }
+        }
+
+        for program in programs {
+            for declaration in &program.0 {
+                let Declaration::FunctionDec {
+                    name,
+                    parameters,
+                    return_type,
+                    code_block,
+                    ..
+                } = declaration
+                else {
+                    continue;
+                };
+                let mut function = FunctionResolver {
+                    table: &mut table,
+                    globals: &globals,
+                    name,
+                    scopes: vec![HashMap::new()],
+                    at: code_block.end,
+                    names: Vec::new(),
+                };
+                let positions = program.1.parameters(name);
+                for (index, (parameter, var_type)) in parameters.iter().enumerate() {
+                    function.var_type(var_type, false);
+                    let at = positions.get(index).copied().unwrap_or(code_block.end);
+                    function.declare(parameter, SymbolKind::Parameter, at);
+                }
+                function.var_type(return_type, false);
+                function.block(code_block);
+            }
+        }
+
+        table.errors.sort_by_key(|error| error.at);
+        table.warnings.sort_by_key(|warning| warning.at);
+        table
+    }
+
+    /// The constant, function, struct or enum called `name`.
+    pub fn global(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .position(|symbol| symbol.function.is_none() && symbol.name == name)
+    }
+
+    /// Where `symbol` is used, in source order. Variables of commands are used at the
+    /// start of the statement.
+    pub fn uses(&self, symbol: usize) -> &[usize] {
+        &self.uses[symbol]
+    }
+
+    /// The symbol declared or used at `at`.
+    pub fn symbol_at(&self, at: usize) -> Option<usize> {
+        (0..self.symbols.len()).find(|symbol| {
+            self.symbols[*symbol].at == Some(at) || self.uses[*symbol].binary_search(&at).is_ok()
+        })
+    }
+
+    fn add(&mut self, symbol: Symbol) -> usize {
+        self.symbols.push(symbol);
+        self.uses.push(Vec::new());
+        self.symbols.len() - 1
+    }
+
+    fn use_symbol(&mut self, symbol: usize, at: usize) {
+        let uses = &mut self.uses[symbol];
+        if let Err(index) = uses.binary_search(&at) {
+            uses.insert(index, at);
+        }
+    }
+}
+
+/// Symbols of the structs and enums `var_type` names, the error is the first name that
+/// is not defined.
+fn named_types(globals: &Globals, var_type: &EFSType) -> Result<Vec<usize>, String> {
+    match var_type {
+        EFSType::Struct(name) | EFSType::Enum(name) | EFSType::TaggedEnum(name) => {
+            let symbol = globals.types.get(name).ok_or_else(|| name.clone())?;
+            Ok(vec![*symbol])
+        }
+        EFSType::List(var_type) | EFSType::Dict(var_type) | EFSType::Option(var_type) => {
+            named_types(globals, var_type)
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Names visible in every function, functions, types and constants do not clash.
+#[derive(Default)]
+struct Globals {
+    functions: HashMap<String, usize>,
+    types: HashMap<String, usize>,
+    /// Constants and `extern` variables.
+    constants: HashMap<String, usize>,
+}
+
+struct FunctionResolver<'a> {
+    table: &'a mut SymbolTable,
+    globals: &'a Globals,
+    name: &'a str,
+    /// Symbols of the parameters and of the locals of each enclosing block.
+    scopes: Vec<HashMap<String, usize>>,
+    /// Start of the statement being resolved.
+    at: usize,
+    /// Names of the statement being resolved that are not bound yet, see
+    /// `CodeBlock::names`.
+    names: Vec<(String, usize)>,
+}
+
+impl FunctionResolver<'_> {
+    fn error(&mut self, message: String) {
+        self.table.errors.push(TypeError {
+            at: self.at,
+            message: format!("{} (in function {})", message, self.name),
+        });
+    }
+
+    fn block(&mut self, block: &CodeBlock) {
+        let at = self.at;
+        let names = std::mem::take(&mut self.names);
+        self.scopes.push(HashMap::new());
+        for ((statement, at), names) in block
+            .statements
+            .iter()
+            .zip(&block.positions)
+            .zip(&block.names)
+        {
+            self.at = *at;
+            self.names = names.clone();
+            self.statement(statement);
+        }
+        self.scopes.pop();
+        self.at = at;
+        self.names = names;
+    }
+
+    /// Runs `resolve` in a scope holding `names`, which are declared by the
+    /// statement being resolved at the position next to them.
+    fn scoped(&mut self, names: &[(&String, usize)], resolve: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        for (name, at) in names {
+            self.declare(name, SymbolKind::Local, *at);
+        }
+        resolve(self);
+        self.scopes.pop();
+    }
+
+    /// Where the next `name` of the statement being resolved is written. The start of
+    /// the statement when `name` is not written there, like the variables of commands.
+    fn position(&mut self, name: &str) -> usize {
+        let last = |name: &str| name.rsplit("::").next().unwrap_or_default().to_string();
+        let segment = last(name);
+        match self
+            .names
+            .iter()
+            .position(|(found, _)| last(found) == segment)
+        {
+            Some(index) => self.names.remove(index).1,
+            None => self.at,
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, at: usize) {
+        if self.scopes.last().unwrap().contains_key(name) {
+            let message = match kind {
+                SymbolKind::Parameter => format!("parameter {} is defined more than once", name),
+                _ => format!("{} is already defined in this block", name),
+            };
+            self.error(message);
+            return;
+        }
+        let shadowed = match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(symbol) => Some(self.table.symbols[*symbol].kind),
+            None => self
+                .globals
+                .constants
+                .get(name)
+                .map(|symbol| self.table.symbols[*symbol].kind),
+        };
+        if let Some(shadowed) = shadowed {
+            self.table.warnings.push(TypeError {
+                at: self.at,
+                message: format!(
+                    "{} shadows a {} of the same name (in function {})",
+                    name,
+                    shadowed.describe(),
+                    self.name
+                ),
+            });
+        }
+
+        let symbol = self.table.add(Symbol {
+            name: name.to_string(),
+            kind,
+            function: Some(self.name.to_string()),
+            at: Some(at),
+        });
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), symbol);
+    }
+
+    /// Records a use of the variable or constant `name`.
+    fn variable(&mut self, name: &str) {
+        let symbol = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.globals.constants.get(name))
+            .copied();
+        let at = self.position(name);
+        match symbol {
+            Some(symbol) => self.table.use_symbol(symbol, at),
+            None => self.error(format!("unknown variable {}", name)),
+        }
+    }
+
+    /// Records the uses of the types `var_type` names, the types of the signature are
+    /// checked but not recorded.
+    fn var_type(&mut self, var_type: &EFSType, used: bool) {
+        match named_types(self.globals, var_type) {
+            Ok(symbols) if used => {
+                for symbol in symbols {
+                    let at = self.position(&self.table.symbols[symbol].name.clone());
+                    self.table.use_symbol(symbol, at);
+                }
+            }
+            Ok(_) => {}
+            Err(unknown) => self.error(format!("unknown type {}", unknown)),
+        }
+    }
+
+    /// Records a use of the struct or enum `name`, written as `path`.
+    fn type_name(&mut self, symbol: usize, path: &str) {
+        let at = self.position(path);
+        self.table.use_symbol(symbol, at);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(Expression::VarDec(name, var_type, value)) => {
+                let at = self.position(name);
+                if let Some(var_type) = var_type {
+                    self.var_type(var_type, true);
+                }
+                self.value(value);
+                self.declare(name, SymbolKind::Local, at);
+            }
+            Statement::Expression(Expression::Assign(name, value)) => {
+                self.variable(name);
+                self.value(value);
+            }
+            Statement::Expression(Expression::AssignField(target, _, _, value)) => {
+                self.value(target);
+                self.value(value);
+            }
+            Statement::Expression(Expression::Value(value)) => self.value(value),
+            Statement::If(condition, block) | Statement::While(condition, block) => {
+                self.value(condition);
+                self.block(block);
+            }
+            Statement::IfLet(name, value, block) => {
+                let at = self.position(name);
+                self.value(value);
+                self.scoped(&[(name, at)], |this| this.block(block));
+            }
+            Statement::Match(value, arms) => {
+                self.value(value);
+                for arm in arms {
+                    let Pattern::Variant(enum_name, variant, bindings) = &arm.pattern else {
+                        self.block(&arm.block);
+                        continue;
+                    };
+                    match self.globals.types.get(enum_name) {
+                        Some(symbol) => {
+                            self.type_name(*symbol, &format!("{}::{}", enum_name, variant))
+                        }
+                        None => self.error(format!("unknown enum {}", enum_name)),
+                    }
+                    let bindings = bindings
+                        .iter()
+                        .flatten()
+                        .map(|name| (name, self.position(name)))
+                        .collect::<Vec<_>>();
+                    self.scoped(&bindings, |this| this.block(&arm.block));
+                }
+            }
+            Statement::For(init, condition, step, block) => {
+                self.scoped(&[], |this| {
+                    this.statement(init);
+                    this.value(condition);
+                    this.statement(step);
+                    this.block(block);
+                });
+            }
+            Statement::ForList(item, list, block) => {
+                let at = self.position(item);
+                self.variable(list);
+                self.scoped(&[(item, at)], |this| this.block(block));
+            }
+            Statement::StaticFor(name, _, _, _, block) => {
+                let at = self.position(name);
+                self.scoped(&[(name, at)], |this| this.block(block));
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.value(value);
+                }
+            }
+            Statement::Commands(commands) => {
+                for command in commands {
+                    // Commands that do not parse are reported by the type checker.
+                    for part in commands::parts(command).unwrap_or_default() {
+                        if let Part::Variable(name, _) = part {
+                            self.variable(name);
+                        }
+                    }
+                }
+            }
+            Statement::Execute(contexts, block) => {
+                for selector in contexts.iter().filter_map(Context::selector) {
+                    self.value(selector);
+                }
+                self.block(block);
+            }
+            Statement::Break | Statement::Continue => {}
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Value(_) => {}
+            Value::Identifier(name) => self.variable(name),
+            Value::Call(name, args) => {
+                let at = self.position(name);
+                match self.globals.functions.get(name) {
+                    Some(symbol) => self.table.use_symbol(*symbol, at),
+                    // Functions of the standard library get a symbol once they are used.
+                    None if stdlib::is_builtin(name) => {
+                        let symbol = self.table.global(name).unwrap_or_else(|| {
+                            self.table.add(Symbol {
+                                name: name.clone(),
+                                kind: SymbolKind::Function,
+                                function: None,
+                                at: None,
+                            })
+                        });
+                        self.table.use_symbol(symbol, at)
+                    }
+                    None => self.error(format!("unknown function {}", name)),
+                }
+                args.iter().for_each(|arg| self.value(arg));
+            }
+            Value::MethodCall(receiver, _, args) => {
+                self.value(receiver);
+                args.iter().for_each(|arg| self.value(arg));
+            }
+            Value::Math(math) => {
+                self.value(&math.left);
+                self.value(&math.right);
+            }
+            Value::List(values) | Value::Text(values) => {
+                values.iter().for_each(|value| self.value(value))
+            }
+            Value::Dict(fields) => self.fields(fields),
+            Value::Struct(name, fields) => {
+                match self.globals.types.get(name) {
+                    Some(symbol) if self.table.symbols[*symbol].kind == SymbolKind::Struct => {
+                        self.type_name(*symbol, name)
+                    }
+                    _ => self.error(format!("unknown struct {}", name)),
+                }
+                self.fields(fields);
+            }
+            Value::Variant(name, variant, args) => {
+                match self.globals.types.get(name) {
+                    Some(symbol) => self.type_name(*symbol, &format!("{}::{}", name, variant)),
+                    None => self.error(format!("unknown enum {}", name)),
+                }
+                args.iter().for_each(|arg| self.value(arg));
+            }
+            Value::Field(value, _) | Value::OptionalField(value, _) => self.value(value),
+            Value::Index(value, index) => {
+                self.value(value);
+                self.value(index);
+            }
+            Value::Cast(value, var_type) => {
+                self.value(value);
+                self.var_type(var_type, true);
+            }
+        }
+    }
+
+    /// Resolves the values of a dict or struct literal in the order of their keys.
+    fn fields(&mut self, fields: &HashMap<String, Value>) {
+        let mut keys = fields.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            self.value(&fields[key]);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::bail;
+use serde_json::{json, Map, Value as Json};
+
+use crate::parser::{
+    ast::Value,
+    types::{EFSType, EFSValueType},
+};
+
+use super::{is_resource_location, selector::is_selector};
+
+/// A JSON object of a single text component.
+pub type Component = Map<String, Json>;
+
+/// Method of a selector that shows the NBT of the entity, `@s.nbt("SelectedItem.id")`.
+pub const ENTITY_NBT: &str = "nbt";
+
+/// Styles of a part of `text!` and the types of their arguments, they are called like
+/// methods: `"hi".color("gold").bold()`.
+const STYLES: &[(&str, &[EFSType])] = &[
+    ("color", &[EFSType::String]),
+    ("bold", &[]),
+    ("italic", &[]),
+    ("underlined", &[]),
+    ("strikethrough", &[]),
+    ("obfuscated", &[]),
+    ("font", &[EFSType::String]),
+    ("insertion", &[EFSType::String]),
+    ("click", &[EFSType::String, EFSType::String]),
+    ("hover", &[EFSType::Text]),
+];
+
+const COLORS: &[&str] = &[
+    "black",
+    "dark_blue",
+    "dark_green",
+    "dark_aqua",
+    "dark_red",
+    "dark_purple",
+    "gold",
+    "gray",
+    "dark_gray",
+    "blue",
+    "green",
+    "aqua",
+    "red",
+    "light_purple",
+    "yellow",
+    "white",
+];
+
+const CLICK_ACTIONS: &[&str] = &[
+    "open_url",
+    "run_command",
+    "suggest_command",
+    "change_page",
+    "copy_to_clipboard",
+];
+
+const FORMATS: &[&str] = &[
+    "bold",
+    "italic",
+    "underlined",
+    "strikethrough",
+    "obfuscated",
+];
+
+/// Keys that hold what a component shows, every component has exactly one of them.
+const CONTENTS: &[&str] = &["text", "translate", "score", "selector", "keybind", "nbt"];
+
+/// Types of the arguments of the style `name`.
+pub fn style_parameters(name: &str) -> Option<&'static [EFSType]> {
+    STYLES
+        .iter()
+        .find(|(style, _)| *style == name)
+        .map(|(_, parameters)| *parameters)
+}
+
+/// `part` without the styles called on it, and the styles in the order they are written.
+pub fn split(part: &Value) -> (&Value, Vec<(&str, &[Value])>) {
+    let mut styles = Vec::new();
+    let mut value = part;
+    while let Value::MethodCall(receiver, method, args) = value {
+        if style_parameters(method).is_none() {
+            break;
+        }
+        styles.push((method.as_str(), args.as_slice()));
+        value = receiver;
+    }
+    styles.reverse();
+    (value, styles)
+}
+
+/// The component showing a constant.
+pub fn literal(value: &EFSValueType) -> anyhow::Result<Component> {
+    let text = match value {
+        EFSValueType::String(text) => text.clone(),
+        EFSValueType::Selector(selector) => return Ok(object(json!({ "selector": selector }))),
+        // Styles of the part apply to the whole nested text.
+        EFSValueType::Text(text) => {
+            let text: Json = serde_json::from_str(text)?;
+            return Ok(object(json!({ "text": "", "extra": [text] })));
+        }
+        EFSValueType::Number(n) | EFSValueType::Int(n) => n.to_string(),
+        EFSValueType::Byte(n) => n.to_string(),
+        EFSValueType::Short(n) => n.to_string(),
+        EFSValueType::Long(n) => n.to_string(),
+        EFSValueType::Float(n) => n.to_string(),
+        EFSValueType::Double(n) => n.to_string(),
+        EFSValueType::Bool(b) => b.to_string(),
+        EFSValueType::None => bail!("None can not be shown in a text component"),
+        value => value.to_snbt(),
+    };
+    Ok(object(json!({ "text": text })))
+}
+
+/// The component showing the score `objective` of `holder`.
+pub fn score(holder: &str, objective: &str) -> Component {
+    object(json!({ "score": { "name": holder, "objective": objective } }))
+}
+
+/// The component showing `path` of the storage `storage`. With `interpret` the
+/// NBT is a JSON text component that is shown as one, like a `Text` variable.
+pub fn storage_nbt(storage: &str, path: &str, interpret: bool) -> Component {
+    let mut component = object(json!({ "nbt": path, "storage": storage }));
+    if interpret {
+        component.insert(String::from("interpret"), Json::Bool(true));
+    }
+    component
+}
+
+/// The component showing `path` of the NBT of the entity `entity`.
+pub fn entity_nbt(entity: &str, path: &str) -> Component {
+    object(json!({ "nbt": path, "entity": entity }))
+}
+
+/// Adds the style `name` to `component`. The values are checked by `validate`.
+pub fn style(component: &mut Component, name: &str, args: &[EFSValueType]) -> anyhow::Result<()> {
+    let (key, value) = match (name, args) {
+        (format, []) if FORMATS.contains(&format) => (format, json!(true)),
+        ("color" | "font" | "insertion", [EFSValueType::String(value)]) => (name, json!(value)),
+        ("click", [EFSValueType::String(action), EFSValueType::String(value)]) => {
+            ("clickEvent", json!({ "action": action, "value": value }))
+        }
+        ("hover", [EFSValueType::String(text)]) => (
+            "hoverEvent",
+            json!({ "action": "show_text", "contents": text }),
+        ),
+        ("hover", [EFSValueType::Text(text)]) => {
+            let text: Json = serde_json::from_str(text)?;
+            (
+                "hoverEvent",
+                json!({ "action": "show_text", "contents": text }),
+            )
+        }
+        _ => {
+            let expected = style_parameters(name).unwrap_or_default();
+            bail!(
+                "style {} takes {:?}, found {:?}",
+                name,
+                expected,
+                args.iter().map(EFSValueType::get_type).collect::<Vec<_>>()
+            )
+        }
+    };
+    component.insert(key.to_string(), value);
+    Ok(())
+}
+
+/// The JSON of `text!` made of `components`. The array starts with an empty string,
+/// otherwise the style of the first part would carry over to the others.
+pub fn join(components: Vec<Component>) -> anyhow::Result<String> {
+    let mut array = vec![json!("")];
+    array.extend(components.into_iter().map(Json::Object));
+    let text = Json::Array(array);
+    validate(&text)?;
+    Ok(text.to_string())
+}
+
+/// The JSON of `text!(parts)` when every part and style is known at compile time.
+pub fn constant(parts: &[Value]) -> anyhow::Result<Option<String>> {
+    let mut components = Vec::new();
+    for part in parts {
+        let (value, styles) = split(part);
+        let Value::Value(value) = value else {
+            return Ok(None);
+        };
+        let mut component = literal(value)?;
+        for (name, args) in styles {
+            let args = args
+                .iter()
+                .map(|arg| match arg {
+                    Value::Value(arg) => Some(arg.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            let Some(args) = args else {
+                return Ok(None);
+            };
+            style(&mut component, name, &args)?;
+        }
+        components.push(component);
+    }
+    join(components).map(Some)
+}
+
+/// Checks that `text` is a text component Minecraft accepts.
+pub fn validate(text: &Json) -> anyhow::Result<()> {
+    match text {
+        Json::String(_) => Ok(()),
+        Json::Array(components) if !components.is_empty() => {
+            components.iter().try_for_each(validate)
+        }
+        Json::Object(component) => validate_component(component),
+        text => bail!("{} is not a text component", text),
+    }
+}
+
+fn validate_component(component: &Component) -> anyhow::Result<()> {
+    let contents = CONTENTS
+        .iter()
+        .filter(|key| component.contains_key(**key))
+        .count();
+    if contents != 1 {
+        bail!(
+            "a text component needs exactly one of {}, found {}",
+            CONTENTS.join(", "),
+            Json::Object(component.clone())
+        );
+    }
+    for (key, value) in component {
+        match (key.as_str(), value) {
+            ("text" | "translate" | "keybind" | "insertion" | "font", Json::String(_)) => {}
+            ("selector", Json::String(selector)) | ("entity", Json::String(selector))
+                if is_selector(selector) => {}
+            ("storage", Json::String(storage)) if is_resource_location(storage) => {}
+            ("nbt", Json::String(path)) if !path.is_empty() => {
+                let sources = ["storage", "entity", "block"]
+                    .iter()
+                    .filter(|key| component.contains_key(**key))
+                    .count();
+                if sources != 1 {
+                    bail!(
+                        "the nbt component {} needs one storage, entity or block",
+                        path
+                    );
+                }
+            }
+            ("block", Json::String(_)) => {}
+            ("score", Json::Object(score))
+                if score.get("name").is_some_and(Json::is_string)
+                    && score.get("objective").is_some_and(Json::is_string) => {}
+            ("color", Json::String(color)) if is_color(color) => {}
+            ("color", Json::String(color)) => bail!(
+                "{} is not a colour, expected #RRGGBB or one of {}",
+                color,
+                COLORS.join(", ")
+            ),
+            (format, Json::Bool(_)) if FORMATS.contains(&format) || format == "interpret" => {}
+            ("clickEvent", Json::Object(event)) => validate_click(event)?,
+            ("hoverEvent", Json::Object(event))
+                if event.get("action") == Some(&json!("show_text")) =>
+            {
+                match event.get("contents") {
+                    Some(contents) => validate(contents)?,
+                    None => bail!("the hover event has no contents"),
+                }
+            }
+            ("extra" | "with", Json::Array(components)) if !components.is_empty() => {
+                components.iter().try_for_each(validate)?
+            }
+            ("separator", separator) => validate(separator)?,
+            (key, value) => bail!("{} is not a valid {} of a text component", value, key),
+        }
+    }
+    Ok(())
+}
+
+fn validate_click(event: &Map<String, Json>) -> anyhow::Result<()> {
+    let (Some(Json::String(action)), Some(Json::String(value))) =
+        (event.get("action"), event.get("value"))
+    else {
+        bail!("a click event needs an action and a value");
+    };
+    match action.as_str() {
+        "open_url" if !(value.starts_with("https://") || value.starts_with("http://")) => {
+            bail!("open_url needs an http or https address, found {}", value)
+        }
+        "run_command" if !value.starts_with('/') => {
+            bail!(
+                "run_command needs a command starting with /, found {}",
+                value
+            )
+        }
+        "change_page" if !value.parse::<u32>().is_ok_and(|page| page > 0) => {
+            bail!("change_page needs a page number, found {}", value)
+        }
+        action if !CLICK_ACTIONS.contains(&action) => bail!(
+            "{} is not a click action, expected one of {}",
+            action,
+            CLICK_ACTIONS.join(", ")
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn is_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => COLORS.contains(&color),
+    }
+}
+
+fn object(value: Json) -> Component {
+    match value {
+        Json::Object(component) => component,
+        _ => unreachable!(),
+    }
+}
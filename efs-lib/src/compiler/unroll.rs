@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::parser::{
+    ast::{CodeBlock, Expression, MatchArm, Math, Pattern, Statement, Value},
+    types::EFSValueType,
+};
+
+/// Values a `static for` takes, `end` is exclusive and a negative `step` counts down.
+pub fn range(start: i32, end: i32, step: i32) -> anyhow::Result<Vec<i32>> {
+    if step == 0 {
+        bail!("the step of a static for can not be 0");
+    }
+    let mut values = Vec::new();
+    let mut value = start;
+    while (step > 0 && value < end) || (step < 0 && value > end) {
+        values.push(value);
+        value = match value.checked_add(step) {
+            Some(value) => value,
+            None => break,
+        };
+    }
+    Ok(values)
+}
+
+/// Copy of `block` with `name` replaced by the constant `value`. Strings get every
+/// `{name}` replaced too, so the value can end up in resource locations and selectors.
+pub fn substitute(block: &CodeBlock, name: &str, value: i32) -> anyhow::Result<CodeBlock> {
+    let substitution = Substitution { name, value };
+    substitution.block(block)
+}
+
+struct Substitution<'a> {
+    name: &'a str,
+    value: i32,
+}
+
+impl Substitution<'_> {
+    fn block(&self, block: &CodeBlock) -> anyhow::Result<CodeBlock> {
+        let mut statements = Vec::new();
+        for statement in &block.statements {
+            statements.push(self.statement(statement)?);
+        }
+        Ok(CodeBlock {
+            statements,
+            positions: block.positions.clone(),
+            names: block.names.clone(),
+            end: block.end,
+        })
+    }
+
+    fn statement(&self, statement: &Statement) -> anyhow::Result<Statement> {
+        Ok(match statement {
+            Statement::For(init, condition, step, block) => Statement::For(
+                Box::new(self.statement(init)?),
+                self.value(condition),
+                Box::new(self.statement(step)?),
+                self.block(block)?,
+            ),
+            Statement::ForList(item, list, block) => {
+                if list == self.name {
+                    bail!("cannot iterate over the static for variable {}", self.name);
+                }
+                if item == self.name {
+                    statement.clone()
+                } else {
+                    Statement::ForList(item.clone(), list.clone(), self.block(block)?)
+                }
+            }
+            // An inner loop over the same name shadows this one.
+            Statement::StaticFor(name, ..) if name == self.name => statement.clone(),
+            Statement::StaticFor(name, start, end, step, block) => {
+                Statement::StaticFor(name.clone(), *start, *end, *step, self.block(block)?)
+            }
+            Statement::While(condition, block) => {
+                Statement::While(self.value(condition), self.block(block)?)
+            }
+            Statement::If(condition, block) => {
+                Statement::If(self.value(condition), self.block(block)?)
+            }
+            Statement::Match(value, arms) => {
+                let mut matched = Vec::new();
+                for arm in arms {
+                    let shadows = matches!(&arm.pattern, Pattern::Variant(_, _, bindings)
+                        if bindings.iter().flatten().any(|binding| binding == self.name));
+                    matched.push(MatchArm {
+                        pattern: arm.pattern.clone(),
+                        block: if shadows {
+                            arm.block.clone()
+                        } else {
+                            self.block(&arm.block)?
+                        },
+                    });
+                }
+                Statement::Match(self.value(value), matched)
+            }
+            Statement::IfLet(name, value, block) if name == self.name => {
+                Statement::IfLet(name.clone(), self.value(value), block.clone())
+            }
+            Statement::IfLet(name, value, block) => {
+                Statement::IfLet(name.clone(), self.value(value), self.block(block)?)
+            }
+            Statement::Return(value) => {
+                Statement::Return(value.as_ref().map(|value| self.value(value)))
+            }
+            Statement::Commands(commands) => {
+                let pattern = format!("$({})", self.name);
+                Statement::Commands(
+                    commands
+                        .iter()
+                        .map(|command| command.replace(&pattern, &self.value.to_string()))
+                        .collect(),
+                )
+            }
+            Statement::Execute(contexts, block) => Statement::Execute(
+                contexts
+                    .iter()
+                    .map(|context| context.map_selector(|selector| self.value(selector)))
+                    .collect(),
+                self.block(block)?,
+            ),
+            Statement::Break => Statement::Break,
+            Statement::Continue => Statement::Continue,
+            Statement::Expression(Expression::VarDec(name, _, _))
+            | Statement::Expression(Expression::Assign(name, _))
+                if name == self.name =>
+            {
+                bail!("cannot assign to the static for variable {}", self.name)
+            }
+            Statement::Expression(Expression::VarDec(name, var_type, value)) => {
+                Statement::Expression(Expression::VarDec(
+                    name.clone(),
+                    var_type.clone(),
+                    self.value(value),
+                ))
+            }
+            Statement::Expression(Expression::Assign(name, value)) => {
+                Statement::Expression(Expression::Assign(name.clone(), self.value(value)))
+            }
+            Statement::Expression(Expression::AssignField(target, field, op, value)) => {
+                Statement::Expression(Expression::AssignField(
+                    self.value(target),
+                    field.clone(),
+                    op.clone(),
+                    self.value(value),
+                ))
+            }
+            Statement::Expression(Expression::Value(value)) => {
+                Statement::Expression(Expression::Value(self.value(value)))
+            }
+        })
+    }
+
+    fn value(&self, value: &Value) -> Value {
+        match value {
+            Value::Identifier(name) if name == self.name => {
+                Value::Value(EFSValueType::Number(self.value))
+            }
+            Value::Value(value) => Value::Value(self.literal(value)),
+            Value::Math(math) => Value::Math(Box::new(Math {
+                left: self.value(&math.left),
+                op: math.op.clone(),
+                right: self.value(&math.right),
+            })),
+            Value::Identifier(_) => value.clone(),
+            Value::List(values) => Value::List(values.iter().map(|v| self.value(v)).collect()),
+            Value::Dict(values) => Value::Dict(self.fields(values)),
+            Value::Struct(name, values) => Value::Struct(name.clone(), self.fields(values)),
+            Value::Call(name, args) => {
+                Value::Call(name.clone(), args.iter().map(|v| self.value(v)).collect())
+            }
+            Value::MethodCall(value, method, args) => Value::MethodCall(
+                Box::new(self.value(value)),
+                method.clone(),
+                args.iter().map(|v| self.value(v)).collect(),
+            ),
+            Value::Text(parts) => Value::Text(parts.iter().map(|v| self.value(v)).collect()),
+            Value::Cast(value, var_type) => {
+                Value::Cast(Box::new(self.value(value)), var_type.clone())
+            }
+            Value::Field(value, field) => Value::Field(Box::new(self.value(value)), field.clone()),
+            Value::Variant(name, variant, args) => Value::Variant(
+                name.clone(),
+                variant.clone(),
+                args.iter().map(|v| self.value(v)).collect(),
+            ),
+            Value::OptionalField(value, field) => {
+                Value::OptionalField(Box::new(self.value(value)), field.clone())
+            }
+            Value::Index(value, index) => {
+                Value::Index(Box::new(self.value(value)), Box::new(self.value(index)))
+            }
+        }
+    }
+
+    fn fields(&self, values: &HashMap<String, Value>) -> HashMap<String, Value> {
+        values
+            .iter()
+            .map(|(key, value)| (key.clone(), self.value(value)))
+            .collect()
+    }
+
+    fn literal(&self, value: &EFSValueType) -> EFSValueType {
+        let pattern = format!("{{{}}}", self.name);
+        match value {
+            EFSValueType::String(string) => {
+                EFSValueType::String(string.replace(&pattern, &self.value.to_string()))
+            }
+            EFSValueType::Selector(selector) => {
+                EFSValueType::Selector(selector.replace(&pattern, &self.value.to_string()))
+            }
+            EFSValueType::List(values) => {
+                EFSValueType::List(values.iter().map(|v| self.literal(v)).collect())
+            }
+            EFSValueType::Dict(values) => EFSValueType::Dict(
+                values
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.literal(value)))
+                    .collect(),
+            ),
+            EFSValueType::Struct(name, values) => EFSValueType::Struct(
+                name.clone(),
+                values
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.literal(value)))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+}
@@ -4,9 +4,15 @@ use std::path::PathBuf;
 pub struct Config {
     pub name: String,
     pub version: String,
+    /// Namespace of the datapack, the name of the project when empty.
+    #[serde(default)]
+    pub namespace: String,
     pub targets: Vec<PathBuf>,
     pub datapack_format: String,
     pub command_format: String,
+    /// Also generate a function that removes the datapack's scoreboards and storage.
+    #[serde(default)]
+    pub uninstall: bool,
 }
 
 impl Config {
@@ -14,9 +20,19 @@ impl Config {
         Self {
             name,
             version: String::from("0.1.0"),
+            namespace: Default::default(),
             targets: Default::default(),
             datapack_format: Default::default(),
             command_format: Default::default(),
+            uninstall: false,
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        if self.namespace.is_empty() {
+            &self.name
+        } else {
+            &self.namespace
         }
     }
 }
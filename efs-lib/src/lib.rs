@@ -1,12 +1,31 @@
+pub mod compiler;
 pub mod config;
+pub mod nbt;
 pub mod parser;
 pub mod project;
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{
-        lexer::Lexer,
-        token::{self, Token, TokenType},
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        compiler::{
+            self,
+            modules::{self, Module},
+            symbols::{Symbol, SymbolKind, SymbolTable},
+            Allocator, Location,
+        },
+        nbt::{self, Compression},
+        parser::{
+            ast::{Declaration, Program, Value},
+            lexer::Lexer,
+            token::{self, Token, TokenType},
+            types::{EFSType, EFSValueType},
+            Parser,
+        },
     };
 
     static TEST_TEXT: &str = "
@@ -19,6 +38,46 @@ mod tests {
         }
     ";
 
+    /// Parses `source`, which has to be valid.
+    fn parse(source: &str) -> Program {
+        Parser::new(String::from(source))
+            .unwrap()
+            .parse::<Program>()
+            .unwrap()
+    }
+
+    /// The module `name` of the project, at the file its name maps to.
+    fn module(name: &str, source: &str) -> Module {
+        Module {
+            name: String::from(name),
+            file: PathBuf::from(format!("src/{}.efs", name.replace("::", "/"))),
+            program: parse(source),
+        }
+    }
+
+    /// The error compiling `source` into the namespace `test` fails with.
+    fn compile_error(source: &str) -> String {
+        format!(
+            "{:#}",
+            compiler::compile("test", &[parse(source)]).err().unwrap()
+        )
+    }
+
+    /// Offset of the first `text` in `source`, where type errors are reported.
+    fn at(source: &str, text: &str) -> usize {
+        source.find(text).unwrap()
+    }
+
+    /// Asserts that checking `source` reports exactly the errors `expected`.
+    fn assert_type_errors(source: &str, expected: &[(usize, &str)]) {
+        let errors = compiler::check::check(&[parse(source)]).unwrap_err().0;
+        let found = errors
+            .iter()
+            .map(|error| (error.at, error.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+
     #[test]
     fn token() {
         let text = String::from("i8");
@@ -29,7 +88,6 @@ mod tests {
             enum_iterator::all::<token::Keyword>().collect::<Vec<_>>()
         );
 
-
         assert_eq!(
             lex.next_token().unwrap().token,
             Token::TypeName(token::TypeName::Byte)
@@ -58,4 +116,2265 @@ mod tests {
         assert!(!failed);
         //assert!(false);
     }
+
+    #[test]
+    fn storage_variables() {
+        let mut alloc = Allocator::new("test");
+
+        let Location::Storage(list) = alloc.allocate(&EFSType::List(Box::new(EFSType::String)))
+        else {
+            panic!("lists should live in storage");
+        };
+        let Location::Score(len) = alloc.allocate(&EFSType::Int) else {
+            panic!("ints should live in a score");
+        };
+
+        assert_eq!(
+            list.set_value(&EFSValueType::List(Vec::new())),
+            "data modify storage test:vars v0 set value []"
+        );
+        assert_eq!(
+            list.append_value(&EFSValueType::String(String::from("a"))),
+            "data modify storage test:vars v0 append value \"a\""
+        );
+        assert_eq!(
+            list.index(-1).set_from(&list.index(0)),
+            "data modify storage test:vars v0[-1] set from storage test:vars v0[0]"
+        );
+        assert_eq!(
+            list.store_length(&len),
+            "execute store result score $v0 test.vars run data get storage test:vars v0"
+        );
+        assert_eq!(list.field("a key").path, "v0.\"a key\"",);
+
+        let Location::Storage(float) = alloc.allocate(&EFSType::Float) else {
+            panic!("floats should live in storage");
+        };
+        assert_eq!(
+            Location::Storage(float.clone()).copy_from(&Location::Score(len.clone()), &EFSType::Float),
+            "execute store result storage test:vars v1 float 1 run scoreboard players get $v0 test.vars"
+        );
+        assert_eq!(
+            Location::Score(len).copy_from(&Location::Storage(float), &EFSType::Int),
+            "execute store result score $v0 test.vars run data get storage test:vars v1 1"
+        );
+    }
+
+    #[test]
+    fn snbt() {
+        let text = r#"{Count:1b,"display name":'say "hi"',id:"minecraft:stone",tags:[B;1b,0b],pos:[1.5d,-2.25f,3L,4s,5]}"#;
+        let value = EFSValueType::from_snbt(text).unwrap();
+
+        let EFSValueType::Dict(entries) = &value else {
+            panic!("expected a compound, got {:?}", value);
+        };
+        assert_eq!(entries["Count"], EFSValueType::Byte(1));
+        assert_eq!(
+            entries["display name"],
+            EFSValueType::String(String::from("say \"hi\""))
+        );
+        assert_eq!(entries["tags"], EFSValueType::NBTByteArray(vec![1, 0]));
+        assert_eq!(
+            entries["pos"],
+            EFSValueType::List(vec![
+                EFSValueType::Double(1.5),
+                EFSValueType::Float(-2.25),
+                EFSValueType::Long(3),
+                EFSValueType::Short(4),
+                EFSValueType::Int(5),
+            ])
+        );
+
+        assert_eq!(
+            value.to_snbt(),
+            r#"{Count:1b,"display name":'say "hi"',id:"minecraft:stone",pos:[1.5d,-2.25f,3L,4s,5],tags:[B;1b,0b]}"#
+        );
+        assert_eq!(EFSValueType::from_snbt(&value.to_snbt()).unwrap(), value);
+
+        assert!(EFSValueType::from_snbt("{a:1").is_err());
+        assert!(EFSValueType::from_snbt("[I;1b]").is_err());
+        assert!(EFSValueType::from_snbt("300b").is_err());
+
+        let empty = EFSValueType::from_snbt(r#"{"":1,'':2}"#).unwrap();
+        assert_eq!(
+            empty,
+            EFSValueType::Dict(HashMap::from([(String::new(), EFSValueType::Int(2))]))
+        );
+        assert_eq!(empty.to_snbt(), r#"{"":2}"#);
+        assert!(EFSValueType::from_snbt("{:1}").is_err());
+        let huge = format!("{}.0", "9".repeat(40));
+        assert!(EFSValueType::from_snbt(&format!("{}f", huge)).is_err());
+        assert!(EFSValueType::from_snbt(&format!("{}d", huge)).is_ok());
+        assert!(EFSValueType::from_snbt(&format!("{}d", "9".repeat(400))).is_err());
+        assert_eq!(EFSValueType::Double(1e300).convert(&EFSType::Float), None);
+    }
+
+    #[test]
+    fn parse_error_display() {
+        let error = |source: &str| {
+            let error = Parser::new(String::from(source))
+                .unwrap()
+                .parse::<Program>()
+                .err()
+                .unwrap();
+            error.to_string()
+        };
+        assert_eq!(
+            error("const X = 1"),
+            "parse error at 11. found: EOI; expected: ControlCharacter(EndOfLine)"
+        );
+        assert_eq!(
+            error("fn main() { let = 1; }"),
+            "parse error at 16. found: ControlCharacter(Assign); expected: Identifier"
+        );
+        assert_eq!(
+            error("struct A { x: i32 y: i32 }"),
+            "parse error at 18. found: Identifier(\"y\"); expected: ControlCharacter(Comma), or ControlCharacter(RBrace)"
+        );
+    }
+
+    #[test]
+    fn nbt_literal() {
+        let parser = Parser::new(String::from(
+            "const item: Dict = nbt { id: \"minecraft:apple\", Count: 2b };",
+        ))
+        .unwrap();
+        let program = parser.parse::<Program>().unwrap();
+
+        let Declaration::ConstDec(assignment) = &program.0[0] else {
+            panic!("expected a const declaration");
+        };
+        assert_eq!(assignment.name, "item");
+        assert_eq!(
+            assignment.var_type,
+            Some(EFSType::Dict(Box::new(EFSType::None)))
+        );
+        assert_eq!(
+            assignment.var,
+            Value::Value(EFSValueType::Dict(HashMap::from([
+                (
+                    String::from("id"),
+                    EFSValueType::String(String::from("minecraft:apple"))
+                ),
+                (String::from("Count"), EFSValueType::Byte(2)),
+            ])))
+        );
+    }
+
+    #[test]
+    fn binary_nbt() {
+        // "hello world" example from the nbt specification
+        let mut bytes = vec![0x0a, 0x00, 0x0b];
+        bytes.extend_from_slice(b"hello world");
+        bytes.extend_from_slice(&[0x08, 0x00, 0x04]);
+        bytes.extend_from_slice(b"name");
+        bytes.extend_from_slice(&[0x00, 0x09]);
+        bytes.extend_from_slice(b"Bananrama");
+        bytes.push(0x00);
+
+        let (name, value) = nbt::read(&bytes).unwrap();
+        assert_eq!(name, "hello world");
+        assert_eq!(
+            value,
+            EFSValueType::Dict(HashMap::from([(
+                String::from("name"),
+                EFSValueType::String(String::from("Bananrama"))
+            )]))
+        );
+        assert_eq!(nbt::write(&name, &value, Compression::None).unwrap(), bytes);
+
+        let value = EFSValueType::from_snbt(
+            r#"{size:[I;2,1,3],blocks:[{pos:[0,0,0],state:0}],palette:[{Name:"minecraft:stone"}],DataVersion:3337,extra:{b:1b,s:-2s,l:9000000000L,f:0.5f,d:0.25d,bytes:[B;1b],longs:[L;],empty:[],text:"🎉"}}"#,
+        )
+        .unwrap();
+        for compression in [Compression::None, Compression::Gzip] {
+            let bytes = nbt::write("", &value, compression).unwrap();
+            assert_eq!(
+                bytes.starts_with(&[0x1f, 0x8b]),
+                compression == Compression::Gzip
+            );
+            assert_eq!(nbt::read(&bytes).unwrap(), (String::new(), value.clone()));
+        }
+
+        let nul = EFSValueType::String(String::from("a\0b"));
+        let bytes = nbt::write("", &nul, Compression::None).unwrap();
+        assert_eq!(&bytes[bytes.len() - 4..], &[b'a', 0xc0, 0x80, b'b']);
+        assert_eq!(nbt::read(&bytes).unwrap().1, nul);
+
+        assert!(nbt::read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(nbt::write(
+            "",
+            &EFSValueType::List(vec![EFSValueType::Int(1), EFSValueType::Byte(1)]),
+            Compression::None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn function_calls() {
+        let program = parse(
+            "
+            fn add(a: i32, b: i32) -> i32 {
+                return a + b;
+            }
+
+            fn greet(name: string) -> string {
+                if (name == \"\") {
+                    return \"nobody\";
+                }
+                return name;
+            }
+
+            fn main() {
+                let x = add(1, add(2, 3)) * 2;
+                let s: string = greet(\"steve\");
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+
+        let main = &datapack.functions["test:_efs/fn/main"];
+        let first_call = main
+            .iter()
+            .position(|c| c == "function test:_efs/fn/add")
+            .unwrap();
+        assert_eq!(
+            main[first_call - 2],
+            "scoreboard players set $arg0 test.vars 2"
+        );
+        assert_eq!(
+            main[first_call - 1],
+            "scoreboard players set $arg1 test.vars 3"
+        );
+        assert!(main.contains(&String::from(
+            "data modify storage test:vars frame.arg0 set value \"steve\""
+        )));
+
+        let add = &datapack.functions["test:_efs/fn/add"];
+        assert_eq!(
+            add[0],
+            "scoreboard players operation $v0 test.vars = $arg0 test.vars"
+        );
+        assert_eq!(
+            add[add.len() - 2],
+            "scoreboard players operation $ret test.vars = $v2 test.vars"
+        );
+
+        let greet = &datapack.functions["test:_efs/fn/greet"];
+        assert!(greet.contains(&String::from(
+            "execute if score $control test.vars matches 1 run return run scoreboard players set $control test.vars 0"
+        )));
+        assert_eq!(
+            datapack.functions["test:_efs/greet/if0"],
+            [
+                "data modify storage test:vars frame.ret set value \"nobody\"",
+                "scoreboard players set $control test.vars 1",
+                "return 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn short_circuit() {
+        let program = parse(
+            "
+            fn check(n: i32) -> bool {
+                return n > 0;
+            }
+
+            fn main(a: bool, n: i32) {
+                let both = a && check(n);
+                let either = a || n > 1;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        // `check` only runs when `a` holds, the comparison only when it does not.
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"][2..],
+            [
+                "execute store success score $v6 test.vars unless score $v3 test.vars matches 0",
+                "execute if score $v6 test.vars matches 1.. run function test:_efs/main/and0",
+                "scoreboard players operation $v5 test.vars = $v6 test.vars",
+                "execute store success score $v9 test.vars unless score $v3 test.vars matches 0",
+                "execute if score $v9 test.vars matches 0 run function test:_efs/main/or1",
+                "scoreboard players operation $v8 test.vars = $v9 test.vars",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/and0"],
+            [
+                "scoreboard players operation $arg0 test.vars = $v4 test.vars",
+                "function test:_efs/fn/check",
+                "scoreboard players operation $v7 test.vars = $ret test.vars",
+                "execute store success score $v6 test.vars unless score $v7 test.vars matches 0",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/or1"][2],
+            "execute store success score $v9 test.vars unless score $v10 test.vars matches 0"
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        let program = parse(
+            "
+            fn fib(n: i32) -> i32 {
+                if (n < 2) {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn is_even(n: i32) -> bool {
+                if (n == 0) {
+                    return true;
+                }
+                return is_odd(n - 1);
+            }
+
+            fn is_odd(n: i32) -> bool {
+                if (n == 0) {
+                    return false;
+                }
+                return is_even(n - 1);
+            }
+
+            fn walk(n: i32, name: string?) {
+                if (n > 0) {
+                    walk(n - 1, None);
+                }
+            }
+
+            fn main() {
+                let x = fib(10);
+                walk(2, \"a\");
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+
+        for name in ["fib", "is_even", "is_odd"] {
+            let wrapper = &datapack.functions[&format!("test:_efs/fn/{}", name)];
+            assert_eq!(
+                wrapper[0],
+                "data modify storage test:vars stack append value {}"
+            );
+            assert!(wrapper.contains(&format!("function test:_efs/{}/body", name)));
+            assert_eq!(
+                wrapper.last().unwrap(),
+                "data remove storage test:vars stack[-1]"
+            );
+        }
+        let fib = &datapack.functions["test:_efs/fn/fib"];
+        assert_eq!(
+            fib[1],
+            "execute store result storage test:vars stack[-1].l0 int 1 run scoreboard players get $v0 test.vars"
+        );
+        assert!(fib.contains(&String::from(
+            "execute store result score $v0 test.vars run data get storage test:vars stack[-1].l0 1"
+        )));
+
+        // The optional name is removed before it is restored, so a frame where it
+        // was None leaves it None.
+        let walk = &datapack.functions["test:_efs/fn/walk"];
+        let restore = walk
+            .iter()
+            .position(|command| command == "data remove storage test:vars v0")
+            .unwrap();
+        assert_eq!(
+            walk[restore + 1],
+            "data modify storage test:vars v0 set from storage test:vars stack[-1].l1"
+        );
+
+        assert!(!datapack.functions.contains_key("test:_efs/main/body"));
+        assert!(!datapack.functions["test:_efs/fn/main"][0].contains("stack"));
+    }
+
+    #[test]
+    fn loops() {
+        let program = parse(
+            "
+            fn sum(limit: i32) -> i32 {
+                let total = 0;
+                for (let i = 0; i < limit; i = i + 1) {
+                    if (i == 3) {
+                        continue;
+                    }
+                    if (i > 7) {
+                        break;
+                    }
+                    total = total + i;
+                }
+                return total;
+            }
+
+            fn countdown(n: i32) {
+                while (n > 0) {
+                    n = n - 1;
+                }
+            }
+
+            fn skip(limit: i32) {
+                for (let i = 0; i < limit; i += 1) {
+                    static for j in 0..2 {
+                        if (i == j) {
+                            continue;
+                        }
+                    }
+                }
+                for (let k = 0; k < limit; k += 1) {
+                    positioned ~ ~1 ~ {
+                        continue;
+                    }
+                }
+            }
+
+            fn greet() {
+                let names = [\"alex\", \"steve\"];
+                for (name in names) {
+                    let greeting = name;
+                }
+            }
+
+            const PRIMES: List<i32> = [2, 3, 5];
+
+            fn primes() {
+                for (n in PRIMES) {
+                    let p = n;
+                }
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+
+        let sum = &datapack.functions["test:_efs/fn/sum"];
+        assert!(sum.contains(&String::from("function test:_efs/sum/for0")));
+        let body = &datapack.functions["test:_efs/sum/for0"];
+        assert_eq!(body.last().unwrap(), "function test:_efs/sum/for0_step");
+        assert!(body.contains(&String::from(
+            "execute if score $control test.vars matches 3 run return run function test:_efs/sum/for0_step"
+        )));
+        assert!(body.contains(&String::from(
+            "execute if score $control test.vars matches 2 run return run scoreboard players set $control test.vars 0"
+        )));
+        let step = &datapack.functions["test:_efs/sum/for0_step"];
+        assert_eq!(step[0], "scoreboard players set $control test.vars 0");
+        assert_eq!(step.last().unwrap(), "function test:_efs/sum/for0");
+
+        // A continue in `static for` or an execute block still runs the step.
+        for (function, step) in [("for0", "for0_step"), ("for3", "for3_step")] {
+            let body = &datapack.functions[&format!("test:_efs/skip/{}", function)];
+            assert!(body.contains(&format!(
+                "execute if score $control test.vars matches 3 run return run function test:_efs/skip/{}",
+                step
+            )));
+            let step = &datapack.functions[&format!("test:_efs/skip/{}", step)];
+            assert_eq!(step[0], "scoreboard players set $control test.vars 0");
+            assert!(step[2].starts_with("scoreboard players add"));
+        }
+
+        let countdown = &datapack.functions["test:_efs/countdown/while0"];
+        assert_eq!(
+            countdown.last().unwrap(),
+            "function test:_efs/countdown/while0"
+        );
+        assert!(countdown[2].ends_with("matches 0 run return 0"));
+
+        let greet = &datapack.functions["test:_efs/greet/for_in0"];
+        assert_eq!(
+            greet[0],
+            "execute unless data storage test:vars v1[0] run return 0"
+        );
+        assert_eq!(greet[2], "data remove storage test:vars v1[0]");
+
+        assert_eq!(
+            datapack.functions["test:_efs/fn/primes"],
+            [
+                "data modify storage test:vars v4 set value [2,3,5]",
+                "function test:_efs/primes/for_in0",
+            ]
+        );
+    }
+
+    #[test]
+    fn static_for() {
+        let program = parse(
+            "
+            fn slots() {
+                static for i in 0..6 step 2 {
+                    let slot = \"container.{i}\";
+                    let doubled = i * 2;
+                }
+                static for i in 3..-3 step -3 {
+                    let x = i;
+                }
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+
+        let slots = &datapack.functions["test:_efs/fn/slots"];
+        for (index, slot) in [0, 2, 4].into_iter().enumerate() {
+            assert!(slots.contains(&format!(
+                "data modify storage test:vars v{} set value \"container.{}\"",
+                index, slot
+            )));
+        }
+        assert!(!slots.iter().any(|command| command.contains("container.6")));
+        assert_eq!(
+            slots[slots.len() - 2..],
+            [
+                "scoreboard players set $v3 test.vars 3",
+                "scoreboard players set $v4 test.vars 0",
+            ]
+        );
+
+        // Only `..` stays out of a number, `2.` is still a float.
+        let tokens = |text: &str| {
+            let mut lex = Lexer::new(String::from(text));
+            let mut tokens = Vec::new();
+            loop {
+                match lex.next_token().unwrap().token {
+                    Token::EOI => return tokens,
+                    token => tokens.push(token),
+                }
+            }
+        };
+        assert_eq!(
+            tokens("0..6"),
+            [
+                Token::Integer(0),
+                Token::ControlCharacter(token::ControlCharacter::Range),
+                Token::Integer(6),
+            ]
+        );
+        assert_eq!(tokens("2. 2.5"), [Token::Float(2.0), Token::Float(2.5)]);
+        assert!(Parser::new(String::from(TEST_TEXT))
+            .unwrap()
+            .parse::<Program>()
+            .is_ok());
+    }
+
+    #[test]
+    fn function_tags() {
+        let a = parse(
+            "
+            #[load, tag(\"hooks\")]
+            fn setup() {}
+            #[tick]
+            fn update() {}
+            ",
+        );
+        let b = parse(
+            "
+            #[load]
+            #[tag(\"other:events\")]
+            fn another() {}
+            ",
+        );
+        let forward = compiler::compile("test", &[a, b]).unwrap();
+        assert_eq!(
+            forward.tag_json("minecraft:load"),
+            "{\n  \"values\": [\n    \"test:_efs/load\",\n    \"test:_efs/fn/another\",\n    \"test:_efs/fn/setup\"\n  ]\n}"
+        );
+        assert_eq!(
+            forward.tags.keys().collect::<Vec<_>>(),
+            [
+                "minecraft:load",
+                "minecraft:tick",
+                "other:events",
+                "test:hooks"
+            ]
+        );
+        assert_eq!(
+            forward.tags["test:hooks"].iter().collect::<Vec<_>>(),
+            ["test:_efs/fn/setup"]
+        );
+
+        let reversed = compiler::compile(
+            "test",
+            &[
+                parse("#[load] #[tag(\"other:events\")] fn another() {}"),
+                parse("#[load, tag(\"hooks\")] fn setup() {} #[tick] fn update() {}"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(reversed.tags, forward.tags);
+
+        let with_args = parse("#[tick] fn update(n: i32) {}");
+        assert!(compiler::compile("test", &[with_args]).is_err());
+
+        let typo = parse("#[tik] fn update() {}");
+        assert_eq!(
+            format!("{:#}", compiler::compile("test", &[typo]).err().unwrap()),
+            "unknown attribute #[tik] on function update, expected one of load, tick, tag, export"
+        );
+    }
+
+    #[test]
+    fn load_function() {
+        let program = parse(
+            "
+            const LIMIT: i32 = 20;
+            const GREETING = \"hello\";
+
+            fn over(n: i32) -> bool {
+                return n > LIMIT;
+            }
+            ",
+        );
+        let mut compiler = compiler::Compiler::new("test");
+        compiler.uninstall = true;
+        compiler.compile(&[program]).unwrap();
+        let datapack = compiler.datapack;
+
+        assert_eq!(
+            datapack.functions["test:_efs/load"],
+            [
+                "scoreboard objectives add test.vars dummy",
+                "scoreboard players set $control test.vars 0",
+                "data modify storage test:vars stack set value []",
+                "data modify storage test:vars frame set value {}",
+            ]
+        );
+        assert_eq!(datapack.tags["minecraft:load"], ["test:_efs/load"]);
+        assert!(datapack.functions["test:_efs/fn/over"]
+            .iter()
+            .any(|command| command.ends_with("test.vars 20")));
+        assert_eq!(
+            datapack.functions["test:_efs/uninstall"],
+            [
+                "scoreboard objectives remove test.vars",
+                "data remove storage test:vars stack",
+                "data remove storage test:vars frame",
+                "data remove storage test:vars scratch",
+                "data remove storage test:vars extern",
+            ]
+        );
+
+        let program = parse(
+            "
+            fn main() {
+                let slots = [1, 2, 3];
+                let i = 1;
+                let slot = slots[i];
+            }
+            ",
+        );
+        let mut compiler = compiler::Compiler::new("test");
+        compiler.uninstall = true;
+        compiler.compile(&[program]).unwrap();
+        let datapack = compiler.datapack;
+        let uninstall = &datapack.functions["test:_efs/uninstall"];
+        let storage = "storage test:vars ";
+        for command in datapack.functions.values().flatten() {
+            for (at, _) in command.match_indices(storage) {
+                let path = &command[at + storage.len()..];
+                let root = path.split(|c: char| " .[".contains(c)).next().unwrap();
+                let remove = format!("data remove {}{}", storage, root);
+                assert!(uninstall.contains(&remove), "{} is not removed", root);
+            }
+        }
+
+        let program = parse("const LIMIT = 1; fn main() { LIMIT = 2; }");
+        assert!(compiler::compile("test", &[program]).is_err());
+    }
+
+    #[test]
+    fn constant_folding() {
+        let program = parse(
+            "
+            const SIZE = 4 * 8 - 2;
+            const HALF: i16 = SIZE / 4;
+            const NAME = \"slot_\" + \"a\";
+            const SLOTS = [SIZE, SIZE * 2];
+            const ORIGIN = Point { x: 0, y: -SIZE };
+            const CONFIG = { size: SIZE, name: NAME };
+
+            fn main() {
+                let x = SIZE + 1;
+                let y = x * (HALF + 1);
+                let SIZE = 2;
+                let z = SIZE;
+                let config = CONFIG;
+                let origin = ORIGIN;
+                let slots = SLOTS;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v0 test.vars 31",
+                "scoreboard players operation $v2 test.vars = $v0 test.vars",
+                "scoreboard players set $v3 test.vars 8",
+                "scoreboard players operation $v2 test.vars *= $v3 test.vars",
+                "scoreboard players operation $v1 test.vars = $v2 test.vars",
+                "scoreboard players set $v4 test.vars 2",
+                "scoreboard players operation $v5 test.vars = $v4 test.vars",
+                "data modify storage test:vars v0 set value {name:\"slot_a\",size:30}",
+                "data modify storage test:vars v1 set value {x:0,y:-30}",
+                "data modify storage test:vars v2 set value [30,60]",
+            ]
+        );
+
+        for (source, message) in [
+            ("const BIG: i8 = 100 + 28;", "128 overflows Byte"),
+            ("const BIG = 2147483647 + 1;", "overflows Number"),
+            ("const ZERO = 1 / 0;", "division by zero"),
+            ("const MIXED = [1, 2.5];", "same type"),
+            (
+                "fn f() -> i32 { return 1; } const CALL = f();",
+                "cannot call f",
+            ),
+        ] {
+            let error = compile_error(source);
+            assert!(error.contains(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn type_check() {
+        let source = "
+            struct Point { x: i32, y: i32 }
+
+            fn length(p: Point) -> i32 {
+                return p;
+            }
+
+            fn main() {
+                let x: f32 = \"hi\";
+                let small: i8 = 300;
+                let fits: i8 = 100;
+                let count = 1;
+                count = 2.5;
+                let origin = Point { x: 1, z: 2 };
+                let total = length(count, 2);
+                if (count) {
+                    break;
+                }
+                let speed: f32 = 2.0;
+                let faster: f32 = speed * 1.5 + 0.25;
+            }
+
+            fn missing() -> bool {
+                let b = true && 1 < 2;
+            }
+        ";
+        assert_type_errors(
+            source,
+            &[
+                (
+                    at(source, "return p"),
+                    "expected Int, found Struct(\"Point\") (in function length)",
+                ),
+                (
+                    at(source, "let x"),
+                    "expected Float, found String (in function main)",
+                ),
+                (
+                    at(source, "let small"),
+                    "300 overflows Byte, use `as` to convert it (in function main)",
+                ),
+                (
+                    at(source, "count = 2.5"),
+                    "expected Number, found Double, use `as` to convert it (in function main)",
+                ),
+                (
+                    at(source, "let origin"),
+                    "struct Point has no field z (in function main)",
+                ),
+                (
+                    at(source, "let origin"),
+                    "missing field y of struct Point (in function main)",
+                ),
+                (
+                    at(source, "let total"),
+                    "function length takes 1 arguments but 2 were given (in function main)",
+                ),
+                (
+                    at(source, "let total"),
+                    "expected Struct(\"Point\"), found Number (in function main)",
+                ),
+                (
+                    at(source, "if (count)"),
+                    "expected a condition of type Bool, found Number (in function main)",
+                ),
+                (
+                    at(source, "break"),
+                    "break outside of a loop (in function main)",
+                ),
+                (
+                    source.rfind('}').unwrap(),
+                    "function missing must return a Bool (in function missing)",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn casts() {
+        let program = parse(
+            "
+            const WRAPPED = 300 as i8;
+            const FLOORED = -1.5 as i32;
+
+            fn main() {
+                let big: i32 = 1000;
+                let small = big as i8;
+                let wide = big as f64;
+                let back = wide as i16;
+                let sum = small + big;
+                let folded = WRAPPED + FLOORED;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v0 test.vars 1000",
+                "execute store result storage test:vars scratch byte 1 run scoreboard players get $v0 test.vars",
+                "execute store result score $v2 test.vars run data get storage test:vars scratch 1",
+                "scoreboard players operation $v1 test.vars = $v2 test.vars",
+                "execute store result storage test:vars v1 double 1 run scoreboard players get $v0 test.vars",
+                "data modify storage test:vars v0 set from storage test:vars v1",
+                "execute store result storage test:vars scratch short 1 run data get storage test:vars v0 1",
+                "execute store result score $v4 test.vars run data get storage test:vars scratch 1",
+                "scoreboard players operation $v3 test.vars = $v4 test.vars",
+                "scoreboard players operation $v6 test.vars = $v1 test.vars",
+                "scoreboard players operation $v6 test.vars += $v0 test.vars",
+                "scoreboard players operation $v5 test.vars = $v6 test.vars",
+                "scoreboard players set $v7 test.vars 42",
+            ]
+        );
+
+        // Floats are computed in fixed point with three decimals.
+        let program = parse(
+            "
+            fn floats(speed: f32, steps: i32) {
+                let next: f32 = speed * 1.5 + steps;
+                let slow = speed / 2.0 < 0.5;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/floats"][2..14],
+            [
+                "execute store result score $v3 test.vars run data get storage test:vars v0 1000",
+                "scoreboard players set $v4 test.vars 1500",
+                "scoreboard players operation $v2 test.vars = $v3 test.vars",
+                "scoreboard players operation $v2 test.vars *= $v4 test.vars",
+                "execute store result storage test:vars scratch double 0.001 run scoreboard players get $v2 test.vars",
+                "execute store result score $v2 test.vars run data get storage test:vars scratch 1",
+                "execute store result storage test:vars scratch int 1 run scoreboard players get $v0 test.vars",
+                "execute store result score $v5 test.vars run data get storage test:vars scratch 1000",
+                "scoreboard players operation $v1 test.vars = $v2 test.vars",
+                "scoreboard players operation $v1 test.vars += $v5 test.vars",
+                "execute store result storage test:vars v2 float 0.001 run scoreboard players get $v1 test.vars",
+                "data modify storage test:vars v1 set from storage test:vars v2",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/fn/floats"][17..20],
+            [
+                "execute store result storage test:vars scratch double 1000 run scoreboard players get $v8 test.vars",
+                "execute store result score $v8 test.vars run data get storage test:vars scratch 1",
+                "scoreboard players operation $v8 test.vars /= $v10 test.vars",
+            ]
+        );
+
+        let error = |source: &str| {
+            let errors = compiler::check::check(&[parse(source)]).unwrap_err().0;
+            errors[0].message.clone()
+        };
+        assert_eq!(
+            error("fn main() { let s = \"a\" as i32; }"),
+            "cannot cast String to Int (in function main)"
+        );
+        assert_eq!(
+            error("fn main() { let l: i64 = 5; let m = l + 1; }"),
+            "cannot use Plus on Long and Number, scores hold 32 bits so cast the Long to i32 with `as` first (in function main)"
+        );
+        assert_eq!(
+            error("fn main() { let a = 6; let b = a & 3; }"),
+            "bitwise & is not supported, scores have no command for it (in function main)"
+        );
+        assert_eq!(
+            error("fn main(s: string) { let t = s + \"!\"; }"),
+            "strings can only be joined when both are known at compile time (in function main)"
+        );
+        compiler::check::check(&[parse(
+            "const A = \"a\"; fn main() { let t = A + \"b\"; let l: i64 = 1; let same = l == l; }",
+        )])
+        .unwrap();
+    }
+    #[test]
+    fn generic_types() {
+        let program = parse(
+            "fn f(names: Dict<string, List<i8>>, total: List<i32>) -> List<List<f32>> { return []; }",
+        );
+        let Declaration::FunctionDec {
+            parameters,
+            return_type,
+            ..
+        } = &program.0[0]
+        else {
+            panic!("expected a function");
+        };
+        let list = |element: EFSType| EFSType::List(Box::new(element));
+        assert_eq!(
+            parameters,
+            &[
+                (
+                    String::from("names"),
+                    EFSType::Dict(Box::new(list(EFSType::Byte)))
+                ),
+                (String::from("total"), list(EFSType::Int)),
+            ]
+        );
+        assert_eq!(return_type, &list(list(EFSType::Float)));
+        assert!(Parser::new(String::from("fn f(d: Dict<i32, i32>) {}"))
+            .unwrap()
+            .parse::<Program>()
+            .is_err());
+
+        let source = "
+            fn main() {
+                let small: List<i8> = [1, 300];
+                let scores: Dict<string, i32> = { a: 1, b: \"two\" };
+                let mixed = { id: \"apple\", count: 2 };
+                let nested: List<List<i32>> = [[1], [2.5]];
+                for (item in small) {
+                    let wide: i64 = item;
+                    let text: string = item;
+                }
+            }
+        ";
+        assert_type_errors(
+            source,
+            &[
+                (
+                    at(source, "let small"),
+                    "300 overflows Byte, use `as` to convert it (in function main)",
+                ),
+                (
+                    at(source, "let scores"),
+                    "expected Int, found String (in function main)",
+                ),
+                (
+                    at(source, "let nested"),
+                    "expected Int, found Double, use `as` to convert it (in function main)",
+                ),
+                (
+                    at(source, "let text"),
+                    "expected String, found Byte (in function main)",
+                ),
+            ],
+        );
+
+        let program = parse(
+            "
+            fn main() {
+                let x: i8 = 5;
+                let scores: Dict<string, i64> = { a: x, b: 2 };
+                let levels: List<i16> = [x, 3];
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v0 test.vars 5",
+                "data modify storage test:vars v0 set value {}",
+                "execute store result storage test:vars v0.a long 1 run scoreboard players get $v0 test.vars",
+                "data modify storage test:vars v0.b set value 2L",
+                "data modify storage test:vars v1 set value []",
+                "data modify storage test:vars v1 append value 0s",
+                "execute store result storage test:vars v1[-1] short 1 run scoreboard players get $v0 test.vars",
+                "data modify storage test:vars v1 append value 3s",
+            ]
+        );
+    }
+    #[test]
+    fn list_access() {
+        let program = parse(
+            "
+            struct Point { x: i32, y: i32 }
+            const PRIMES: List<i32> = [2, 3, 5];
+
+            fn main(i: i32) {
+                let levels: List<i32> = [1];
+                levels.push(i);
+                levels.push(7);
+                let count = levels.len();
+                let first = levels[0];
+                let picked = levels[i];
+                let known = PRIMES[-1] + PRIMES.len();
+                let p = Point { x: 1, y: 2 };
+                p.x = picked;
+                p.y += 3;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players operation $v0 test.vars = $arg0 test.vars",
+                "data modify storage test:vars v0 set value []",
+                "data modify storage test:vars v0 append value 1",
+                "data modify storage test:vars v0 append value 0",
+                "execute store result storage test:vars v0[-1] int 1 run scoreboard players get $v0 test.vars",
+                "data modify storage test:vars v0 append value 7",
+                "execute store result score $v2 test.vars run data get storage test:vars v0",
+                "scoreboard players operation $v1 test.vars = $v2 test.vars",
+                "execute store result score $v3 test.vars run data get storage test:vars v0[0] 1",
+                "execute store result storage test:vars extern.index int 1 run scoreboard players get $v0 test.vars",
+                "function test:_efs/main/index0 with storage test:vars extern",
+                "execute store result score $v4 test.vars run data get storage test:vars v1 1",
+                "scoreboard players set $v5 test.vars 8",
+                "data modify storage test:vars v2 set value {}",
+                "data modify storage test:vars v2.x set value 1",
+                "data modify storage test:vars v2.y set value 2",
+                "execute store result storage test:vars v2.x int 1 run scoreboard players get $v4 test.vars",
+                "execute store result score $v6 test.vars run data get storage test:vars v2.y 1",
+                "scoreboard players add $v6 test.vars 3",
+                "execute store result storage test:vars v2.y int 1 run scoreboard players get $v6 test.vars",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/index0"],
+            ["$data modify storage test:vars v1 set from storage test:vars v0[$(index)]"]
+        );
+
+        let source = "
+            struct Point { x: i32, y: i32 }
+            const ORIGIN: Point = Point { x: 0, y: 0 };
+            const PRIMES: List<i32> = [2, 3, 5];
+
+            fn main(i: i32, points: List<Point>, name: string) {
+                points.push(1);
+                let a = name[0];
+                let b = PRIMES[3];
+                let c = points[true];
+                PRIMES.push(7);
+                ORIGIN.x = 1;
+                points[i].x = 2;
+                points[0].z = 3;
+            }
+        ";
+        assert_type_errors(
+            source,
+            &[
+                (
+                    at(source, "points.push"),
+                    "expected Struct(\"Point\"), found Number (in function main)",
+                ),
+                (
+                    at(source, "let a"),
+                    "cannot index String (in function main)",
+                ),
+                (
+                    at(source, "let b"),
+                    "index 3 is out of range for a list of 3 (in function main)",
+                ),
+                (
+                    at(source, "let c"),
+                    "a list index must be an integer, found Bool (in function main)",
+                ),
+                (
+                    at(source, "PRIMES.push"),
+                    "cannot assign to constant PRIMES (in function main)",
+                ),
+                (
+                    at(source, "ORIGIN.x"),
+                    "cannot assign to constant ORIGIN (in function main)",
+                ),
+                (
+                    at(source, "points[i]"),
+                    "list elements can only be assigned at an index known at compile time (in function main)",
+                ),
+                (
+                    at(source, "points[0].z"),
+                    "Struct(\"Point\") has no field z (in function main)",
+                ),
+            ],
+        );
+    }
+    #[test]
+    fn optionals() {
+        let program = parse(
+            "
+            struct Player { name: string, level: i32, guild: string? }
+
+            fn find(id: i32) -> Option<Player> {
+                if (id == 0) {
+                    return None;
+                }
+                return Player { name: \"Steve\", level: id, guild: None };
+            }
+
+            fn main() {
+                let best: i32? = None;
+                best = 5;
+                if (let value = best) {
+                    let doubled = value * 2;
+                }
+                let player = find(1);
+                let level = player?.level;
+                let guild = player?.guild;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/find/if0"],
+            [
+                "data remove storage test:vars frame.ret",
+                "scoreboard players set $control test.vars 1",
+                "return 0",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v3 test.vars -2147483648",
+                "scoreboard players set $v3 test.vars 5",
+                "execute unless score $v3 test.vars matches -2147483648 run scoreboard players operation $v4 test.vars = $v3 test.vars",
+                "execute unless score $v3 test.vars matches -2147483648 run function test:_efs/main/if0",
+                "scoreboard players set $arg0 test.vars 1",
+                "function test:_efs/fn/find",
+                "data remove storage test:vars v1",
+                "execute if data storage test:vars frame.ret run data modify storage test:vars v1 set from storage test:vars frame.ret",
+                "data remove storage test:vars v0",
+                "execute if data storage test:vars v1 run data modify storage test:vars v0 set from storage test:vars v1",
+                "scoreboard players set $v9 test.vars -2147483648",
+                "execute if data storage test:vars v0.level run execute store result score $v9 test.vars run data get storage test:vars v0.level 1",
+                "scoreboard players operation $v8 test.vars = $v9 test.vars",
+                "data remove storage test:vars v3",
+                "execute if data storage test:vars v0.guild run data modify storage test:vars v3 set from storage test:vars v0.guild",
+                "data remove storage test:vars v2",
+                "execute if data storage test:vars v3 run data modify storage test:vars v2 set from storage test:vars v3",
+            ]
+        );
+
+        let source = "
+            struct Point { x: i32, y: i32 }
+
+            fn main() {
+                let x: i32? = 1;
+                let sum = x + 1;
+                let y: i32 = x;
+                let origin: Point? = None;
+                let z = origin.x;
+                if (let v = y) {}
+            }
+        ";
+        assert_type_errors(
+            source,
+            &[
+                (
+                    at(source, "let sum"),
+                    "cannot use Plus on Option(Int) and Number (in function main)"
+                ),
+                (
+                    at(source, "let y"),
+                    "expected Int, found Option(Int), unwrap it with `if let` first (in function main)"
+                ),
+                (
+                    at(source, "let z"),
+                    "Option(Struct(\"Point\")) may be None, use `?.` to access x (in function main)"
+                ),
+                (
+                    at(source, "if (let"),
+                    "if let needs an optional value, found Int (in function main)"
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let program = parse(
+            "
+            enum Mode { Idle, Running, Done }
+            enum Reward { Nothing, Points(i32), Item(string, i8) }
+
+            fn main() {
+                let mode = Mode::Running;
+                let reward = Reward::Item(\"diamond\", 3);
+                match (mode) {
+                    Mode::Idle => {}
+                    _ => {
+                        mode = Mode::Done;
+                    }
+                }
+                match (reward) {
+                    Reward::Points(points) => {
+                        let doubled = points * 2;
+                    }
+                    Reward::Item(name, _) => {}
+                    Reward::Nothing => {}
+                }
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v0 test.vars 1",
+                "data modify storage test:vars v0 set value {variant:2}",
+                "data modify storage test:vars v0._0 set value \"diamond\"",
+                "data modify storage test:vars v0._1 set value 3b",
+                "scoreboard players operation $v1 test.vars = $v0 test.vars",
+                "execute if score $v1 test.vars matches 0 run function test:_efs/main/match0",
+                "execute if score $v1 test.vars matches 1..2 run function test:_efs/main/match1",
+                "execute store result score $v2 test.vars run data get storage test:vars v0.variant 1",
+                "execute if score $v2 test.vars matches 1 run execute store result score $v3 test.vars run data get storage test:vars v0._0 1",
+                "execute if score $v2 test.vars matches 1 run function test:_efs/main/match2",
+                "execute if score $v2 test.vars matches 2 run data modify storage test:vars v1 set from storage test:vars v0._0",
+                "execute if score $v2 test.vars matches 2 run function test:_efs/main/match3",
+                "execute if score $v2 test.vars matches 0 run function test:_efs/main/match4",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/match1"],
+            ["scoreboard players set $v0 test.vars 2"]
+        );
+
+        let source = "
+            enum Mode { Idle, Running, Done }
+            enum Reward { Nothing, Points(i32) }
+
+            fn main() {
+                let mode = Mode::Idle;
+                match (mode) {
+                    Mode::Idle => {}
+                    Mode::Running => {}
+                }
+                let reward = Reward::Points();
+                match (reward) {
+                    _ => {}
+                    Reward::Nothing => {}
+                }
+            }
+        ";
+        assert_type_errors(
+            source,
+            &[
+                (
+                    at(source, "match (mode)"),
+                    "match on Mode is missing Mode::Done (in function main)",
+                ),
+                (
+                    at(source, "let reward"),
+                    "Reward::Points takes 1 values but 0 were given (in function main)",
+                ),
+                (
+                    at(source, "match (reward)"),
+                    "unreachable pattern Reward::Nothing (in function main)",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn methods() {
+        let program = parse(
+            "
+            struct Counter { count: i32, step: i32 }
+
+            impl Counter {
+                fn new(step: i32) -> Counter {
+                    return Counter { count: 0, step: step };
+                }
+
+                fn next(self) -> i32 {
+                    return self.count + self.step;
+                }
+
+                fn bump(self) {
+                    self.count += self.step;
+                }
+            }
+
+            fn main() {
+                let counter = Counter::new(2);
+                let value = counter.next();
+                counter.bump();
+            }
+            ",
+        );
+        let Declaration::Impl(name, functions) = &program.0[1] else {
+            panic!("expected an impl block");
+        };
+        assert_eq!(name, "Counter");
+        assert_eq!(functions.len(), 3);
+
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/counter/next"],
+            [
+                "data modify storage test:vars v0 set from storage test:vars frame.arg0",
+                "execute store result score $v2 test.vars run data get storage test:vars v0.count 1",
+                "scoreboard players operation $v1 test.vars = $v2 test.vars",
+                "execute store result score $v3 test.vars run data get storage test:vars v0.step 1",
+                "scoreboard players operation $v1 test.vars += $v3 test.vars",
+                "scoreboard players operation $ret test.vars = $v1 test.vars",
+                "return 0",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $arg0 test.vars 2",
+                "function test:_efs/fn/counter/new",
+                "data modify storage test:vars v3 set from storage test:vars frame.ret",
+                "data modify storage test:vars v2 set from storage test:vars v3",
+                "data modify storage test:vars frame.arg0 set from storage test:vars v2",
+                "function test:_efs/fn/counter/next",
+                "scoreboard players operation $v7 test.vars = $ret test.vars",
+                "scoreboard players operation $v6 test.vars = $v7 test.vars",
+                "data modify storage test:vars frame.arg0 set from storage test:vars v2",
+                "function test:_efs/fn/counter/bump",
+                "data modify storage test:vars v2 set from storage test:vars frame.self",
+            ]
+        );
+        // `bump` changes `self`, so it hands it back after its body.
+        assert_eq!(
+            datapack.functions["test:_efs/fn/counter/bump"],
+            [
+                "function test:_efs/counter/bump/body",
+                "data modify storage test:vars frame.self set from storage test:vars v1",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/counter/bump/body"].last().unwrap(),
+            "execute store result storage test:vars v1.count int 1 run scoreboard players get $v4 test.vars"
+        );
+        assert!(!datapack
+            .functions
+            .contains_key("test:_efs/counter/next/body"));
+
+        let source = "
+            struct Counter { count: i32 }
+
+            impl Counter {
+                fn new() -> Counter {
+                    return Counter { count: 0 };
+                }
+
+                fn add(self, amount: i32) {}
+            }
+
+            impl Missing {
+                fn nothing() {}
+            }
+
+            fn main() {
+                let counter = Counter::new();
+                counter.add();
+                counter.reset();
+                counter.new();
+                let maybe: Counter? = None;
+                maybe.add(1);
+            }
+        ";
+        assert_type_errors(
+            source,
+            &[
+                (
+                    at(source, "fn nothing() {}") + "fn nothing() {".len(),
+                    "impl for unknown type Missing"
+                ),
+                (
+                    at(source, "counter.add"),
+                    "method add takes 1 arguments but 0 were given (in function main)"
+                ),
+                (
+                    at(source, "counter.reset"),
+                    "Struct(\"Counter\") has no method reset (in function main)"
+                ),
+                (
+                    at(source, "counter.new"),
+                    "Counter::new is not a method, call it as Counter::new() (in function main)"
+                ),
+                (
+                    at(source, "maybe.add"),
+                    "Option(Struct(\"Counter\")) may be None, unwrap it with `if let` to call add (in function main)"
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn modules() {
+        let math = || {
+            module(
+                "util::math",
+                "
+                pub const SCALE = 10;
+                pub const PRIMES: List<i32> = [2, 3];
+
+                pub fn scale(x: i32) -> i32 {
+                    return helper(x) * SCALE;
+                }
+
+                fn helper(x: i32) -> i32 {
+                    return x;
+                }
+                ",
+            )
+        };
+        let geo = || {
+            module(
+                "geo",
+                "
+                pub struct Point { x: i32, y: i32 }
+
+                impl Point {
+                    pub fn origin() -> Point {
+                        return Point { x: 0, y: 0 };
+                    }
+
+                    fn secret() {}
+                }
+                ",
+            )
+        };
+        assert_eq!(
+            Module::name_of(Path::new("pack/src"), Path::new("pack/src/util/math.efs")).unwrap(),
+            "util::math"
+        );
+
+        let main = module(
+            "main",
+            "
+            use \"geo\";
+            use util::math;
+            use util::math::SCALE;
+
+            fn main() {
+                let point: Point = Point::origin();
+                let scaled = math::scale(point.x);
+                let scale = SCALE;
+            }
+            ",
+        );
+        let programs = modules::link(&[main, math(), geo()]).unwrap();
+        let datapack = compiler::compile("test", &programs).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main/main"][..5],
+            [
+                "function test:geo/point/origin",
+                "data modify storage test:vars v1 set from storage test:vars frame.ret",
+                "data modify storage test:vars v0 set from storage test:vars v1",
+                "execute store result score $arg0 test.vars run data get storage test:vars v0.x 1",
+                "function test:util/math/scale",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main/main"].last().unwrap(),
+            "scoreboard players set $v2 test.vars 10"
+        );
+        assert!(datapack.functions["test:util/math/scale"]
+            .contains(&String::from("function test:_efs/fn/util/math/helper")));
+
+        let main = module(
+            "main",
+            "
+            use util::math::PRIMES;
+
+            fn main() {
+                for (prime in PRIMES) {}
+            }
+            ",
+        );
+        let programs = modules::link(&[main, math(), geo()]).unwrap();
+        let datapack = compiler::compile("test", &programs).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main/main"][0],
+            "data modify storage test:vars v0 set value [2,3]"
+        );
+
+        let link = |source: &str| {
+            let main = module("main", source);
+            format!("{:#}", modules::link(&[main, math(), geo()]).unwrap_err())
+        };
+        assert_eq!(
+            link("use util::math::helper;"),
+            "in module main: helper is private to module util::math"
+        );
+        assert_eq!(
+            link("use \"missing\";"),
+            "in module main: cannot find missing"
+        );
+        assert_eq!(
+            link("fn main() { geo::Point::secret(); }"),
+            "in module main: in function main: Point::secret is private to module geo"
+        );
+        assert_eq!(
+            link("use util::nothing;"),
+            "in module main: unknown module util::nothing"
+        );
+
+        let a = module("a", "use \"b\";");
+        let b = module("b", "use \"a.efs\";");
+        assert_eq!(
+            modules::link(&[a, b]).unwrap_err().to_string(),
+            "import cycle: a -> b -> a"
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        let source = "
+            const LIMIT = 10;
+
+            struct Point { x: i32, y: i32 }
+
+            fn scale(point: Point, by: i32) -> i32 {
+                let result = point.x * by;
+                if (result > LIMIT) {
+                    let result = LIMIT;
+                    return result;
+                }
+                return result;
+            }
+
+            fn main() {
+                let point = Point { x: 1, y: 2 };
+                let LIMIT = scale(point, 2);
+            }
+        ";
+        let table = SymbolTable::build(&[parse(source)]);
+        assert!(table.errors.is_empty());
+
+        let result = table
+            .symbols
+            .iter()
+            .position(|symbol| symbol.name == "result")
+            .unwrap();
+        assert_eq!(
+            table.symbols[result],
+            Symbol {
+                name: String::from("result"),
+                kind: SymbolKind::Local,
+                function: Some(String::from("scale")),
+                at: Some(at(source, "result = point")),
+            }
+        );
+        assert_eq!(
+            table.uses(result),
+            [
+                at(source, "result > LIMIT"),
+                at(source, "result;\n            }")
+            ]
+        );
+        let point = table
+            .symbols
+            .iter()
+            .position(|symbol| symbol.name == "point")
+            .unwrap();
+        assert_eq!(
+            table.symbols[point],
+            Symbol {
+                name: String::from("point"),
+                kind: SymbolKind::Parameter,
+                function: Some(String::from("scale")),
+                at: Some(at(source, "point: Point")),
+            }
+        );
+        assert_eq!(table.uses(point), [at(source, "point.x")]);
+        let limit = table.global("LIMIT").unwrap();
+        assert_eq!(table.symbols[limit].at, Some(at(source, "LIMIT = 10")));
+        assert_eq!(
+            table.uses(limit),
+            [at(source, "LIMIT) {"), at(source, "LIMIT;\n")]
+        );
+        let point = table.global("Point").unwrap();
+        assert_eq!(table.symbols[point].at, Some(at(source, "Point { x: i32")));
+        assert_eq!(table.uses(point), [at(source, "Point { x: 1")]);
+        let scale = table.symbol_at(at(source, "scale(point, 2)")).unwrap();
+        assert_eq!(
+            table.symbols[scale].at,
+            Some(at(source, "scale(point: Point"))
+        );
+
+        let warnings = table
+            .warnings
+            .iter()
+            .map(|warning| (warning.at, warning.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                (
+                    at(source, "let result = LIMIT"),
+                    "result shadows a variable of the same name (in function scale)"
+                ),
+                (
+                    at(source, "let LIMIT"),
+                    "LIMIT shadows a constant of the same name (in function main)"
+                ),
+            ]
+        );
+
+        let source = "
+            fn twice(a: i32, a: i32) {}
+
+            fn twice() {
+                let x = 1;
+                let x = missing;
+                undefined(x);
+                let shapes: List<Shape> = [];
+            }
+        ";
+        let end = |text: &str| at(source, text) + text.len() - 1;
+        assert_type_errors(
+            source,
+            &[
+                (
+                    end("fn twice(a: i32, a: i32) {}"),
+                    "parameter a is defined more than once (in function twice)",
+                ),
+                (
+                    at(source, "let x = missing"),
+                    "unknown variable missing (in function twice)",
+                ),
+                (
+                    at(source, "let x = missing"),
+                    "x is already defined in this block (in function twice)",
+                ),
+                (
+                    at(source, "undefined(x)"),
+                    "unknown function undefined (in function twice)",
+                ),
+                (
+                    at(source, "let shapes"),
+                    "unknown type Shape (in function twice)",
+                ),
+                (
+                    source.rfind('}').unwrap(),
+                    "function twice is defined more than once",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn resource_locations() {
+        let program = parse(
+            "
+            struct MyPoint { x: i32 }
+
+            impl MyPoint {
+                pub fn makeOrigin() -> MyPoint {
+                    return MyPoint { x: 0 };
+                }
+
+                fn secret() {}
+            }
+
+            #[export(\"other:api/run\")]
+            fn run(times: i32) {
+                if (times > 0) {
+                    MyPoint::makeOrigin();
+                }
+            }
+
+            #[export(\"api/stop\")]
+            fn stop() {}
+
+            pub fn main() {
+                run(2);
+                helper();
+            }
+
+            fn helper() {}
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions.keys().collect::<Vec<_>>(),
+            [
+                "other:api/run",
+                "test:_efs/fn/helper",
+                "test:_efs/fn/my_point/secret",
+                "test:_efs/load",
+                "test:_efs/run/if0",
+                "test:api/stop",
+                "test:main",
+                "test:my_point/make_origin",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:main"][1..],
+            ["function other:api/run", "function test:_efs/fn/helper"]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/run/if0"][0],
+            "function test:my_point/make_origin"
+        );
+        assert_eq!(
+            compiler::function_path("util::HTTPServer::new"),
+            "util/http_server/new"
+        );
+        assert_eq!(
+            compiler::function_path("parseJSONValue"),
+            "parse_json_value"
+        );
+
+        assert_eq!(
+            compile_error("#[export(\"_efs/load\")] fn setup() {}"),
+            "function setup can not compile to test:_efs/load, _efs is reserved for the compiler"
+        );
+        assert_eq!(
+            compile_error("fn myName() {} fn my_name() {}"),
+            "functions myName and my_name both compile to test:_efs/fn/my_name"
+        );
+        assert_eq!(
+            compile_error("#[export(\"Bad Name\")] fn run() {}"),
+            "test:Bad Name is not a valid resource location"
+        );
+
+        let module = module("main", "#[export(\"api/run\")] fn run() {}");
+        assert_eq!(
+            format!("{:#}", modules::link(&[module]).unwrap_err()),
+            "in module main: #[export] on function run needs it to be pub"
+        );
+    }
+
+    #[test]
+    fn externs() {
+        let program = parse(
+            "
+            extern fn heal(amount: i32, target: string) = \"lib:health/heal\";
+            extern fn roll() -> i32 = \"lib:dice/roll\";
+            extern fn reset() = \"lib:reset\";
+            extern score mana: i32 = \"@s lib.mana\";
+            extern storage settings: Dict<string, i32> = \"lib:data settings\";
+
+            fn main() {
+                heal(roll(), \"@a\");
+                reset();
+                mana = mana + 5;
+                let limit = settings.max;
+            }
+            ",
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "execute store result score $ret test.vars run function lib:dice/roll",
+                "scoreboard players operation $v0 test.vars = $ret test.vars",
+                "data modify storage test:vars v0 set value \"@a\"",
+                "execute store result storage test:vars extern.amount int 1 run scoreboard players get $v0 test.vars",
+                "data modify storage test:vars extern.target set from storage test:vars v0",
+                "function lib:health/heal with storage test:vars extern",
+                "function lib:reset",
+                "scoreboard players operation $v1 test.vars = @s lib.mana",
+                "scoreboard players add $v1 test.vars 5",
+                "scoreboard players operation @s lib.mana = $v1 test.vars",
+                "execute store result score $v2 test.vars run data get storage lib:data settings.max 1",
+            ]
+        );
+
+        assert_eq!(
+            compile_error("extern fn heal(amount: i32) = \"lib:heal\"; fn main() { heal(\"a\"); }"),
+            "type error at 54: expected Int, found String (in function main)"
+        );
+        assert_eq!(
+            compile_error("extern score name: string = \"@s lib.name\";"),
+            "extern score name must have a score type, found String"
+        );
+        assert_eq!(
+            compile_error("extern storage data: i32 = \"lib:data\";"),
+            "the location of extern data must be two words, found \"lib:data\""
+        );
+        assert_eq!(
+            compile_error("extern fn name() -> string = \"lib:name\";"),
+            "extern function name can only return a score type, found String"
+        );
+        assert_eq!(
+            compile_error("extern fn run() = \"Lib:Run\";"),
+            "Lib:Run is not a valid resource location"
+        );
+    }
+
+    #[test]
+    fn raw_commands() {
+        let program = parse(
+            r#"
+            const LIMIT = 10;
+
+            struct Point { x: i32, y: i32 }
+
+            fn main() {
+                let count = 3;
+                let point = Point { x: 1, y: 2 };
+                cmd "scoreboard players add $(count) 1";
+                mc! {
+                    # comments and empty lines are skipped
+
+                    execute if score $(count) matches $(LIMIT).. run say it's done
+                    data modify $(point.x) set value 5
+                    tellraw @a {"text":"}"}
+                }
+                static for i in 0..2 {
+                    cmd "say $(i)";
+                }
+            }
+            "#,
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"][4..],
+            [
+                "scoreboard players add $v0 test.vars 1",
+                "execute if score $v0 test.vars matches 10.. run say it's done",
+                "data modify storage test:vars v0.x set value 5",
+                "tellraw @a {\"text\":\"}\"}",
+                "say 0",
+                "say 1",
+            ]
+        );
+
+        let error =
+            |source: &str| compile_error(&format!("fn main() {{ let count = 1; {} }}", source));
+        assert_eq!(
+            error(r#"cmd "sya hi";"#),
+            "type error at 27: unknown command sya (in function main)"
+        );
+        assert_eq!(
+            error(r#"cmd "say $(missing)";"#),
+            "type error at 27: unknown variable missing (in function main)"
+        );
+        assert_eq!(
+            error(r#"cmd "say $(count";"#),
+            "type error at 27: unclosed $( in command say $(count (in function main)"
+        );
+        assert_eq!(
+            error(r#"cmd "say $(count.x)";"#),
+            "type error at 27: Number has no field x (in function main)"
+        );
+    }
+
+    #[test]
+    fn standard_library() {
+        let compile = |source: &str| {
+            modules::link(&[module("main", source)])
+                .and_then(|programs| compiler::compile("test", &programs))
+        };
+        let datapack = compile(
+            r#"
+            use std::cmd;
+            use std::cmd::say;
+
+            const PLAYERS = "@a[tag=player]";
+
+            fn main() {
+                say("hello world");
+                cmd::tellraw(PLAYERS, "it's {on}");
+                cmd::give("@s", "diamond", 2 * 32);
+                cmd::effect("@s", "minecraft:speed", 10, 1);
+                cmd::playsound("entity.player.levelup", "master", "@s");
+                cmd::fill("~-1 ~ ~-1", "~1 ~2 ~1", "air");
+                cmd::tp("Steve", "^ ^ ^5");
+                std::cmd::tag("@e[type=pig]", "marked");
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main/main"],
+            [
+                "say hello world",
+                r#"tellraw @a[tag=player] "it's {on}""#,
+                "give @s diamond 64",
+                "effect give @s minecraft:speed 10 1",
+                "playsound entity.player.levelup master @s",
+                "fill ~-1 ~ ~-1 ~1 ~2 ~1 air",
+                "tp Steve ^ ^ ^5",
+                "tag @e[type=pig] add marked",
+            ]
+        );
+
+        let error = |source: &str| {
+            let source = format!("use std::cmd; fn main() {{ let x = 1; {} }}", source);
+            format!("{:#}", compile(&source).err().unwrap())
+        };
+        let datapack = compile(
+            r#"
+            use std::cmd;
+
+            fn reward(n: i32, item: string, message: string) {
+                cmd::give(@s, "diamond", n);
+                cmd::give(@s, item, n * 2);
+                cmd::tellraw(@a, message);
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main/reward"][3..],
+            [
+                "execute store result storage test:vars extern.count int 1 run scoreboard players get $v0 test.vars",
+                "function test:_efs/main/reward/give0 with storage test:vars extern",
+                "scoreboard players operation $v1 test.vars = $v0 test.vars",
+                "scoreboard players set $v2 test.vars 2",
+                "scoreboard players operation $v1 test.vars *= $v2 test.vars",
+                "data modify storage test:vars extern.item set from storage test:vars v0",
+                "execute store result storage test:vars extern.count int 1 run scoreboard players get $v1 test.vars",
+                "function test:_efs/main/reward/give1 with storage test:vars extern",
+                r#"tellraw @a ["",{"nbt":"v1","storage":"test:vars"}]"#,
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/reward/give0"],
+            ["$give @s diamond $(count)"]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/reward/give1"],
+            ["$give @s $(item) $(count)"]
+        );
+        assert_eq!(
+            error(r#"std::cmd::kill("@q");"#),
+            "in function main::main: argument targets of std::cmd::kill: @q is not a selector or player name"
+        );
+        assert_eq!(
+            error(r#"std::cmd::tp("@s", "~ ^ 1");"#),
+            "in function main::main: argument pos of std::cmd::tp: ~ ^ 1 is not a position"
+        );
+        assert_eq!(
+            error(r#"std::cmd::setblock("~ ~ ~", "Stone");"#),
+            "in function main::main: argument block of std::cmd::setblock: Stone is not a valid resource location"
+        );
+        assert_eq!(
+            error(r#"std::cmd::give("@s", "stone", "one");"#),
+            "type error at 37: expected Int, found String (in function main::main)"
+        );
+        assert_eq!(
+            error(r#"cmd::fly();"#),
+            "in module main: in function main: module std::cmd has no item fly"
+        );
+        assert_eq!(
+            modules::link(&[module("std::cmd", "pub fn say() {}")])
+                .err()
+                .unwrap()
+                .to_string(),
+            "module std::cmd can not be defined, std is the standard library"
+        );
+    }
+
+    #[test]
+    fn selectors() {
+        let program = parse(
+            r#"
+            const PIGS: Selector = @e.type("pig").limit(2);
+
+            fn main() {
+                as @a[tag=x] at @s {
+                    std::cmd::say("hi");
+                }
+                positioned ~ ~1 ~ {
+                    std::cmd::kill(PIGS);
+                }
+                as "Steve" positioned ^ ^ ^-1.5 {
+                    std::cmd::setblock("~ ~ ~", "stone");
+                }
+                in minecraft:the_nether {
+                    std::cmd::kill(@e[type=item].sort("nearest"));
+                }
+                in the_end {}
+                static for i in 0..2 {
+                    as @e[tag=team{i}] {}
+                }
+            }
+            "#,
+        );
+        let datapack = compiler::compile("test", &[program]).unwrap();
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "execute as @a[tag=x] at @s run function test:_efs/main/as0",
+                "execute positioned ~ ~1 ~ run function test:_efs/main/positioned1",
+                "execute as Steve positioned ^ ^ ^-1.5 run function test:_efs/main/as2",
+                "execute in minecraft:the_nether run function test:_efs/main/in3",
+                "execute in minecraft:the_end run function test:_efs/main/in4",
+                "execute as @e[tag=team0] run function test:_efs/main/as5",
+                "execute as @e[tag=team1] run function test:_efs/main/as6",
+            ]
+        );
+        assert_eq!(datapack.functions["test:_efs/main/as0"], ["say hi"]);
+        assert_eq!(
+            datapack.functions["test:_efs/main/positioned1"],
+            ["kill @e[type=pig,limit=2]"]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/in3"],
+            ["kill @e[type=item,sort=nearest]"]
+        );
+
+        let error =
+            |source: &str| compile_error(&format!("fn main() {{ let count = 1; {} }}", source));
+        assert_eq!(
+            error("as @a { return; }"),
+            "type error at 35: return can not leave an as or at block, it runs once per entity (in function main)"
+        );
+        assert_eq!(
+            error("while (true) { as @s { break; } }"),
+            "type error at 50: break outside of a loop (in function main)"
+        );
+        assert_eq!(
+            error("positioned ~ ^ 1 { }"),
+            "in function main: ~ ^ 1 is not a position"
+        );
+        assert_eq!(
+            error(r#"let s = "@a"; as s { }"#),
+            "type error at 41: the selector of as has to be known at compile time (in function main)"
+        );
+        assert_eq!(
+            error("let s: Selector = @a; at s { }"),
+            "type error at 49: the selector of at has to be known at compile time (in function main)"
+        );
+        assert_eq!(
+            compile_error("fn heal(target: Selector) { as target { } }"),
+            "type error at 28: the selector of as has to be known at compile time (in function heal)"
+        );
+        assert_eq!(
+            error("as @e.limit(0) { }"),
+            "in function main: the limit of a selector must be positive, found 0"
+        );
+        assert_eq!(
+            error(r#"as @a.color("red") { }"#),
+            "type error at 27: Selector has no method color (in function main)"
+        );
+        assert_eq!(
+            error("as count { }"),
+            "type error at 27: expected Selector, found Number (in function main)"
+        );
+        assert_eq!(
+            error("in Nether { }"),
+            "in function main: Nether is not a valid dimension"
+        );
+    }
+
+    #[test]
+    fn entity_scores() {
+        let program = parse(
+            r#"
+            score kills: i32 on entity;
+            score alive: bool on entity;
+
+            const TOP: Selector = @p.tag("top");
+
+            fn main() {
+                let bonus = 2;
+                as @a[tag=player] {
+                    @s.kills += 1;
+                    @s.kills *= bonus;
+                    @s.alive = true;
+                    let total = @s.kills + TOP.kills;
+                }
+                @a.kills = 0;
+                TOP.kills -= 3;
+                bonus += 1;
+            }
+            "#,
+        );
+        let mut compiler = compiler::Compiler::new("test");
+        compiler.uninstall = true;
+        compiler.compile(&[program]).unwrap();
+        let datapack = compiler.datapack;
+        assert_eq!(
+            datapack.functions["test:_efs/load"][..3],
+            [
+                "scoreboard objectives add test.vars dummy",
+                "scoreboard objectives add test.alive dummy",
+                "scoreboard objectives add test.kills dummy",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/uninstall"][1..3],
+            [
+                "scoreboard objectives remove test.alive",
+                "scoreboard objectives remove test.kills",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v0 test.vars 2",
+                "execute as @a[tag=player] run function test:_efs/main/as0",
+                "scoreboard players set @a test.kills 0",
+                "scoreboard players remove @p[tag=top] test.kills 3",
+                "scoreboard players operation $v3 test.vars = $v0 test.vars",
+                "scoreboard players add $v3 test.vars 1",
+                "scoreboard players operation $v0 test.vars = $v3 test.vars",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/as0"],
+            [
+                "scoreboard players add @s test.kills 1",
+                "scoreboard players operation @s test.kills *= $v0 test.vars",
+                "scoreboard players set @s test.alive 1",
+                "scoreboard players operation $v2 test.vars = @s test.kills",
+                "scoreboard players operation $v2 test.vars += @p[tag=top] test.kills",
+                "scoreboard players operation $v1 test.vars = $v2 test.vars",
+            ]
+        );
+
+        let error = |source: &str| {
+            compile_error(&format!(
+                "score kills: i32 on entity; fn main() {{ {} }}",
+                source
+            ))
+        };
+        assert_eq!(
+            error("@s.kills += 1;"),
+            "type error at 40: @s.kills is only known inside an as block, where @s is an entity (in function main)"
+        );
+        assert_eq!(
+            error("as @a { @s.deaths = 1; }"),
+            "type error at 48: entities have no score deaths, declare it with `score deaths: i32 on entity;` (in function main)"
+        );
+        assert_eq!(
+            error("let x = 1; x.kills = 1;"),
+            "type error at 51: Number has no field kills (in function main)"
+        );
+        assert_eq!(
+            error("let x = @a.kills;"),
+            "type error at 40: cannot read kills of @a, it can select more than one entity (in function main)"
+        );
+        assert_eq!(
+            error("let x = @p[limit=2].kills;"),
+            "type error at 40: cannot read kills of @p[limit=2], it can select more than one entity (in function main)"
+        );
+        assert_eq!(
+            error("let p = @p; let x = p.kills;"),
+            "type error at 52: the selector of score kills has to be known at compile time (in function main)"
+        );
+        assert_eq!(
+            compile_error("score kills: i32 on entity; fn best(p: Selector) -> i32 { return p.kills; }"),
+            "type error at 58: the selector of score kills has to be known at compile time (in function best)"
+        );
+        let program = parse(
+            "score kills: i32 on entity; fn main() { let x = @e.limit(1).kills + @r.kills; @a.kills = x; }",
+        );
+        assert!(compiler::compile("test", &[program]).is_ok());
+        assert_eq!(
+            format!(
+                "{:#}",
+                compiler::compile("test", &[parse("score name: string on entity;")])
+                    .err()
+                    .unwrap()
+            ),
+            "score name must have a score type, found String"
+        );
+        assert!(Parser::new(String::from("score kills: i32 on player;"))
+            .unwrap()
+            .parse::<Program>()
+            .is_err());
+    }
+
+    #[test]
+    fn text_components() {
+        let program = parse(
+            r##"
+            score kills: i32 on entity;
+
+            const GREETING = text!("Hi ".color("gold").bold(), "there".italic());
+            const PLAIN: Text = "plain";
+
+            fn main() {
+                let x = 3;
+                let name = "Steve";
+                std::cmd::title(@a, GREETING);
+                std::cmd::subtitle(@a, PLAIN);
+                as @a {
+                    std::cmd::tellraw(@s, text!(
+                        GREETING,
+                        @s.kills,
+                        " ",
+                        x,
+                        name.color("#ff00aa"),
+                        @s.nbt("SelectedItem.id"),
+                        @p,
+                        "[click]".click("run_command", "/say hi").hover(text!("Say ", "hi".bold()))
+                    ));
+                }
+            }
+            "##,
+        );
+        let mut compiler = compiler::Compiler::new("test");
+        compiler.compile(&[program]).unwrap();
+        let datapack = compiler.datapack;
+        assert_eq!(
+            datapack.functions["test:_efs/fn/main"],
+            [
+                "scoreboard players set $v0 test.vars 3",
+                "data modify storage test:vars v0 set value \"Steve\"",
+                r#"title @a title ["",{"bold":true,"color":"gold","text":"Hi "},{"italic":true,"text":"there"}]"#,
+                r#"title @a subtitle "plain""#,
+                "execute as @a run function test:_efs/main/as0",
+            ]
+        );
+        assert_eq!(
+            datapack.functions["test:_efs/main/as0"],
+            [concat!(
+                r#"tellraw @s ["","#,
+                r#"{"extra":[["",{"bold":true,"color":"gold","text":"Hi "},{"italic":true,"text":"there"}]],"text":""},"#,
+                r#"{"score":{"name":"@s","objective":"test.kills"}},"#,
+                r#"{"text":" "},"#,
+                r#"{"score":{"name":"$v0","objective":"test.vars"}},"#,
+                r##"{"color":"#ff00aa","nbt":"v0","storage":"test:vars"},"##,
+                r#"{"entity":"@s","nbt":"SelectedItem.id"},"#,
+                r#"{"selector":"@p"},"#,
+                r#"{"clickEvent":{"action":"run_command","value":"/say hi"},"#,
+                r#""hoverEvent":{"action":"show_text","contents":["",{"text":"Say "},{"bold":true,"text":"hi"}]},"#,
+                r#""text":"[click]"}]"#,
+            )]
+        );
+
+        let error = |source: &str| {
+            compile_error(&format!(
+                "score kills: i32 on entity; fn main() {{ std::cmd::tellraw(@a, text!({})); }}",
+                source
+            ))
+        };
+        assert_eq!(
+            error(r#""a".color("pink")"#),
+            "in function main: pink is not a colour, expected #RRGGBB or one of black, dark_blue, dark_green, dark_aqua, dark_red, dark_purple, gold, gray, dark_gray, blue, green, aqua, red, light_purple, yellow, white"
+        );
+        assert_eq!(
+            error(r#""a".click("run_command", "say hi")"#),
+            "in function main: run_command needs a command starting with /, found say hi"
+        );
+        assert_eq!(
+            error(r#""a".click("open_url", "example.com")"#),
+            "in function main: open_url needs an http or https address, found example.com"
+        );
+        assert_eq!(
+            error(r#""a".bold(true)"#),
+            "type error at 40: style bold takes 0 arguments but 1 were given (in function main)"
+        );
+        assert_eq!(
+            error("@s.nbt(1)"),
+            "type error at 40: expected String, found Number (in function main)"
+        );
+        assert_eq!(
+            error(r#"std::cmd::say("x")"#),
+            "type error at 40: a part of text! has no value to show (in function main)"
+        );
+    }
 }
@@ -0,0 +1,341 @@
+//! Binary NBT as used by `.nbt` structure files and `level.dat`: big-endian,
+//! optionally gzip compressed, with strings in Java's modified UTF-8.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail};
+use flate2::{read::GzDecoder, write::GzEncoder};
+
+use crate::parser::types::EFSValueType;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+}
+
+/// Reads a named root tag, detecting gzip compression from the header.
+/// Compounds are read as `Dict`s.
+pub fn read(bytes: &[u8]) -> anyhow::Result<(String, EFSValueType)> {
+    let mut data = Vec::new();
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        GzDecoder::new(bytes).read_to_end(&mut data)?;
+    } else {
+        data.extend_from_slice(bytes);
+    }
+
+    let mut reader = Reader {
+        data: &data,
+        pos: 0,
+    };
+    let tag = reader.u8()?;
+    if tag == TAG_END {
+        bail!("nbt root tag is empty");
+    }
+    let name = reader.string()?;
+    let value = reader.payload(tag, 0)?;
+    Ok((name, value))
+}
+
+pub fn read_file(path: impl AsRef<Path>) -> anyhow::Result<(String, EFSValueType)> {
+    read(&fs::read(path)?)
+}
+
+/// Writes `value` as a root tag called `name`.
+pub fn write(
+    name: &str,
+    value: &EFSValueType,
+    compression: Compression,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    data.push(tag_id(value)?);
+    write_string(&mut data, name)?;
+    write_payload(&mut data, value)?;
+
+    match compression {
+        Compression::None => Ok(data),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+pub fn write_file(
+    path: impl AsRef<Path>,
+    name: &str,
+    value: &EFSValueType,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    fs::write(path, write(name, value, compression)?)?;
+    Ok(())
+}
+
+/// Nesting limit used by Minecraft, guards against stack overflows on bad input.
+const MAX_DEPTH: usize = 512;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| anyhow!("unexpected end of nbt data at byte {}", self.pos))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn length(&mut self) -> anyhow::Result<usize> {
+        let length = i32::from_be_bytes(self.take()?);
+        usize::try_from(length).map_err(|_| anyhow!("negative nbt length {}", length))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let length = u16::from_be_bytes(self.take()?) as usize;
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + length)
+            .ok_or_else(|| anyhow!("unexpected end of nbt data at byte {}", self.pos))?;
+        self.pos += length;
+        decode_mutf8(bytes)
+    }
+
+    fn payload(&mut self, tag: u8, depth: usize) -> anyhow::Result<EFSValueType> {
+        if depth > MAX_DEPTH {
+            bail!("nbt nested deeper than {}", MAX_DEPTH);
+        }
+
+        Ok(match tag {
+            TAG_BYTE => EFSValueType::Byte(i8::from_be_bytes(self.take()?)),
+            TAG_SHORT => EFSValueType::Short(i16::from_be_bytes(self.take()?)),
+            TAG_INT => EFSValueType::Int(i32::from_be_bytes(self.take()?)),
+            TAG_LONG => EFSValueType::Long(i64::from_be_bytes(self.take()?)),
+            TAG_FLOAT => EFSValueType::Float(f32::from_be_bytes(self.take()?)),
+            TAG_DOUBLE => EFSValueType::Double(f64::from_be_bytes(self.take()?)),
+            TAG_BYTE_ARRAY => {
+                let length = self.length()?;
+                EFSValueType::NBTByteArray(
+                    (0..length)
+                        .map(|_| Ok(i8::from_be_bytes(self.take()?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            TAG_STRING => EFSValueType::String(self.string()?),
+            TAG_LIST => {
+                let element = self.u8()?;
+                let length = self.length()?;
+                if element == TAG_END && length > 0 {
+                    bail!("nbt list of {} end tags", length);
+                }
+                EFSValueType::List(
+                    (0..length)
+                        .map(|_| self.payload(element, depth + 1))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            TAG_COMPOUND => {
+                let mut entries = HashMap::new();
+                loop {
+                    let tag = self.u8()?;
+                    if tag == TAG_END {
+                        break;
+                    }
+                    let name = self.string()?;
+                    entries.insert(name, self.payload(tag, depth + 1)?);
+                }
+                EFSValueType::Dict(entries)
+            }
+            TAG_INT_ARRAY => {
+                let length = self.length()?;
+                EFSValueType::NBTIntArray(
+                    (0..length)
+                        .map(|_| Ok(i32::from_be_bytes(self.take()?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            TAG_LONG_ARRAY => {
+                let length = self.length()?;
+                EFSValueType::NBTLongArray(
+                    (0..length)
+                        .map(|_| Ok(i64::from_be_bytes(self.take()?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            tag => bail!("unknown nbt tag {} at byte {}", tag, self.pos),
+        })
+    }
+}
+
+fn tag_id(value: &EFSValueType) -> anyhow::Result<u8> {
+    Ok(match value {
+        EFSValueType::Byte(_) | EFSValueType::Bool(_) => TAG_BYTE,
+        EFSValueType::Short(_) => TAG_SHORT,
+        EFSValueType::Number(_) | EFSValueType::Int(_) => TAG_INT,
+        EFSValueType::Long(_) => TAG_LONG,
+        EFSValueType::Float(_) => TAG_FLOAT,
+        EFSValueType::Double(_) => TAG_DOUBLE,
+        EFSValueType::NBTByteArray(_) => TAG_BYTE_ARRAY,
+        EFSValueType::String(_) | EFSValueType::Selector(_) | EFSValueType::Text(_) => TAG_STRING,
+        EFSValueType::List(_) => TAG_LIST,
+        EFSValueType::Struct(_, _) | EFSValueType::Dict(_) => TAG_COMPOUND,
+        EFSValueType::NBTIntArray(_) => TAG_INT_ARRAY,
+        EFSValueType::NBTLongArray(_) => TAG_LONG_ARRAY,
+        EFSValueType::None => bail!("None has no nbt representation"),
+    })
+}
+
+fn write_length(data: &mut Vec<u8>, length: usize) -> anyhow::Result<()> {
+    let length = i32::try_from(length).map_err(|_| anyhow!("nbt length {} too long", length))?;
+    data.extend_from_slice(&length.to_be_bytes());
+    Ok(())
+}
+
+fn write_string(data: &mut Vec<u8>, string: &str) -> anyhow::Result<()> {
+    let bytes = encode_mutf8(string);
+    let length =
+        u16::try_from(bytes.len()).map_err(|_| anyhow!("nbt string {:?} too long", string))?;
+    data.extend_from_slice(&length.to_be_bytes());
+    data.extend_from_slice(&bytes);
+    Ok(())
+}
+
+fn write_payload(data: &mut Vec<u8>, value: &EFSValueType) -> anyhow::Result<()> {
+    match value {
+        EFSValueType::Byte(n) => data.extend_from_slice(&n.to_be_bytes()),
+        EFSValueType::Bool(b) => data.push(*b as u8),
+        EFSValueType::Short(n) => data.extend_from_slice(&n.to_be_bytes()),
+        EFSValueType::Number(n) | EFSValueType::Int(n) => data.extend_from_slice(&n.to_be_bytes()),
+        EFSValueType::Long(n) => data.extend_from_slice(&n.to_be_bytes()),
+        EFSValueType::Float(n) => data.extend_from_slice(&n.to_be_bytes()),
+        EFSValueType::Double(n) => data.extend_from_slice(&n.to_be_bytes()),
+        EFSValueType::String(s) | EFSValueType::Selector(s) | EFSValueType::Text(s) => {
+            write_string(data, s)?
+        }
+        EFSValueType::NBTByteArray(array) => {
+            write_length(data, array.len())?;
+            array
+                .iter()
+                .for_each(|n| data.extend_from_slice(&n.to_be_bytes()));
+        }
+        EFSValueType::NBTIntArray(array) => {
+            write_length(data, array.len())?;
+            array
+                .iter()
+                .for_each(|n| data.extend_from_slice(&n.to_be_bytes()));
+        }
+        EFSValueType::NBTLongArray(array) => {
+            write_length(data, array.len())?;
+            array
+                .iter()
+                .for_each(|n| data.extend_from_slice(&n.to_be_bytes()));
+        }
+        EFSValueType::List(list) => {
+            let element = list.first().map_or(Ok(TAG_END), tag_id)?;
+            for item in list {
+                if tag_id(item)? != element {
+                    bail!("nbt list elements must all have the same type");
+                }
+            }
+            data.push(element);
+            write_length(data, list.len())?;
+            for item in list {
+                write_payload(data, item)?;
+            }
+        }
+        EFSValueType::Struct(_, entries) | EFSValueType::Dict(entries) => {
+            let mut keys = entries.keys().collect::<Vec<_>>();
+            keys.sort();
+            for key in keys {
+                data.push(tag_id(&entries[key])?);
+                write_string(data, key)?;
+                write_payload(data, &entries[key])?;
+            }
+            data.push(TAG_END);
+        }
+        EFSValueType::None => bail!("None has no nbt representation"),
+    }
+    Ok(())
+}
+
+/// Java's modified UTF-8: NUL is written as two bytes and characters outside the
+/// BMP as a surrogate pair of three byte sequences.
+fn encode_mutf8(string: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(string.len());
+    for unit in string.encode_utf16() {
+        match unit {
+            0x0001..=0x007f => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07ff => {
+                bytes.push(0xc0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            }
+            _ => {
+                bytes.push(0xe0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+fn decode_mutf8(bytes: &[u8]) -> anyhow::Result<String> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    let continuation = |pos: usize| {
+        bytes
+            .get(pos)
+            .filter(|b| *b & 0xc0 == 0x80)
+            .map(|b| (b & 0x3f) as u16)
+            .ok_or_else(|| anyhow!("invalid modified utf-8 in nbt string"))
+    };
+    while let Some(&byte) = bytes.get(pos) {
+        match byte {
+            0x01..=0x7f => {
+                units.push(byte as u16);
+                pos += 1;
+            }
+            0xc0..=0xdf => {
+                units.push(((byte & 0x1f) as u16) << 6 | continuation(pos + 1)?);
+                pos += 2;
+            }
+            0xe0..=0xef => {
+                units.push(
+                    ((byte & 0x0f) as u16) << 12
+                        | continuation(pos + 1)? << 6
+                        | continuation(pos + 2)?,
+                );
+                pos += 3;
+            }
+            _ => bail!("invalid modified utf-8 in nbt string"),
+        }
+    }
+    String::from_utf16(&units).map_err(|_| anyhow!("invalid modified utf-8 in nbt string"))
+}
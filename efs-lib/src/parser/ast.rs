@@ -3,28 +3,134 @@ use std::{collections::HashMap, path::PathBuf};
 use crate::parser::token::{Keyword, TokenType};
 
 use super::{
-    token::{ControlCharacter, Operator, Token, TokenHolder},
+    parse_path,
+    token::{ControlCharacter, Operator, Token, TokenHolder, TypeName},
     types::{EFSType, EFSValueType},
     ParseError, ParserItem,
 };
 
-pub struct Program(pub Vec<Declaration>);
+#[derive(Debug, Clone)]
+pub struct Program(pub Vec<Declaration>, pub Declared);
 
 impl ParserItem for Program {
     fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
         let mut decs = Vec::new();
+        let mut declared = Declared::default();
         let mut pos = start;
 
         while pos < tokens.len() {
             let (dec, len) = Declaration::parse(pos, &tokens[pos..])?;
+            declared.add(&dec, &tokens[pos..pos + len]);
             decs.push(dec);
             pos += len;
         }
 
-        Ok((Self(decs), pos))
+        Ok((Self(decs, declared), pos))
     }
 }
 
+/// Where the items of a program and the parameters of its functions are named in the
+/// source.
+#[derive(Debug, Clone, Default)]
+pub struct Declared {
+    /// By item, the functions of `impl Type` as `Type::function`.
+    items: HashMap<String, usize>,
+    /// By function, in the order of the parameters.
+    parameters: HashMap<String, Vec<usize>>,
+}
+
+impl Declared {
+    /// Where the item `name` is named, `name` may be qualified by the module it is in.
+    pub fn item(&self, name: &str) -> Option<usize> {
+        Self::find(&self.items, name).copied()
+    }
+
+    /// Where each parameter of the function `name` is named.
+    pub fn parameters(&self, name: &str) -> &[usize] {
+        Self::find(&self.parameters, name).map_or(&[], Vec::as_slice)
+    }
+
+    fn find<'a, T>(map: &'a HashMap<String, T>, mut name: &str) -> Option<&'a T> {
+        loop {
+            if let Some(found) = map.get(name) {
+                return Some(found);
+            }
+            name = name.split_once("::")?.1;
+        }
+    }
+
+    /// Records the names of `declaration`, which was parsed from `tokens`.
+    fn add(&mut self, declaration: &Declaration, tokens: &[TokenHolder]) {
+        let name = match declaration {
+            Declaration::Public(declaration) => return self.add(declaration, tokens),
+            Declaration::Impl(type_name, functions) => {
+                for function in functions {
+                    if let Declaration::FunctionDec {
+                        name, parameters, ..
+                    } = function.item()
+                    {
+                        let qualified = format!("{}::{}", type_name, name);
+                        self.function(qualified, name, parameters, tokens);
+                    }
+                }
+                return;
+            }
+            Declaration::FunctionDec {
+                name, parameters, ..
+            } => return self.function(name.clone(), name, parameters, tokens),
+            Declaration::ConstDec(constant) => &constant.name,
+            Declaration::ExternVariable(variable) => &variable.name,
+            Declaration::ExternFunction { name, .. }
+            | Declaration::StructDef(name, _)
+            | Declaration::EnumDef(name, _)
+            | Declaration::EntityScore(name, _) => name,
+            Declaration::UseFile(_) | Declaration::Use(_) => return,
+        };
+        if let Some(at) = identifier(tokens, 0, name) {
+            self.items.insert(name.clone(), tokens[at].start);
+        }
+    }
+
+    /// Records the function written as `fn name(parameters)` in `tokens`.
+    fn function(
+        &mut self,
+        key: String,
+        name: &str,
+        parameters: &[(String, EFSType)],
+        tokens: &[TokenHolder],
+    ) {
+        let Some(at) = (1..tokens.len()).find(|at| {
+            tokens[at - 1]
+                .token
+                .is(TokenType::Keyword(Keyword::Function))
+                && matches!(&tokens[*at].token, Token::Identifier(found) if found == name)
+        }) else {
+            return;
+        };
+        self.items.insert(key.clone(), tokens[at].start);
+        let mut next = at + 1;
+        let mut positions = Vec::new();
+        for (parameter, _) in parameters {
+            let Some(at) = identifier(tokens, next, parameter) else {
+                break;
+            };
+            positions.push(tokens[at].start);
+            next = at + 1;
+        }
+        self.parameters.insert(key, positions);
+    }
+}
+
+/// Index of the first identifier `name` in `tokens` from `from` on.
+fn identifier(tokens: &[TokenHolder], from: usize, name: &str) -> Option<usize> {
+    (from..tokens.len())
+        .find(|at| matches!(&tokens[*at].token, Token::Identifier(found) if found == name))
+}
+
+/// Name, parameters and return type of a function.
+type Signature = (String, Vec<(String, EFSType)>, EFSType);
+
+#[derive(Debug, Clone)]
 pub enum Declaration {
     FunctionDec {
         is_static: bool,
@@ -35,8 +141,45 @@ pub enum Declaration {
         code_block: CodeBlock,
     },
     ConstDec(Assignment),
+    /// `use "path"`, imports every public item of the file at `path`.
     UseFile(PathBuf),
+    /// `use module::item;` or `use module;`
+    Use(Vec<String>),
+    /// `pub` before a declaration, other modules may only use public items.
+    Public(Box<Declaration>),
     StructDef(String, HashMap<String, EFSType>),
+    /// `enum Name { Variant, Variant(type, ...) }`, variants are numbered in order.
+    EnumDef(String, Vec<(String, Vec<EFSType>)>),
+    /// `impl Name { fn ... }`, functions whose first parameter is `self` are methods.
+    Impl(String, Vec<Declaration>),
+    /// `extern fn name(params) -> type = "ns:path";`, a function of another datapack.
+    ExternFunction {
+        name: String,
+        parameters: Vec<(String, EFSType)>,
+        return_type: EFSType,
+        location: String,
+    },
+    ExternVariable(ExternVariable),
+    /// `score name: type on entity;`, a score every entity has, read and written as
+    /// `selector.name`.
+    EntityScore(String, EFSType),
+}
+
+/// `extern score name: type = "holder objective";` or
+/// `extern storage name: type = "ns:id path";`, a value another datapack keeps.
+#[derive(Debug, Clone)]
+pub struct ExternVariable {
+    pub name: String,
+    pub var_type: EFSType,
+    pub kind: ExternKind,
+    /// Where the value is, as it is written in the `scoreboard` or `data` command.
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternKind {
+    Score,
+    Storage,
 }
 impl ParserItem for Declaration {
     fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
@@ -45,20 +188,59 @@ impl ParserItem for Declaration {
             Some(TokenType::Keyword(Keyword::Function))
             | Some(TokenType::Keyword(Keyword::Static))
             | Some(TokenType::ControlCharacter(ControlCharacter::Attribute)) => {
-                todo!()
+                Self::parse_function(start, tokens, None)
             }
             Some(TokenType::Keyword(Keyword::Const)) => {
-                let (assignment, len) = Assignment::parse(start + 1, tokens)?;
-                Ok((Self::ConstDec(assignment), len + 1))
+                let (assignment, len) = Assignment::parse(start + 1, &tokens[1..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 1,
+                    TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                )?;
+                Ok((Self::ConstDec(assignment), len + 2))
+            }
+            Some(TokenType::Keyword(Keyword::Pub)) => {
+                let next = TokenHolder::expect_many(
+                    tokens,
+                    1,
+                    &[
+                        TokenType::Keyword(Keyword::Function),
+                        TokenType::Keyword(Keyword::Static),
+                        TokenType::Keyword(Keyword::Const),
+                        TokenType::Keyword(Keyword::Struct),
+                        TokenType::Keyword(Keyword::Enum),
+                        TokenType::Keyword(Keyword::Extern),
+                    ],
+                )?;
+                if next.token.is_many(&[
+                    TokenType::Keyword(Keyword::Function),
+                    TokenType::Keyword(Keyword::Static),
+                ]) {
+                    return Self::parse_function(start, tokens, None);
+                }
+                let (declaration, len) = Self::parse(start + 1, &tokens[1..])?;
+                Ok((Self::Public(Box::new(declaration)), len + 1))
             }
             Some(TokenType::Keyword(Keyword::UseFile)) => {
                 if let Some(token) = tokens.get(1) {
                     if let Token::String(s) = token.token.clone() {
-                        Ok((Declaration::UseFile(s.into()), 2))
+                        let end = tokens.get(2).is_some_and(|t| {
+                            t.token
+                                .is(TokenType::ControlCharacter(ControlCharacter::EndOfLine))
+                        });
+                        Ok((Declaration::UseFile(s.into()), if end { 3 } else { 2 }))
+                    } else if token.token.is(TokenType::Identifier) {
+                        let (path, len) = parse_path(&tokens[1..])?;
+                        TokenHolder::expect(
+                            tokens,
+                            len + 1,
+                            TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                        )?;
+                        Ok((Declaration::Use(path), len + 2))
                     } else {
                         Err(ParseError {
                             at: token.clone(),
-                            expected: Vec::from([TokenType::String]),
+                            expected: Vec::from([TokenType::String, TokenType::Identifier]),
                         })
                     }
                 } else {
@@ -73,31 +255,71 @@ impl ParserItem for Declaration {
                 }
             }
             Some(TokenType::Keyword(Keyword::Struct)) => {
-                if let Some(ident_holder) = tokens.get(start + 1) {
-                    if let Token::Identifier(ident) = &ident_holder.token {
-                        TokenHolder::is_or_eoi(
-                            tokens.get(start + 2),
-                            TokenType::ControlCharacter(ControlCharacter::LBrace),
-                            ident_holder.start + ident_holder.length,
-                        )?;
+                let first = first.unwrap();
+                let ident_holder =
+                    TokenHolder::is_or_eoi(tokens.get(1), TokenType::Identifier, first.start)?;
+                let Token::Identifier(name) = ident_holder.token.clone() else {
+                    unreachable!()
+                };
+                TokenHolder::is_or_eoi(
+                    tokens.get(2),
+                    TokenType::ControlCharacter(ControlCharacter::LBrace),
+                    ident_holder.start + ident_holder.length,
+                )?;
 
-                        let mut pos = 3;
-                        loop {
-                            if let Token::Identifier(ident) = TokenHolder::is_or_eoi(tokens.get(pos), TokenType::Identifier, pos) {
-                                
-                            }
-                        }
+                let mut fields = HashMap::new();
+                let mut pos = 3;
+                loop {
+                    let holder = TokenHolder::is_many_or_eoi(
+                        tokens.get(pos),
+                        &[
+                            TokenType::Identifier,
+                            TokenType::ControlCharacter(ControlCharacter::RBrace),
+                        ],
+                        tokens[pos - 1].start + tokens[pos - 1].length,
+                    )?;
+                    let Token::Identifier(field) = holder.token.clone() else {
+                        break;
+                    };
+                    TokenHolder::is_or_eoi(
+                        tokens.get(pos + 1),
+                        TokenType::ControlCharacter(ControlCharacter::TypeClarify),
+                        holder.start + holder.length,
+                    )?;
+                    let (field_type, len) = EFSType::parse(start + pos + 2, &tokens[pos + 2..])?;
+                    fields.insert(field, field_type);
+                    pos += len + 2;
 
+                    let holder = TokenHolder::is_many_or_eoi(
+                        tokens.get(pos),
+                        &[
+                            TokenType::ControlCharacter(ControlCharacter::Comma),
+                            TokenType::ControlCharacter(ControlCharacter::RBrace),
+                        ],
+                        tokens[pos - 1].start + tokens[pos - 1].length,
+                    )?;
+                    if holder
+                        .token
+                        .is(TokenType::ControlCharacter(ControlCharacter::RBrace))
+                    {
+                        break;
                     }
+                    pos += 1;
                 }
 
-                todo!()
+                Ok((Self::StructDef(name, fields), pos + 1))
+            }
+            Some(TokenType::Keyword(Keyword::Enum)) => Self::parse_enum(start, tokens),
+            Some(TokenType::Keyword(Keyword::Impl)) => Self::parse_impl(start, tokens),
+            Some(TokenType::Keyword(Keyword::Extern)) => Self::parse_extern(start, tokens),
+            Some(TokenType::Identifier) if matches!(&tokens[0].token, Token::Identifier(word) if word == "score") => {
+                Self::parse_score(start, tokens)
             }
             _ => Err(ParseError {
                 at: tokens
                     .first()
                     .unwrap_or(&TokenHolder {
-                        start: start,
+                        start,
                         length: 0,
                         token: Token::EOI,
                     })
@@ -109,12 +331,462 @@ impl ParserItem for Declaration {
                     TokenType::Keyword(Keyword::Const),
                     TokenType::Keyword(Keyword::UseFile),
                     TokenType::Keyword(Keyword::Struct),
+                    TokenType::Keyword(Keyword::Enum),
+                    TokenType::Keyword(Keyword::Impl),
+                    TokenType::Keyword(Keyword::Pub),
+                    TokenType::Keyword(Keyword::Extern),
                 ]),
             }),
         }
     }
 }
 
+impl Declaration {
+    /// The declaration without its `pub`.
+    pub fn item(&self) -> &Declaration {
+        match self {
+            Declaration::Public(declaration) => declaration.item(),
+            declaration => declaration,
+        }
+    }
+
+    fn parse_enum(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let Token::Identifier(name) = TokenHolder::expect(tokens, 1, TokenType::Identifier)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+        TokenHolder::expect(
+            tokens,
+            2,
+            TokenType::ControlCharacter(ControlCharacter::LBrace),
+        )?;
+
+        let mut variants = Vec::new();
+        let mut pos = 3;
+        loop {
+            let holder = TokenHolder::expect_many(
+                tokens,
+                pos,
+                &[
+                    TokenType::Identifier,
+                    TokenType::ControlCharacter(ControlCharacter::RBrace),
+                ],
+            )?;
+            let Token::Identifier(variant) = holder.token.clone() else {
+                break;
+            };
+            pos += 1;
+
+            let mut payload = Vec::new();
+            if tokens.get(pos).is_some_and(|t| {
+                t.token
+                    .is(TokenType::ControlCharacter(ControlCharacter::LeftParen))
+            }) {
+                pos += 1;
+                loop {
+                    let (var_type, len) = EFSType::parse(start + pos, &tokens[pos..])?;
+                    payload.push(var_type);
+                    pos += len;
+                    if !TokenHolder::expect_many(
+                        tokens,
+                        pos,
+                        &[
+                            TokenType::ControlCharacter(ControlCharacter::Comma),
+                            TokenType::ControlCharacter(ControlCharacter::RightParen),
+                        ],
+                    )?
+                    .token
+                    .is(TokenType::ControlCharacter(ControlCharacter::Comma))
+                    {
+                        break;
+                    }
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            variants.push((variant, payload));
+
+            if !TokenHolder::expect_many(
+                tokens,
+                pos,
+                &[
+                    TokenType::ControlCharacter(ControlCharacter::Comma),
+                    TokenType::ControlCharacter(ControlCharacter::RBrace),
+                ],
+            )?
+            .token
+            .is(TokenType::ControlCharacter(ControlCharacter::Comma))
+            {
+                break;
+            }
+            pos += 1;
+        }
+
+        Ok((Self::EnumDef(name, variants), pos + 1))
+    }
+
+    fn parse_impl(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let Token::Identifier(name) = TokenHolder::expect(tokens, 1, TokenType::Identifier)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+        TokenHolder::expect(
+            tokens,
+            2,
+            TokenType::ControlCharacter(ControlCharacter::LBrace),
+        )?;
+
+        let mut functions = Vec::new();
+        let mut pos = 3;
+        while !TokenHolder::expect_many(
+            tokens,
+            pos,
+            &[
+                TokenType::Keyword(Keyword::Function),
+                TokenType::Keyword(Keyword::Static),
+                TokenType::Keyword(Keyword::Pub),
+                TokenType::ControlCharacter(ControlCharacter::Attribute),
+                TokenType::ControlCharacter(ControlCharacter::RBrace),
+            ],
+        )?
+        .token
+        .is(TokenType::ControlCharacter(ControlCharacter::RBrace))
+        {
+            let (function, len) = Self::parse_function(start + pos, &tokens[pos..], Some(&name))?;
+            functions.push(function);
+            pos += len;
+        }
+
+        Ok((Self::Impl(name, functions), pos + 1))
+    }
+
+    /// Parses a function, inside `impl receiver` the first parameter may be a bare `self`.
+    /// A `pub` function comes back wrapped in `Declaration::Public`.
+    fn parse_function(
+        start: usize,
+        tokens: &[TokenHolder],
+        receiver: Option<&str>,
+    ) -> Result<(Self, usize), ParseError> {
+        let mut attributes = HashMap::new();
+        let mut pos = 0;
+        while tokens.get(pos).is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::Attribute))
+        }) {
+            pos += Self::parse_attributes(&mut attributes, &tokens[pos..])?;
+        }
+
+        let is_pub = tokens
+            .get(pos)
+            .is_some_and(|t| t.token.is(TokenType::Keyword(Keyword::Pub)));
+        if is_pub {
+            pos += 1;
+        }
+
+        let is_static = tokens
+            .get(pos)
+            .is_some_and(|t| t.token.is(TokenType::Keyword(Keyword::Static)));
+        if is_static {
+            pos += 1;
+        }
+
+        let ((name, parameters, return_type), len) =
+            Self::parse_signature(start + pos, &tokens[pos..], receiver)?;
+        pos += len;
+
+        let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+
+        let function = Self::FunctionDec {
+            is_static,
+            attributes,
+            name,
+            parameters,
+            return_type,
+            code_block,
+        };
+        if is_pub {
+            return Ok((Self::Public(Box::new(function)), pos + len));
+        }
+        Ok((function, pos + len))
+    }
+
+    /// Parses `fn name(params) -> type` up to the body of a function.
+    fn parse_signature(
+        start: usize,
+        tokens: &[TokenHolder],
+        receiver: Option<&str>,
+    ) -> Result<(Signature, usize), ParseError> {
+        TokenHolder::expect(tokens, 0, TokenType::Keyword(Keyword::Function))?;
+        let Token::Identifier(name) = TokenHolder::expect(tokens, 1, TokenType::Identifier)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+        TokenHolder::expect(
+            tokens,
+            2,
+            TokenType::ControlCharacter(ControlCharacter::LeftParen),
+        )?;
+        let mut pos = 3;
+
+        let mut parameters = Vec::new();
+        if !tokens.get(pos).is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::RightParen))
+        }) {
+            loop {
+                let Token::Identifier(param) =
+                    TokenHolder::expect(tokens, pos, TokenType::Identifier)?
+                        .token
+                        .clone()
+                else {
+                    unreachable!()
+                };
+                let typed = tokens.get(pos + 1).is_some_and(|t| {
+                    t.token
+                        .is(TokenType::ControlCharacter(ControlCharacter::TypeClarify))
+                });
+                match receiver {
+                    Some(receiver) if param == "self" && parameters.is_empty() && !typed => {
+                        parameters.push((param, EFSType::Struct(receiver.to_string())));
+                        pos += 1;
+                    }
+                    _ => {
+                        TokenHolder::expect(
+                            tokens,
+                            pos + 1,
+                            TokenType::ControlCharacter(ControlCharacter::TypeClarify),
+                        )?;
+                        let (param_type, len) =
+                            EFSType::parse(start + pos + 2, &tokens[pos + 2..])?;
+                        parameters.push((param, param_type));
+                        pos += len + 2;
+                    }
+                }
+
+                if !TokenHolder::expect_many(
+                    tokens,
+                    pos,
+                    &[
+                        TokenType::ControlCharacter(ControlCharacter::Comma),
+                        TokenType::ControlCharacter(ControlCharacter::RightParen),
+                    ],
+                )?
+                .token
+                .is(TokenType::ControlCharacter(ControlCharacter::Comma))
+                {
+                    break;
+                }
+                pos += 1;
+            }
+        }
+        pos += 1;
+
+        let mut return_type = EFSType::None;
+        if tokens.get(pos).is_some_and(|t| {
+            t.token.is(TokenType::ControlCharacter(
+                ControlCharacter::FunctionReturn,
+            ))
+        }) {
+            let (parsed, len) = EFSType::parse(start + pos + 1, &tokens[pos + 1..])?;
+            return_type = parsed;
+            pos += len + 1;
+        }
+        Ok(((name, parameters, return_type), pos))
+    }
+
+    /// Parses `extern fn ... = "location";`, `extern score ... = "location";` or
+    /// `extern storage ... = "location";`.
+    fn parse_extern(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let next = TokenHolder::expect_many(
+            tokens,
+            1,
+            &[TokenType::Keyword(Keyword::Function), TokenType::Identifier],
+        )?;
+        let kind = match &next.token {
+            Token::Identifier(kind) if kind == "score" => ExternKind::Score,
+            Token::Identifier(kind) if kind == "storage" => ExternKind::Storage,
+            Token::Identifier(_) => {
+                return Err(ParseError {
+                    at: next.clone(),
+                    expected: vec![TokenType::Keyword(Keyword::Function)],
+                })
+            }
+            _ => {
+                let ((name, parameters, return_type), len) =
+                    Self::parse_signature(start + 1, &tokens[1..], None)?;
+                let (location, end) = Self::parse_extern_location(tokens, len + 1)?;
+                let function = Self::ExternFunction {
+                    name,
+                    parameters,
+                    return_type,
+                    location,
+                };
+                return Ok((function, end));
+            }
+        };
+
+        let Token::Identifier(name) = TokenHolder::expect(tokens, 2, TokenType::Identifier)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+        TokenHolder::expect(
+            tokens,
+            3,
+            TokenType::ControlCharacter(ControlCharacter::TypeClarify),
+        )?;
+        let (var_type, len) = EFSType::parse(start + 4, &tokens[4..])?;
+        let (location, end) = Self::parse_extern_location(tokens, len + 4)?;
+        let variable = ExternVariable {
+            name,
+            var_type,
+            kind,
+            location,
+        };
+        Ok((Self::ExternVariable(variable), end))
+    }
+
+    /// Parses `score name: type on entity;`.
+    fn parse_score(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let Token::Identifier(name) = TokenHolder::expect(tokens, 1, TokenType::Identifier)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+        TokenHolder::expect(
+            tokens,
+            2,
+            TokenType::ControlCharacter(ControlCharacter::TypeClarify),
+        )?;
+        let (var_type, len) = EFSType::parse(start + 3, &tokens[3..])?;
+        let mut pos = len + 3;
+        for word in ["on", "entity"] {
+            let holder = TokenHolder::expect(tokens, pos, TokenType::Identifier)?;
+            if holder.token != Token::Identifier(word.to_string()) {
+                return Err(ParseError {
+                    at: holder.clone(),
+                    expected: vec![TokenType::Identifier],
+                });
+            }
+            pos += 1;
+        }
+        TokenHolder::expect(
+            tokens,
+            pos,
+            TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+        )?;
+        Ok((Self::EntityScore(name, var_type), pos + 1))
+    }
+
+    /// Parses the `= "location";` at `pos` that ends an `extern` declaration.
+    fn parse_extern_location(
+        tokens: &[TokenHolder],
+        pos: usize,
+    ) -> Result<(String, usize), ParseError> {
+        TokenHolder::expect(
+            tokens,
+            pos,
+            TokenType::ControlCharacter(ControlCharacter::Assign),
+        )?;
+        let Token::String(location) = TokenHolder::expect(tokens, pos + 1, TokenType::String)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+        TokenHolder::expect(
+            tokens,
+            pos + 2,
+            TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+        )?;
+        Ok((location, pos + 3))
+    }
+
+    /// Parses one `#[name, name(arg, ...)]` list into `attributes`, arguments may be
+    /// identifiers or strings.
+    fn parse_attributes(
+        attributes: &mut HashMap<String, Vec<String>>,
+        tokens: &[TokenHolder],
+    ) -> Result<usize, ParseError> {
+        TokenHolder::expect(
+            tokens,
+            1,
+            TokenType::ControlCharacter(ControlCharacter::LBracket),
+        )?;
+        let mut pos = 2;
+        loop {
+            let Token::Identifier(name) = TokenHolder::expect(tokens, pos, TokenType::Identifier)?
+                .token
+                .clone()
+            else {
+                unreachable!()
+            };
+            pos += 1;
+
+            let mut args = Vec::new();
+            if tokens.get(pos).is_some_and(|t| {
+                t.token
+                    .is(TokenType::ControlCharacter(ControlCharacter::LeftParen))
+            }) {
+                pos += 1;
+                loop {
+                    match &TokenHolder::expect_many(
+                        tokens,
+                        pos,
+                        &[TokenType::Identifier, TokenType::String],
+                    )?
+                    .token
+                    {
+                        Token::Identifier(arg) | Token::String(arg) => args.push(arg.clone()),
+                        _ => unreachable!(),
+                    }
+                    pos += 1;
+                    if !TokenHolder::expect_many(
+                        tokens,
+                        pos,
+                        &[
+                            TokenType::ControlCharacter(ControlCharacter::Comma),
+                            TokenType::ControlCharacter(ControlCharacter::RightParen),
+                        ],
+                    )?
+                    .token
+                    .is(TokenType::ControlCharacter(ControlCharacter::Comma))
+                    {
+                        break;
+                    }
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            attributes.entry(name).or_default().extend(args);
+
+            if !TokenHolder::expect_many(
+                tokens,
+                pos,
+                &[
+                    TokenType::ControlCharacter(ControlCharacter::Comma),
+                    TokenType::ControlCharacter(ControlCharacter::RBracket),
+                ],
+            )?
+            .token
+            .is(TokenType::ControlCharacter(ControlCharacter::Comma))
+            {
+                return Ok(pos + 1);
+            }
+            pos += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Assignment {
     pub name: String,
     pub var_type: Option<EFSType>,
@@ -123,37 +795,1162 @@ pub struct Assignment {
 
 impl ParserItem for Assignment {
     fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
-        todo!()
+        let Token::Identifier(name) = TokenHolder::expect(tokens, 0, TokenType::Identifier)?
+            .token
+            .clone()
+        else {
+            unreachable!()
+        };
+
+        let mut pos = 1;
+        let mut var_type = None;
+        if tokens.get(pos).is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::TypeClarify))
+        }) {
+            let (parsed, len) = EFSType::parse(start + pos + 1, &tokens[pos + 1..])?;
+            var_type = Some(parsed);
+            pos += len + 1;
+        }
+
+        TokenHolder::expect(
+            tokens,
+            pos,
+            TokenType::ControlCharacter(ControlCharacter::Assign),
+        )?;
+        let (var, len) = Value::parse(start + pos + 1, &tokens[pos + 1..])?;
+
+        Ok((
+            Self {
+                name,
+                var_type,
+                var,
+            },
+            pos + len + 1,
+        ))
     }
 }
 
-pub struct CodeBlock(Vec<Statement>);
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub statements: Vec<Statement>,
+    /// Where each statement starts in the source, for diagnostics.
+    pub positions: Vec<usize>,
+    /// The names each statement is written with and where they are in the source, in
+    /// source order. `a::b` paths are one name, fields, methods, the keys of literals and
+    /// the names inside nested blocks are left out.
+    pub names: Vec<Vec<(String, usize)>>,
+    /// Where the closing `}` is in the source.
+    pub end: usize,
+}
+
+impl ParserItem for CodeBlock {
+    fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        TokenHolder::expect(
+            tokens,
+            0,
+            TokenType::ControlCharacter(ControlCharacter::LBrace),
+        )?;
+        let mut statements = Vec::new();
+        let mut positions = Vec::new();
+        let mut names = Vec::new();
+        let mut pos = 1;
+        while !tokens.get(pos).is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::RBrace))
+        }) {
+            if pos >= tokens.len() {
+                TokenHolder::expect(
+                    tokens,
+                    pos,
+                    TokenType::ControlCharacter(ControlCharacter::RBrace),
+                )?;
+            }
+            let (statement, len) = Statement::parse(start + pos, &tokens[pos..])?;
+            names.push(statement_names(&statement, &tokens[pos..pos + len]));
+            statements.push(statement);
+            positions.push(tokens[pos].start);
+            pos += len;
+        }
+        Ok((
+            Self {
+                statements,
+                positions,
+                names,
+                end: tokens[pos].start,
+            },
+            pos + 1,
+        ))
+    }
+}
+
+/// The names of `statement`, which was parsed from `tokens`, see `CodeBlock::names`.
+fn statement_names(statement: &Statement, tokens: &[TokenHolder]) -> Vec<(String, usize)> {
+    let blocks = match statement {
+        Statement::For(_, _, _, block)
+        | Statement::ForList(_, _, block)
+        | Statement::StaticFor(_, _, _, _, block)
+        | Statement::While(_, block)
+        | Statement::If(_, block)
+        | Statement::IfLet(_, _, block)
+        | Statement::Execute(_, block) => vec![block],
+        Statement::Match(_, arms) => arms.iter().map(|arm| &arm.block).collect(),
+        _ => Vec::new(),
+    };
+    let nested = |at: usize| {
+        blocks.iter().any(|block| {
+            block
+                .positions
+                .first()
+                .is_some_and(|start| (*start..block.end).contains(&at))
+        })
+    };
+    let is = |at: Option<usize>, expected: ControlCharacter| {
+        at.and_then(|at| tokens.get(at))
+            .is_some_and(|t| t.token.is(TokenType::ControlCharacter(expected)))
+    };
+
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        let Token::Identifier(name) = &tokens[pos].token else {
+            pos += 1;
+            continue;
+        };
+        let start = tokens[pos].start;
+        let previous = pos.checked_sub(1);
+        let mut path = name.clone();
+        while is(Some(pos + 1), ControlCharacter::Scope) {
+            let Some(Token::Identifier(segment)) = tokens.get(pos + 2).map(|t| &t.token) else {
+                break;
+            };
+            path = format!("{}::{}", path, segment);
+            pos += 2;
+        }
+        pos += 1;
+        let member =
+            is(previous, ControlCharacter::Dot) || is(previous, ControlCharacter::OptionalDot);
+        let key = (is(previous, ControlCharacter::LBrace) || is(previous, ControlCharacter::Comma))
+            && is(Some(pos), ControlCharacter::TypeClarify);
+        if !member && !key && !nested(start) {
+            names.push((path, start));
+        }
+    }
+    names
+}
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    For(Box<Statement>, Box<Statement>, Box<Statement>, CodeBlock),
+    /// `for (init; condition; step) {}`, the step is an expression without `;`.
+    For(Box<Statement>, Value, Box<Statement>, CodeBlock),
+    /// `for (item in list) {}`
     ForList(String, String, CodeBlock),
+    /// `static for i in start..end step n {}`, unrolled at compile time.
     StaticFor(String, i32, i32, i32, CodeBlock),
-    While(Box<Statement>, CodeBlock),
-    If(Box<Statement>, CodeBlock),
+    While(Value, CodeBlock),
+    If(Value, CodeBlock),
+    /// `if (let name = value) {}` runs the block with the value of an optional
+    /// bound to `name` if it is not `None`.
+    IfLet(String, Value, CodeBlock),
+    /// `match (value) { Enum::Variant(a, b) => {} _ => {} }`
+    Match(Value, Vec<MatchArm>),
+    Return(Option<Value>),
+    Break,
+    Continue,
+    /// `mc! { ... }` or `cmd "...";`, commands emitted as they are written except
+    /// that `$(name)` is replaced by where the variable `name` is.
+    Commands(Vec<String>),
+    /// `as @a at @s { ... }`, runs the block in another context, like `execute`.
+    Execute(Vec<Context>, CodeBlock),
     Expression(Expression),
 }
 
+/// A part of the context commands run in, one subcommand of `execute`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Context {
+    /// `as <selector>`, once for every entity with `@s` being that entity.
+    As(Value),
+    /// `at <selector>`, once at the position of every entity.
+    At(Value),
+    /// `positioned ~ ~1 ~`
+    Positioned(String),
+    /// `in minecraft:the_nether`
+    In(String),
+}
+
+impl Context {
+    /// The selector of `as` and `at`.
+    pub fn selector(&self) -> Option<&Value> {
+        match self {
+            Context::As(selector) | Context::At(selector) => Some(selector),
+            Context::Positioned(_) | Context::In(_) => None,
+        }
+    }
+
+    /// Copy with the selector of `as` and `at` replaced by `map(selector)`.
+    pub fn map_selector(&self, map: impl FnOnce(&Value) -> Value) -> Self {
+        match self {
+            Context::As(selector) => Context::As(map(selector)),
+            Context::At(selector) => Context::At(map(selector)),
+            context => context.clone(),
+        }
+    }
+
+    /// Whether the block runs once for every selected entity instead of once.
+    pub fn is_loop(&self) -> bool {
+        matches!(self, Context::As(_) | Context::At(_))
+    }
+
+    /// Whether `tokens` start with a context. `at` and `positioned` are only
+    /// keywords when they are followed by what they take.
+    fn starts(tokens: &[TokenHolder]) -> bool {
+        let next = tokens.get(1).map(|t| t.token.token_type());
+        match tokens.first().map(|t| &t.token) {
+            Some(Token::Keyword(Keyword::As | Keyword::In)) => true,
+            Some(Token::Identifier(name)) if name == "at" => {
+                matches!(
+                    next,
+                    Some(TokenType::Selector | TokenType::String | TokenType::Identifier)
+                )
+            }
+            Some(Token::Identifier(name)) if name == "positioned" => matches!(
+                next,
+                Some(
+                    TokenType::Coordinate
+                        | TokenType::Integer
+                        | TokenType::Float
+                        | TokenType::Operator(Operator::Minus)
+                        | TokenType::Operator(Operator::Xor)
+                )
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl ParserItem for Context {
+    fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        match &tokens[0].token {
+            Token::Keyword(Keyword::As) => {
+                let (selector, len) = parse_selector(start + 1, &tokens[1..])?;
+                Ok((Context::As(selector), len + 1))
+            }
+            Token::Keyword(Keyword::In) => {
+                let holder = TokenHolder::expect_many(
+                    tokens,
+                    1,
+                    &[TokenType::String, TokenType::Identifier],
+                )?;
+                match &holder.token {
+                    Token::String(dimension) => Ok((Context::In(dimension.clone()), 2)),
+                    Token::Identifier(namespace)
+                        if tokens.get(2).is_some_and(|t| {
+                            t.token
+                                .is(TokenType::ControlCharacter(ControlCharacter::TypeClarify))
+                        }) =>
+                    {
+                        let Token::Identifier(path) =
+                            &TokenHolder::expect(tokens, 3, TokenType::Identifier)?.token
+                        else {
+                            unreachable!()
+                        };
+                        Ok((Context::In(format!("{}:{}", namespace, path)), 4))
+                    }
+                    Token::Identifier(dimension) => Ok((Context::In(dimension.clone()), 2)),
+                    _ => unreachable!(),
+                }
+            }
+            Token::Identifier(name) if name == "at" => {
+                let (selector, len) = parse_selector(start + 1, &tokens[1..])?;
+                Ok((Context::At(selector), len + 1))
+            }
+            _ => {
+                let mut coordinates = Vec::new();
+                let mut pos = 1;
+                for _ in 0..3 {
+                    let previous = &tokens[pos - 1];
+                    let end = previous.start + previous.length;
+                    let (coordinate, len) = parse_coordinate(&tokens[pos..], end)?;
+                    coordinates.push(coordinate);
+                    pos += len;
+                }
+                Ok((Context::Positioned(coordinates.join(" ")), pos))
+            }
+        }
+    }
+}
+
+/// The selector of `as` and `at`, a selector literal with its builder methods or a
+/// constant. Other values would take the `{` of the block for a struct.
+fn parse_selector(start: usize, tokens: &[TokenHolder]) -> Result<(Value, usize), ParseError> {
+    let holder = TokenHolder::expect_many(
+        tokens,
+        0,
+        &[
+            TokenType::Selector,
+            TokenType::String,
+            TokenType::Identifier,
+        ],
+    )?;
+    match &holder.token {
+        Token::Selector(_) => return Value::parse_postfix(start, tokens),
+        Token::String(name) => return Ok((Value::Value(EFSValueType::String(name.clone())), 1)),
+        _ => {}
+    }
+    let (mut path, len) = parse_path(tokens)?;
+    let name = path.pop().unwrap();
+    if path.is_empty() {
+        return Ok((Value::Identifier(name), len));
+    }
+    Ok((Value::Variant(path.join("::"), name, Vec::new()), len))
+}
+
+/// One coordinate of a position: `~1`, `^1`, `-1` or `1.5`. The parts of `^-1` and
+/// `-1` are separate tokens, they have to touch each other.
+fn parse_coordinate(tokens: &[TokenHolder], end: usize) -> Result<(String, usize), ParseError> {
+    let touches = |pos: usize| {
+        pos > 0
+            && tokens.get(pos).is_some_and(|t| {
+                let previous = &tokens[pos - 1];
+                t.start == previous.start + previous.length
+            })
+    };
+    let holder = TokenHolder::is_many_or_eoi(
+        tokens.first(),
+        &[
+            TokenType::Coordinate,
+            TokenType::Operator(Operator::Xor),
+            TokenType::Operator(Operator::Minus),
+            TokenType::Integer,
+            TokenType::Float,
+        ],
+        end,
+    )?;
+    let (mut text, mut pos) = match &holder.token {
+        Token::Coordinate(coordinate) => return Ok((coordinate.clone(), 1)),
+        Token::Operator(Operator::Xor) if !touches(1) => return Ok((String::from("^"), 1)),
+        Token::Operator(Operator::Xor) => (String::from("^"), 1),
+        _ => (String::new(), 0),
+    };
+    if tokens
+        .get(pos)
+        .is_some_and(|t| t.token.is(TokenType::Operator(Operator::Minus)))
+        && (pos == 0 || touches(pos))
+    {
+        text.push('-');
+        pos += 1;
+    }
+    let number = TokenHolder::is_many_or_eoi(
+        tokens.get(pos),
+        &[TokenType::Integer, TokenType::Float],
+        end,
+    )?;
+    if pos > 0 && !touches(pos) {
+        return Err(ParseError {
+            at: number.clone(),
+            expected: vec![TokenType::Coordinate],
+        });
+    }
+    match &number.token {
+        Token::Integer(n) => text.push_str(&n.to_string()),
+        Token::Float(n) => text.push_str(&n.to_string()),
+        _ => unreachable!(),
+    }
+    Ok((text, pos + 1))
+}
+
+impl ParserItem for Statement {
+    fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        if Context::starts(tokens) {
+            let mut contexts = Vec::new();
+            let mut pos = 0;
+            while Context::starts(&tokens[pos..]) {
+                let (context, len) = Context::parse(start + pos, &tokens[pos..])?;
+                contexts.push(context);
+                pos += len;
+            }
+            let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+            return Ok((Statement::Execute(contexts, code_block), pos + len));
+        }
+        match tokens.first().map(|t| &t.token) {
+            Some(Token::Commands(commands)) => {
+                return Ok((Statement::Commands(commands.clone()), 1))
+            }
+            Some(Token::Identifier(name))
+                if name == "cmd"
+                    && tokens.get(1).is_some_and(|t| t.token.is(TokenType::String)) =>
+            {
+                let Token::String(command) = &tokens[1].token else {
+                    unreachable!()
+                };
+                TokenHolder::expect(
+                    tokens,
+                    2,
+                    TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                )?;
+                return Ok((Statement::Commands(vec![command.clone()]), 3));
+            }
+            _ => {}
+        }
+        match tokens.first().map(|t| t.token.token_type()) {
+            Some(TokenType::Keyword(Keyword::If))
+                if tokens
+                    .get(2)
+                    .is_some_and(|t| t.token.is(TokenType::Keyword(Keyword::VarDeceleration))) =>
+            {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                )?;
+                let name = match &TokenHolder::expect(tokens, 3, TokenType::Identifier)?.token {
+                    Token::Identifier(name) => name.clone(),
+                    _ => unreachable!(),
+                };
+                TokenHolder::expect(
+                    tokens,
+                    4,
+                    TokenType::ControlCharacter(ControlCharacter::Assign),
+                )?;
+                let (value, len) = Value::parse(start + 5, &tokens[5..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 5,
+                    TokenType::ControlCharacter(ControlCharacter::RightParen),
+                )?;
+                let pos = len + 6;
+                let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+                Ok((Statement::IfLet(name, value, code_block), pos + len))
+            }
+            Some(TokenType::Keyword(Keyword::If)) => {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                )?;
+                let (condition, len) = Value::parse(start + 2, &tokens[2..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 2,
+                    TokenType::ControlCharacter(ControlCharacter::RightParen),
+                )?;
+                let pos = len + 3;
+                let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+                Ok((Statement::If(condition, code_block), pos + len))
+            }
+            Some(TokenType::Keyword(Keyword::Match)) => {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                )?;
+                let (value, len) = Value::parse(start + 2, &tokens[2..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 2,
+                    TokenType::ControlCharacter(ControlCharacter::RightParen),
+                )?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 3,
+                    TokenType::ControlCharacter(ControlCharacter::LBrace),
+                )?;
+                let mut pos = len + 4;
+                let mut arms = Vec::new();
+                while !tokens.get(pos).is_some_and(|t| {
+                    t.token
+                        .is(TokenType::ControlCharacter(ControlCharacter::RBrace))
+                }) {
+                    let (pattern, len) = Pattern::parse(start + pos, &tokens[pos..])?;
+                    TokenHolder::expect(
+                        tokens,
+                        pos + len,
+                        TokenType::ControlCharacter(ControlCharacter::MatchArm),
+                    )?;
+                    pos += len + 1;
+                    let (block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+                    arms.push(MatchArm { pattern, block });
+                    pos += len;
+                }
+                Ok((Statement::Match(value, arms), pos + 1))
+            }
+            Some(TokenType::Keyword(Keyword::While)) => {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                )?;
+                let (condition, len) = Value::parse(start + 2, &tokens[2..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 2,
+                    TokenType::ControlCharacter(ControlCharacter::RightParen),
+                )?;
+                let pos = len + 3;
+                let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+                Ok((Statement::While(condition, code_block), pos + len))
+            }
+            Some(TokenType::Keyword(Keyword::For)) => {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                )?;
+                if let (Some(Token::Identifier(item)), Some(Token::Keyword(Keyword::In))) = (
+                    tokens.get(2).map(|t| &t.token),
+                    tokens.get(3).map(|t| &t.token),
+                ) {
+                    let list = match &TokenHolder::expect(tokens, 4, TokenType::Identifier)?.token {
+                        Token::Identifier(list) => list.clone(),
+                        _ => unreachable!(),
+                    };
+                    TokenHolder::expect(
+                        tokens,
+                        5,
+                        TokenType::ControlCharacter(ControlCharacter::RightParen),
+                    )?;
+                    let (code_block, len) = CodeBlock::parse(start + 6, &tokens[6..])?;
+                    return Ok((Statement::ForList(item.clone(), list, code_block), len + 6));
+                }
+
+                let mut pos = 2;
+                let (init, len) = Statement::parse(start + pos, &tokens[pos..])?;
+                pos += len;
+                let (condition, len) = Value::parse(start + pos, &tokens[pos..])?;
+                pos += len;
+                TokenHolder::expect(
+                    tokens,
+                    pos,
+                    TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                )?;
+                pos += 1;
+                let (step, len) = Expression::parse(start + pos, &tokens[pos..])?;
+                pos += len;
+                TokenHolder::expect(
+                    tokens,
+                    pos,
+                    TokenType::ControlCharacter(ControlCharacter::RightParen),
+                )?;
+                pos += 1;
+                let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+                Ok((
+                    Statement::For(
+                        Box::new(init),
+                        condition,
+                        Box::new(Statement::Expression(step)),
+                        code_block,
+                    ),
+                    pos + len,
+                ))
+            }
+            Some(TokenType::Keyword(Keyword::Static)) => {
+                TokenHolder::expect(tokens, 1, TokenType::Keyword(Keyword::For))?;
+                let name = match &TokenHolder::expect(tokens, 2, TokenType::Identifier)?.token {
+                    Token::Identifier(name) => name.clone(),
+                    _ => unreachable!(),
+                };
+                TokenHolder::expect(tokens, 3, TokenType::Keyword(Keyword::In))?;
+                let mut pos = 4;
+                let (from, len) = parse_static_integer(&tokens[pos..])?;
+                pos += len;
+                TokenHolder::expect(
+                    tokens,
+                    pos,
+                    TokenType::ControlCharacter(ControlCharacter::Range),
+                )?;
+                pos += 1;
+                let (to, len) = parse_static_integer(&tokens[pos..])?;
+                pos += len;
+                let mut step = 1;
+                if tokens
+                    .get(pos)
+                    .is_some_and(|t| t.token == Token::Identifier(String::from("step")))
+                {
+                    let (value, len) = parse_static_integer(&tokens[pos + 1..])?;
+                    step = value;
+                    pos += len + 1;
+                }
+                let (code_block, len) = CodeBlock::parse(start + pos, &tokens[pos..])?;
+                Ok((
+                    Statement::StaticFor(name, from, to, step, code_block),
+                    pos + len,
+                ))
+            }
+            Some(TokenType::Keyword(keyword @ (Keyword::Break | Keyword::Continue))) => {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                )?;
+                let statement = match keyword {
+                    Keyword::Break => Statement::Break,
+                    _ => Statement::Continue,
+                };
+                Ok((statement, 2))
+            }
+            Some(TokenType::Keyword(Keyword::Return)) => {
+                if tokens.get(1).is_some_and(|t| {
+                    t.token
+                        .is(TokenType::ControlCharacter(ControlCharacter::EndOfLine))
+                }) {
+                    return Ok((Statement::Return(None), 2));
+                }
+                let (value, len) = Value::parse(start + 1, &tokens[1..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 1,
+                    TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                )?;
+                Ok((Statement::Return(Some(value)), len + 2))
+            }
+            _ => {
+                let (expression, len) = Expression::parse(start, tokens)?;
+                TokenHolder::expect(
+                    tokens,
+                    len,
+                    TokenType::ControlCharacter(ControlCharacter::EndOfLine),
+                )?;
+                Ok((Statement::Expression(expression), len + 1))
+            }
+        }
+    }
+}
+
+/// An integer literal bound of a static for, optionally negated.
+fn parse_static_integer(tokens: &[TokenHolder]) -> Result<(i32, usize), ParseError> {
+    let negative = tokens
+        .first()
+        .is_some_and(|t| t.token.is(TokenType::Operator(Operator::Minus)));
+    let pos = usize::from(negative);
+    let holder = TokenHolder::expect(tokens, pos, TokenType::Integer)?;
+    let Token::Integer(value) = holder.token else {
+        unreachable!()
+    };
+    let value = if negative { -value } else { value };
+    match i32::try_from(value) {
+        Ok(value) => Ok((value, pos + 1)),
+        Err(_) => Err(ParseError {
+            at: holder.clone(),
+            expected: Vec::from([TokenType::Integer]),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
-    VarDec(String, Option<EFSType>, EFSValueType),
-    Assign(String, EFSValueType),
+    VarDec(String, Option<EFSType>, Value),
+    /// `name = value`, `name += value` and the other operators parse as
+    /// `name = name + value`.
+    Assign(String, Value),
+    /// `target.field = value` or `target.field += value`, with `-=`, `*=` and `/=`.
+    /// Assigns a field of a struct or typed dict, or a score of entities, `@s.kills += 1`.
+    AssignField(Value, String, Option<Operator>, Value),
+    Value(Value),
+}
+
+impl ParserItem for Expression {
+    fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        if tokens
+            .first()
+            .is_some_and(|t| t.token.is(TokenType::Keyword(Keyword::VarDeceleration)))
+        {
+            let (assignment, len) = Assignment::parse(start + 1, &tokens[1..])?;
+            return Ok((
+                Expression::VarDec(assignment.name, assignment.var_type, assignment.var),
+                len + 1,
+            ));
+        }
+
+        if let (
+            Some(Token::Identifier(name)),
+            Some(Token::ControlCharacter(ControlCharacter::Assign)),
+        ) = (
+            tokens.first().map(|t| &t.token),
+            tokens.get(1).map(|t| &t.token),
+        ) {
+            let (value, len) = Value::parse(start + 2, &tokens[2..])?;
+            return Ok((Expression::Assign(name.clone(), value), len + 2));
+        }
+
+        if let Ok((target, len)) = Value::parse_postfix(start, tokens) {
+            if let Some((op, op_len)) = assignment_operator(&tokens[len..]) {
+                let pos = len + op_len;
+                let (value, len) = Value::parse(start + pos, &tokens[pos..])?;
+                let expression = match (target, op) {
+                    (Value::Field(target, field), op) => {
+                        Expression::AssignField(*target, field, op, value)
+                    }
+                    (Value::Identifier(name), Some(op)) => {
+                        let math = Math {
+                            left: Value::Identifier(name.clone()),
+                            op,
+                            right: value,
+                        };
+                        Expression::Assign(name, Value::Math(Box::new(math)))
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            at: tokens[pos - op_len].clone(),
+                            expected: vec![TokenType::ControlCharacter(
+                                ControlCharacter::EndOfLine,
+                            )],
+                        })
+                    }
+                };
+                return Ok((expression, pos + len));
+            }
+        }
+
+        let (value, len) = Value::parse(start, tokens)?;
+        Ok((Expression::Value(value), len))
+    }
+}
+
+/// `=`, or `+=`, `-=`, `*=` and `/=` written without a space, with the number of
+/// tokens it takes.
+fn assignment_operator(tokens: &[TokenHolder]) -> Option<(Option<Operator>, usize)> {
+    let assign = |holder: &TokenHolder| {
+        holder
+            .token
+            .is(TokenType::ControlCharacter(ControlCharacter::Assign))
+    };
+    let first = tokens.first()?;
+    if assign(first) {
+        return Some((None, 1));
+    }
+    match (&first.token, tokens.get(1)) {
+        (
+            Token::Operator(
+                op @ (Operator::Plus | Operator::Minus | Operator::Multi | Operator::Div),
+            ),
+            Some(next),
+        ) if assign(next) && next.start == first.start + first.length => {
+            Some((Some(op.clone()), 2))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub block: CodeBlock,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `Enum::Variant(a, _)`, binds the payload to names, `None` for a `_`.
+    Variant(String, String, Vec<Option<String>>),
+    /// `_`, matches anything.
+    Wildcard,
+}
+
+impl ParserItem for Pattern {
+    fn parse(_start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let holder =
+            TokenHolder::expect_many(tokens, 0, &[TokenType::Identifier, TokenType::TypeName])?;
+        match &holder.token {
+            Token::Identifier(_) => {}
+            Token::TypeName(TypeName::None) => return Ok((Pattern::Wildcard, 1)),
+            _ => {
+                return Err(ParseError {
+                    at: holder.clone(),
+                    expected: vec![TokenType::Identifier],
+                })
+            }
+        }
+        TokenHolder::expect(
+            tokens,
+            1,
+            TokenType::ControlCharacter(ControlCharacter::Scope),
+        )?;
+        let (mut path, mut pos) = parse_path(tokens)?;
+        let variant = path.pop().unwrap();
+        let name = path.join("::");
+
+        let mut bindings = Vec::new();
+        if tokens.get(pos).is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::LeftParen))
+        }) {
+            pos += 1;
+            loop {
+                let holder = TokenHolder::expect_many(
+                    tokens,
+                    pos,
+                    &[TokenType::Identifier, TokenType::TypeName],
+                )?;
+                match &holder.token {
+                    Token::Identifier(name) => bindings.push(Some(name.clone())),
+                    Token::TypeName(TypeName::None) => bindings.push(None),
+                    _ => {
+                        return Err(ParseError {
+                            at: holder.clone(),
+                            expected: vec![TokenType::Identifier],
+                        })
+                    }
+                }
+                pos += 1;
+                if !TokenHolder::expect_many(
+                    tokens,
+                    pos,
+                    &[
+                        TokenType::ControlCharacter(ControlCharacter::Comma),
+                        TokenType::ControlCharacter(ControlCharacter::RightParen),
+                    ],
+                )?
+                .token
+                .is(TokenType::ControlCharacter(ControlCharacter::Comma))
+                {
+                    break;
+                }
+                pos += 1;
+            }
+            pos += 1;
+        }
+        Ok((Pattern::Variant(name, variant, bindings), pos))
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Value(EFSValueType),
     Math(Box<Math>),
     Identifier(String),
     List(Vec<Value>),
     Dict(HashMap<String, Value>),
-    Struct(HashMap<String, Value>),
+    /// `Name { field: value }`
+    Struct(String, HashMap<String, Value>),
+    Call(String, Vec<Value>),
+    /// `value as type`, see `EFSValueType::convert` for how numbers convert.
+    Cast(Box<Value>, EFSType),
+    /// `value.field` of a struct or dict.
+    Field(Box<Value>, String),
+    /// `value?.field`, `None` when the optional value is.
+    OptionalField(Box<Value>, String),
+    /// `list[index]`, negative indices count from the end.
+    Index(Box<Value>, Box<Value>),
+    /// `Enum::Variant` or `Enum::Variant(payload, ...)`, `Name::function(args)` of an
+    /// `impl` block parses the same until `compiler::resolve` tells them apart.
+    Variant(String, String, Vec<Value>),
+    /// `value.method(args)`, the method of the type of `value`.
+    MethodCall(Box<Value>, String, Vec<Value>),
+    /// `text!(part, ...)`, a JSON text component. Parts are styled with methods,
+    /// `"hi".bold()`, see `compiler::text`.
+    Text(Vec<Value>),
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Math {
     pub left: Value,
     pub op: Operator,
     pub right: Value,
 }
+
+impl ParserItem for Value {
+    fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        Self::parse_binary(start, tokens, 0)
+    }
+}
+
+impl Value {
+    /// Precedence climbing over `Operator::precedence`, all operators are left associative.
+    fn parse_binary(
+        start: usize,
+        tokens: &[TokenHolder],
+        min_precedence: u8,
+    ) -> Result<(Self, usize), ParseError> {
+        let (mut left, mut pos) = Self::parse_cast(start, tokens)?;
+
+        while let Some(Token::Operator(op)) = tokens.get(pos).map(|t| &t.token) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            let op = op.clone();
+            let (right, len) =
+                Self::parse_binary(start + pos + 1, &tokens[pos + 1..], op.precedence() + 1)?;
+            left = Value::Math(Box::new(Math { left, op, right }));
+            pos += len + 1;
+        }
+
+        Ok((left, pos))
+    }
+
+    /// A primary followed by any number of `as type`, which binds tighter than any operator.
+    fn parse_cast(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let (mut value, mut pos) = Self::parse_postfix(start, tokens)?;
+        while tokens
+            .get(pos)
+            .is_some_and(|t| t.token.is(TokenType::Keyword(Keyword::As)))
+        {
+            let (var_type, len) = EFSType::parse(start + pos + 1, &tokens[pos + 1..])?;
+            value = Value::Cast(Box::new(value), var_type);
+            pos += len + 1;
+        }
+        Ok((value, pos))
+    }
+
+    /// A primary followed by any number of `.field`, `?.field` and `[index]`.
+    fn parse_postfix(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let (mut value, mut pos) = Self::parse_primary(start, tokens)?;
+        loop {
+            let optional = match tokens.get(pos).map(|t| &t.token) {
+                Some(Token::ControlCharacter(ControlCharacter::Dot)) => false,
+                Some(Token::ControlCharacter(ControlCharacter::OptionalDot)) => true,
+                Some(Token::ControlCharacter(ControlCharacter::LBracket)) => {
+                    let (index, len) = Self::parse(start + pos + 1, &tokens[pos + 1..])?;
+                    TokenHolder::expect(
+                        tokens,
+                        pos + len + 1,
+                        TokenType::ControlCharacter(ControlCharacter::RBracket),
+                    )?;
+                    value = Value::Index(Box::new(value), Box::new(index));
+                    pos += len + 2;
+                    continue;
+                }
+                _ => break,
+            };
+            let field = match &TokenHolder::expect(tokens, pos + 1, TokenType::Identifier)?.token {
+                Token::Identifier(field) => field.clone(),
+                _ => unreachable!(),
+            };
+            let call = !optional
+                && tokens.get(pos + 2).is_some_and(|t| {
+                    t.token
+                        .is(TokenType::ControlCharacter(ControlCharacter::LeftParen))
+                });
+            if call {
+                let (args, len) = Self::parse_list(
+                    start + pos + 3,
+                    &tokens[pos + 3..],
+                    ControlCharacter::RightParen,
+                )?;
+                value = Value::MethodCall(Box::new(value), field, args);
+                pos += len + 3;
+                continue;
+            }
+            value = if optional {
+                Value::OptionalField(Box::new(value), field)
+            } else {
+                Value::Field(Box::new(value), field)
+            };
+            pos += 2;
+        }
+        Ok((value, pos))
+    }
+
+    fn parse_primary(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let holder = TokenHolder::expect_many(
+            tokens,
+            0,
+            &[
+                TokenType::Integer,
+                TokenType::Float,
+                TokenType::String,
+                TokenType::NBT,
+                TokenType::Selector,
+                TokenType::Identifier,
+                TokenType::Keyword(Keyword::True),
+                TokenType::Keyword(Keyword::False),
+                TokenType::Keyword(Keyword::None),
+                TokenType::Keyword(Keyword::Text),
+                TokenType::Operator(Operator::Minus),
+                TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                TokenType::ControlCharacter(ControlCharacter::LBracket),
+                TokenType::ControlCharacter(ControlCharacter::LBrace),
+            ],
+        )?;
+
+        match &holder.token {
+            Token::Integer(n) => Ok((
+                Value::Value(match i32::try_from(*n) {
+                    Ok(n) => EFSValueType::Number(n),
+                    Err(_) => EFSValueType::Long(*n),
+                }),
+                1,
+            )),
+            Token::Float(n) => Ok((Value::Value(EFSValueType::Double(*n)), 1)),
+            Token::String(s) => Ok((Value::Value(EFSValueType::String(s.clone())), 1)),
+            Token::NBT(value) => Ok((Value::Value(value.clone()), 1)),
+            Token::Selector(selector) => {
+                Ok((Value::Value(EFSValueType::Selector(selector.clone())), 1))
+            }
+            Token::Identifier(name) => {
+                let (mut path, pos) = parse_path(tokens)?;
+                if path.len() > 1 {
+                    let next = tokens.get(pos).map(|t| t.token.token_type());
+                    if next == Some(TokenType::ControlCharacter(ControlCharacter::LBrace)) {
+                        let (fields, len) =
+                            Self::parse_fields(start + pos + 1, &tokens[pos + 1..])?;
+                        return Ok((Value::Struct(path.join("::"), fields), pos + len + 1));
+                    }
+                    let member = path.pop().unwrap();
+                    let mut args = Vec::new();
+                    let mut len = pos;
+                    if next == Some(TokenType::ControlCharacter(ControlCharacter::LeftParen)) {
+                        let (parsed, args_len) = Self::parse_list(
+                            start + pos + 1,
+                            &tokens[pos + 1..],
+                            ControlCharacter::RightParen,
+                        )?;
+                        args = parsed;
+                        len += args_len + 1;
+                    }
+                    return Ok((Value::Variant(path.join("::"), member, args), len));
+                }
+                if tokens.get(1).is_some_and(|t| {
+                    t.token
+                        .is(TokenType::ControlCharacter(ControlCharacter::LeftParen))
+                }) {
+                    let (args, len) =
+                        Self::parse_list(start + 2, &tokens[2..], ControlCharacter::RightParen)?;
+                    return Ok((Value::Call(name.clone(), args), len + 2));
+                }
+                if tokens.get(1).is_some_and(|t| {
+                    t.token
+                        .is(TokenType::ControlCharacter(ControlCharacter::LBrace))
+                }) {
+                    let (fields, len) = Self::parse_fields(start + 2, &tokens[2..])?;
+                    return Ok((Value::Struct(name.clone(), fields), len + 2));
+                }
+                Ok((Value::Identifier(name.clone()), 1))
+            }
+            Token::Keyword(Keyword::True) => Ok((Value::Value(EFSValueType::Bool(true)), 1)),
+            Token::Keyword(Keyword::False) => Ok((Value::Value(EFSValueType::Bool(false)), 1)),
+            Token::Keyword(Keyword::None) => Ok((Value::Value(EFSValueType::None), 1)),
+            Token::Keyword(Keyword::Text) => {
+                TokenHolder::expect(
+                    tokens,
+                    1,
+                    TokenType::ControlCharacter(ControlCharacter::LeftParen),
+                )?;
+                let (parts, len) =
+                    Self::parse_list(start + 2, &tokens[2..], ControlCharacter::RightParen)?;
+                Ok((Value::Text(parts), len + 2))
+            }
+            Token::Operator(Operator::Minus) => {
+                let (value, len) = Self::parse_postfix(start + 1, &tokens[1..])?;
+                let negated = match value {
+                    Value::Value(EFSValueType::Number(n)) => Value::Value(EFSValueType::Number(-n)),
+                    Value::Value(EFSValueType::Long(n)) => Value::Value(EFSValueType::Long(-n)),
+                    Value::Value(EFSValueType::Double(n)) => Value::Value(EFSValueType::Double(-n)),
+                    value => Value::Math(Box::new(Math {
+                        left: Value::Value(EFSValueType::Number(0)),
+                        op: Operator::Minus,
+                        right: value,
+                    })),
+                };
+                Ok((negated, len + 1))
+            }
+            Token::ControlCharacter(ControlCharacter::LeftParen) => {
+                let (value, len) = Self::parse(start + 1, &tokens[1..])?;
+                TokenHolder::expect(
+                    tokens,
+                    len + 1,
+                    TokenType::ControlCharacter(ControlCharacter::RightParen),
+                )?;
+                Ok((value, len + 2))
+            }
+            Token::ControlCharacter(ControlCharacter::LBracket) => {
+                let (values, len) =
+                    Self::parse_list(start + 1, &tokens[1..], ControlCharacter::RBracket)?;
+                Ok((Value::List(values), len + 1))
+            }
+            Token::ControlCharacter(ControlCharacter::LBrace) => {
+                let (fields, len) = Self::parse_fields(start + 1, &tokens[1..])?;
+                Ok((Value::Dict(fields), len + 1))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parses comma separated values up to and including the `end` character.
+    /// `key: value` pairs up to and including the closing `}`, keys are identifiers or strings.
+    fn parse_fields(
+        start: usize,
+        tokens: &[TokenHolder],
+    ) -> Result<(HashMap<String, Self>, usize), ParseError> {
+        let mut fields = HashMap::new();
+        let mut pos = 0;
+
+        if tokens.first().is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::RBrace))
+        }) {
+            return Ok((fields, 1));
+        }
+
+        loop {
+            let key = match &TokenHolder::expect_many(
+                tokens,
+                pos,
+                &[TokenType::Identifier, TokenType::String],
+            )?
+            .token
+            {
+                Token::Identifier(key) | Token::String(key) => key.clone(),
+                _ => unreachable!(),
+            };
+            TokenHolder::expect(
+                tokens,
+                pos + 1,
+                TokenType::ControlCharacter(ControlCharacter::TypeClarify),
+            )?;
+            pos += 2;
+            let (value, len) = Self::parse(start + pos, &tokens[pos..])?;
+            fields.insert(key, value);
+            pos += len;
+
+            let holder = TokenHolder::expect_many(
+                tokens,
+                pos,
+                &[
+                    TokenType::ControlCharacter(ControlCharacter::Comma),
+                    TokenType::ControlCharacter(ControlCharacter::RBrace),
+                ],
+            )?;
+            pos += 1;
+            if holder
+                .token
+                .is(TokenType::ControlCharacter(ControlCharacter::RBrace))
+            {
+                return Ok((fields, pos));
+            }
+        }
+    }
+
+    pub(crate) fn parse_list(
+        start: usize,
+        tokens: &[TokenHolder],
+        end: ControlCharacter,
+    ) -> Result<(Vec<Self>, usize), ParseError> {
+        let mut values = Vec::new();
+        let mut pos = 0;
+
+        if tokens
+            .first()
+            .is_some_and(|t| t.token.is(TokenType::ControlCharacter(end.clone())))
+        {
+            return Ok((values, 1));
+        }
+
+        loop {
+            let (value, len) = Self::parse(start + pos, &tokens[pos..])?;
+            values.push(value);
+            pos += len;
+
+            let holder = TokenHolder::expect_many(
+                tokens,
+                pos,
+                &[
+                    TokenType::ControlCharacter(ControlCharacter::Comma),
+                    TokenType::ControlCharacter(end.clone()),
+                ],
+            )?;
+            pos += 1;
+            if holder.token.is(TokenType::ControlCharacter(end.clone())) {
+                return Ok((values, pos));
+            }
+        }
+    }
+}
@@ -7,7 +7,6 @@ use super::token::TokenHolder;
 pub struct Lexer {
     text: Vec<char>,
     pos: usize,
-    history: Vec<TokenHolder>,
 }
 
 impl Lexer {
@@ -15,12 +14,11 @@ impl Lexer {
         Self {
             text: text.chars().collect(),
             pos: 0,
-            history: Vec::new() ,
         }
     }
 
     fn skip_whitespace(&mut self) {
-        while self.text.get(self.pos).map_or(false, |c| c.is_whitespace()) {
+        while self.text.get(self.pos).is_some_and(|c| c.is_whitespace()) {
             self.pos += 1;
         }
     }
@@ -41,8 +39,6 @@ impl Lexer {
     }
 }
 
-
-
 #[derive(Debug)]
 pub struct LexError {
     at: usize,
@@ -60,4 +56,3 @@ impl Display for LexError {
 }
 
 impl Error for LexError {}
-
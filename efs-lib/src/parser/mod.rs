@@ -2,7 +2,7 @@ use std::{error::Error, fmt::Display};
 
 use self::{
     lexer::Lexer,
-    token::{TokenHolder, TokenType},
+    token::{ControlCharacter, Token, TokenHolder, TokenType},
 };
 
 pub mod ast;
@@ -32,7 +32,7 @@ impl Display for ParseError {
         )?;
         for i in 0..self.expected.len() {
             write!(f, "{:?}", self.expected[i])?;
-            if i == self.expected.len() - 2 {
+            if self.expected.len() >= 2 && i == self.expected.len() - 2 {
                 write!(f, ", or ")?;
             } else if i != self.expected.len() - 1 {
                 write!(f, ", ")?;
@@ -45,28 +45,46 @@ impl Display for ParseError {
 impl Error for ParseError {}
 
 pub struct Parser {
-    text: String,
-    lexer: Lexer,
-    current_token: TokenHolder,
+    tokens: Vec<TokenHolder>,
 }
 
 impl Parser {
     pub fn new(text: String) -> anyhow::Result<Self> {
-        let mut lexer = Lexer::new(text.clone());
-        let current_token = lexer.next_token()?;
-        Ok(Self {
-            text: text,
-            lexer,
-            current_token,
-        })
+        let mut lexer = Lexer::new(text);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token()?;
+            if token.token.is(TokenType::EOI) {
+                break;
+            }
+            tokens.push(token);
+        }
+        Ok(Self { tokens })
+    }
+
+    pub fn parse<T: ParserItem>(&self) -> Result<T, ParseError> {
+        T::parse(0, &self.tokens).map(|(item, _)| item)
     }
+}
 
-    fn error(&self, expected: &[TokenType]) -> anyhow::Error {
-        anyhow::anyhow!(
-            "Invalid syntax, got: {:?}, expected: {:?}, at {}",
-            self.current_token.token,
-            expected,
-            self.current_token.start
-        )
+/// Parses `name::name::...` into its segments.
+pub(crate) fn parse_path(tokens: &[TokenHolder]) -> Result<(Vec<String>, usize), ParseError> {
+    let mut path = Vec::new();
+    let mut pos = 0;
+    loop {
+        let Token::Identifier(segment) =
+            &TokenHolder::expect(tokens, pos, TokenType::Identifier)?.token
+        else {
+            unreachable!()
+        };
+        path.push(segment.clone());
+        pos += 1;
+        if !tokens.get(pos).is_some_and(|t| {
+            t.token
+                .is(TokenType::ControlCharacter(ControlCharacter::Scope))
+        }) {
+            return Ok((path, pos));
+        }
+        pos += 1;
     }
 }
@@ -1,13 +1,12 @@
-use std::{fmt::Display, path::PathBuf};
+use std::fmt::Display;
 
 use enum_iterator::{all, Sequence};
 use strum::Display;
 
-use super::{types::EFSType, ParseError};
-
-
-
-
+use super::{
+    types::{EFSType, EFSValueType},
+    ParseError,
+};
 
 #[derive(Debug, Clone)]
 pub struct TokenHolder {
@@ -35,7 +34,7 @@ impl TokenHolder {
         }
     }
     pub fn is_many(&self, expected: &[TokenType]) -> Result<&Self, ParseError> {
-        if self.token.is_many(expected.clone()) {
+        if self.token.is_many(expected) {
             Ok(self)
         } else {
             Err(ParseError {
@@ -62,6 +61,23 @@ impl TokenHolder {
             })
         }
     }
+    /// Checks the token at `pos`, reporting the end of the previous token when
+    /// `tokens` runs out.
+    pub fn expect(tokens: &[Self], pos: usize, expected: TokenType) -> Result<&Self, ParseError> {
+        Self::is_or_eoi(tokens.get(pos), expected, Self::end_of(tokens, pos))
+    }
+    pub fn expect_many<'a>(
+        tokens: &'a [Self],
+        pos: usize,
+        expected: &[TokenType],
+    ) -> Result<&'a Self, ParseError> {
+        Self::is_many_or_eoi(tokens.get(pos), expected, Self::end_of(tokens, pos))
+    }
+    fn end_of(tokens: &[Self], pos: usize) -> usize {
+        tokens[..pos.min(tokens.len())]
+            .last()
+            .map_or(0, |token| token.start + token.length)
+    }
     pub fn is_many_or_eoi<'a>(
         s: Option<&'a Self>,
         expected: &[TokenType],
@@ -91,7 +107,14 @@ pub enum Token {
     Keyword(Keyword),
     ControlCharacter(ControlCharacter),
     Operator(Operator),
-    FilePath(PathBuf),
+    NBT(EFSValueType),
+    /// The lines of an `mc! { ... }` block.
+    Commands(Vec<String>),
+    /// A target selector like `@a[tag=x]`.
+    Selector(String),
+    /// A relative coordinate, `~` or `~-1.5`. Local coordinates start with `^`, which
+    /// is `Operator::Xor` on its own.
+    Coordinate(String),
     EOI,
     TypeName(TypeName),
 }
@@ -105,7 +128,10 @@ pub enum TokenType {
     Keyword(Keyword),
     ControlCharacter(ControlCharacter),
     Operator(Operator),
-    FilePath,
+    NBT,
+    Commands,
+    Selector,
+    Coordinate,
     EOI,
     TypeName,
 }
@@ -121,7 +147,10 @@ impl Token {
             Token::Operator(op) => TokenType::Operator(op.clone()),
             Token::EOI => TokenType::EOI,
             Token::String(_) => TokenType::String,
-            Token::FilePath(_) => TokenType::FilePath,
+            Token::NBT(_) => TokenType::NBT,
+            Token::Commands(_) => TokenType::Commands,
+            Token::Selector(_) => TokenType::Selector,
+            Token::Coordinate(_) => TokenType::Coordinate,
             Token::TypeName(_) => TokenType::TypeName,
         }
     }
@@ -146,11 +175,18 @@ impl Token {
         }
 
         let results = [
-            Self::parse_path(text),
             Self::parse_identifier(text),
             Self::parse_number(text),
             Self::parse_string(text),
-            Keyword::parse(text).map(|res| (Token::Keyword(res.0), res.1)),
+            Self::parse_nbt(text),
+            Self::parse_commands(text),
+            Self::parse_selector(text),
+            Self::parse_coordinate(text),
+            Keyword::parse(text)
+                // `text!=` is `text != ...`.
+                .filter(|res| res.0 != Keyword::Text || text.get(res.1) != Some(&'='))
+                .map(|res| (Token::Keyword(res.0), res.1)),
+            TypeName::parse(text).map(|res| (Token::TypeName(res.0), res.1)),
             ControlCharacter::parse(text).map(|res| (Token::ControlCharacter(res.0), res.1)),
             Operator::parse(text).map(|res| (Token::Operator(res.0), res.1)),
         ];
@@ -166,37 +202,19 @@ impl Token {
             .clone()
     }
 
-    fn parse_path(text: &[char]) -> Option<(Self, usize)> {
-        let mut path = String::default();
-        let mut pos = 0;
-
-        while text.get(pos).map_or(false, |c| {
-            c.is_alphanumeric() || c == &'/' || c == &'.' || c == &'_'
-        }) {
-            path.push(text.get(pos)?.clone());
-            pos += 1;
-        }
-
-        if path.is_empty() {
-            return None;
-        }
-
-        Some((Self::FilePath(path.clone().into()), path.chars().count()))
-    }
-
     fn parse_identifier(text: &[char]) -> Option<(Self, usize)> {
         let mut ident = String::default();
         let mut pos = 0;
 
         if text.get(pos).map(|c| c.is_alphabetic() || c == &'_')? {
-            ident.push(text.get(pos)?.clone());
+            ident.push(*text.get(pos)?);
             pos += 1;
 
             while text
                 .get(pos)
-                .map_or(false, |c| c.is_alphanumeric() || c == &'_')
+                .is_some_and(|c| c.is_alphanumeric() || c == &'_')
             {
-                ident.push(text.get(pos)?.clone());
+                ident.push(*text.get(pos)?);
                 pos += 1;
             }
         }
@@ -214,7 +232,7 @@ impl Token {
             pos += 1;
 
             while text.get(pos) != Some(&'"') {
-                string.push(text.get(pos)?.clone());
+                string.push(*text.get(pos)?);
                 pos += 1;
             }
 
@@ -224,27 +242,134 @@ impl Token {
         }
     }
 
-    fn parse_number(text: &[char]) -> Option<(Self, usize)> {
-        let mut result = String::default();
+    /// An SNBT compound or list written inline as `nbt { ... }` or `nbt [ ... ]`.
+    fn parse_nbt(text: &[char]) -> Option<(Self, usize)> {
+        let keyword = ['n', 'b', 't'];
+        if !text.starts_with(&keyword) {
+            return None;
+        }
 
-        let mut pos = 0;
+        let mut pos = keyword.len();
+        while text.get(pos).is_some_and(|c| c.is_whitespace()) {
+            pos += 1;
+        }
+        if !matches!(text.get(pos), Some('{') | Some('[')) {
+            return None;
+        }
+
+        let (value, length) = EFSValueType::parse_snbt(&text[pos..]).ok()?;
+        Some((Self::NBT(value), pos + length))
+    }
+
+    /// Raw commands written as `mc! { ... }`, one command per line. Braces inside the
+    /// commands have to be balanced unless they are in a `"` string.
+    fn parse_commands(text: &[char]) -> Option<(Self, usize)> {
+        let keyword = ['m', 'c', '!'];
+        if !text.starts_with(&keyword) {
+            return None;
+        }
 
-        if text.get(pos)? == &'-' {
-            result.push('-');
+        let mut pos = keyword.len();
+        while text.get(pos).is_some_and(|c| c.is_whitespace()) {
             pos += 1;
         }
+        if text.get(pos) != Some(&'{') {
+            return None;
+        }
+        let body = pos + 1;
+        let mut depth = 0;
+        let mut quoted = false;
+        loop {
+            pos += 1;
+            match (*text.get(pos)?, quoted) {
+                ('\\', true) => pos += 1,
+                ('"', _) => quoted = !quoted,
+                ('{', false) => depth += 1,
+                ('}', false) if depth == 0 => break,
+                ('}', false) => depth -= 1,
+                _ => {}
+            }
+        }
+
+        let commands = text[body..pos]
+            .iter()
+            .collect::<String>()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        Some((Self::Commands(commands), pos + 1))
+    }
+
+    /// `@a`, `@e[type=pig,tag=x]` and so on, brackets inside the arguments have to be
+    /// balanced unless they are in a string.
+    fn parse_selector(text: &[char]) -> Option<(Self, usize)> {
+        if text.first() != Some(&'@') || !text.get(1).is_some_and(|c| "parsen".contains(*c)) {
+            return None;
+        }
+        let mut pos = 2;
+        if text.get(pos) == Some(&'[') {
+            let mut depth = 0;
+            let mut quote = None;
+            loop {
+                match (*text.get(pos)?, quote) {
+                    ('\\', Some(_)) => pos += 1,
+                    (c, Some(q)) if c == q => quote = None,
+                    (_, Some(_)) => {}
+                    (c @ ('"' | '\''), None) => quote = Some(c),
+                    ('[' | '{', None) => depth += 1,
+                    (']' | '}', None) => depth -= 1,
+                    _ => {}
+                }
+                pos += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        Some((Self::Selector(text[..pos].iter().collect()), pos))
+    }
 
-        while text.get(pos).map_or(false, |char| char.is_ascii_digit()) {
-            result.push(text.get(pos)?.clone());
+    /// `~` followed by an optional number.
+    fn parse_coordinate(text: &[char]) -> Option<(Self, usize)> {
+        if text.first() != Some(&'~') {
+            return None;
+        }
+        let mut pos = 1;
+        if text.get(pos) == Some(&'-') {
+            pos += 1;
+        }
+        while text
+            .get(pos)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+        {
+            pos += 1;
+        }
+        if text[pos - 1] == '-' {
+            pos -= 1;
+        }
+        Some((Self::Coordinate(text[..pos].iter().collect()), pos))
+    }
+
+    fn parse_number(text: &[char]) -> Option<(Self, usize)> {
+        let mut result = String::default();
+
+        let mut pos = 0;
+
+        while text.get(pos).is_some_and(|char| char.is_ascii_digit()) {
+            result.push(*text.get(pos)?);
             pos += 1;
         }
 
-        if text.get(pos).map_or(false, |char| char == &'.') {
+        // `0..10` is a range, not the float `0.` followed by `.10`.
+        if text.get(pos).is_some_and(|char| char == &'.') && text.get(pos + 1) != Some(&'.') {
             result.push('.');
             pos += 1;
 
-            while text.last().map_or(false, |char| char.is_ascii_digit()) {
-                result.push(text.get(pos)?.clone());
+            while text.get(pos).is_some_and(|char| char.is_ascii_digit()) {
+                result.push(*text.get(pos)?);
+                pos += 1;
             }
 
             Some((Token::Float(result.parse().ok()?), result.chars().count()))
@@ -272,16 +397,36 @@ pub enum Keyword {
     UseFile,
     #[strum(serialize = "if")]
     If,
+    #[strum(serialize = "return")]
+    Return,
+    #[strum(serialize = "break")]
+    Break,
+    #[strum(serialize = "continue")]
+    Continue,
     #[strum(serialize = "struct")]
     Struct,
+    #[strum(serialize = "enum")]
+    Enum,
+    #[strum(serialize = "impl")]
+    Impl,
+    #[strum(serialize = "pub")]
+    Pub,
+    #[strum(serialize = "extern")]
+    Extern,
+    #[strum(serialize = "match")]
+    Match,
     #[strum(serialize = "in")]
     In,
+    #[strum(serialize = "as")]
+    As,
     #[strum(serialize = "true")]
     True,
     #[strum(serialize = "false")]
     False,
     #[strum(serialize = "None")]
     None,
+    #[strum(serialize = "text!")]
+    Text,
 }
 
 impl LexerType for Keyword {}
@@ -306,8 +451,10 @@ pub enum TypeName {
     String,
     #[strum(serialize = "bool")]
     Bool,
-    // #[strum(to_string = "List<{0}>")]
-    // List(Box<EFSType>),
+    #[strum(serialize = "List")]
+    List,
+    #[strum(serialize = "Option")]
+    Option,
     #[strum(serialize = "Dict")]
     Dict,
     #[strum(serialize = "NBTi8Array")]
@@ -316,11 +463,19 @@ pub enum TypeName {
     NBTIntArray,
     #[strum(serialize = "NBTi64Array")]
     NBTLongArray,
+    #[strum(serialize = "Selector")]
+    Selector,
+    #[strum(serialize = "Text")]
+    Text,
     #[strum(serialize = "_")]
     None,
 }
 
+impl LexerType for TypeName {}
+
 impl TypeName {
+    /// The type this name stands for on its own, `List`, `Dict` and `Option` without
+    /// type parameters hold values of any type.
     pub fn to_type(&self) -> EFSType {
         match self {
             TypeName::Number => EFSType::Number,
@@ -332,10 +487,14 @@ impl TypeName {
             TypeName::Double => EFSType::Double,
             TypeName::String => EFSType::String,
             TypeName::Bool => EFSType::Bool,
-            TypeName::Dict => EFSType::Dict,
+            TypeName::List => EFSType::List(Box::new(EFSType::None)),
+            TypeName::Dict => EFSType::Dict(Box::new(EFSType::None)),
+            TypeName::Option => EFSType::Option(Box::new(EFSType::None)),
             TypeName::NBTByteArray => EFSType::NBTByteArray,
             TypeName::NBTIntArray => EFSType::NBTIntArray,
             TypeName::NBTLongArray => EFSType::NBTLongArray,
+            TypeName::Selector => EFSType::Selector,
+            TypeName::Text => EFSType::Text,
             TypeName::None => EFSType::None,
         }
     }
@@ -355,8 +514,14 @@ pub enum ControlCharacter {
     TypeClarify,
     #[strum(serialize = ".")]
     Dot,
+    #[strum(serialize = "..")]
+    Range,
     #[strum(serialize = "::")]
     Scope,
+    #[strum(serialize = "?")]
+    Optional,
+    #[strum(serialize = "?.")]
+    OptionalDot,
     #[strum(serialize = "(")]
     LeftParen,
     #[strum(serialize = ")")]
@@ -371,6 +536,8 @@ pub enum ControlCharacter {
     RBrace,
     #[strum(serialize = "=")]
     Assign,
+    #[strum(serialize = "=>")]
+    MatchArm,
 }
 
 impl LexerType for ControlCharacter {}
@@ -410,23 +577,24 @@ pub enum Operator {
 }
 
 impl Operator {
+    /// How tightly the operator binds its operands, higher binds tighter.
     pub fn precedence(&self) -> u8 {
         match self {
-            Operator::Plus => 6,
-            Operator::Minus => 6,
-            Operator::Multi => 5,
-            Operator::Div => 5,
-            Operator::Equal => 2,
-            Operator::NotEqual => 2,
-            Operator::Less => 2,
-            Operator::LessOrEqual => 2,
-            Operator::Greater => 2,
-            Operator::GreaterOrEqual => 2,
+            Operator::Multi => 10,
+            Operator::Div => 10,
+            Operator::Plus => 9,
+            Operator::Minus => 9,
+            Operator::And => 8,
+            Operator::Xor => 7,
+            Operator::Or => 6,
+            Operator::Equal => 5,
+            Operator::NotEqual => 5,
+            Operator::Less => 5,
+            Operator::LessOrEqual => 5,
+            Operator::Greater => 5,
+            Operator::GreaterOrEqual => 5,
+            Operator::LogicalAnd => 4,
             Operator::LogicalOr => 3,
-            Operator::LogicalAnd => 3,
-            Operator::Or => 4,
-            Operator::And => 4,
-            Operator::Xor => 4,
         }
     }
 }
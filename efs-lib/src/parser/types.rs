@@ -1,7 +1,15 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use super::{
+    parse_path,
+    token::{ControlCharacter, Operator, Token, TokenHolder, TokenType, TypeName},
+    ParseError, ParserItem,
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum EFSType {
+    /// `num`, the type of integer literals. At runtime it is a 32 bit score like
+    /// `i32`, but a constant `num` also converts to any numeric type it fits in.
     Number,
     Byte,
     Short,
@@ -12,14 +20,173 @@ pub enum EFSType {
     String,
     Bool,
     Struct(String),
+    /// An enum without payloads, kept in a score as the index of its variant.
+    Enum(String),
+    /// An enum with payloads, kept in storage as `{variant: index, _0: payload, ...}`.
+    TaggedEnum(String),
+    /// `List<T>`, the element type is `None` while it is unknown.
     List(Box<EFSType>),
-    Dict,
+    /// `Dict<string, V>`, a compound with values of type `V`. Keys are always
+    /// strings and a `None` value type allows any value, like a plain `Dict`.
+    Dict(Box<EFSType>),
+    /// `Option<T>` or `T?`, a value that may be `None`. Absent values are a missing
+    /// NBT path in storage and `scoreboard::ABSENT` in a score.
+    Option(Box<EFSType>),
     NBTByteArray,
     NBTIntArray,
     NBTLongArray,
+    /// A target selector, kept in storage as its text.
+    Selector,
+    /// A JSON text component built by `text!`, kept in storage as its JSON.
+    Text,
     None,
 }
 
+impl EFSType {
+    /// Whether values of this type are kept in a scoreboard rather than in storage.
+    pub fn is_score_type(&self) -> bool {
+        match self {
+            EFSType::Option(var_type) => var_type.is_score_type(),
+            _ => matches!(
+                self,
+                EFSType::Number
+                    | EFSType::Byte
+                    | EFSType::Short
+                    | EFSType::Int
+                    | EFSType::Bool
+                    | EFSType::Enum(_)
+            ),
+        }
+    }
+
+    /// `Option<self>`, optional types stay as they are since `None` can only be absent once.
+    pub fn optional(self) -> EFSType {
+        match self {
+            EFSType::Option(_) => self,
+            _ => EFSType::Option(Box::new(self)),
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || matches!(self, EFSType::Float | EFSType::Double)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            EFSType::Number | EFSType::Byte | EFSType::Short | EFSType::Int | EFSType::Long
+        )
+    }
+
+    /// Position of a numeric type in the order values widen in, `Number` and `Int`
+    /// are both 32 bit.
+    fn rank(&self) -> usize {
+        match self {
+            EFSType::Byte => 0,
+            EFSType::Short => 1,
+            EFSType::Number | EFSType::Int => 2,
+            EFSType::Long => 3,
+            EFSType::Float => 4,
+            _ => 5,
+        }
+    }
+
+    /// The wider of two numeric types, the type arithmetic on both results in.
+    pub fn promote(&self, other: &EFSType) -> EFSType {
+        if other.rank() > self.rank() {
+            other.clone()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// Whether numbers of this type convert to `to` implicitly: integers widen to
+    /// wider integers, every integer widens to `f32` and `f64` and `f32` widens to
+    /// `f64`. Anything else needs an `as` cast.
+    pub fn widens_to(&self, to: &EFSType) -> bool {
+        self.is_numeric() && to.is_numeric() && self.rank() <= to.rank()
+    }
+
+    /// The NBT type used by `execute store` when writing this type into storage.
+    pub fn store_type(&self) -> Option<&'static str> {
+        match self {
+            EFSType::Byte | EFSType::Bool => Some("byte"),
+            EFSType::Short => Some("short"),
+            EFSType::Number | EFSType::Int | EFSType::Enum(_) => Some("int"),
+            EFSType::Long => Some("long"),
+            EFSType::Float => Some("float"),
+            EFSType::Double => Some("double"),
+            EFSType::Option(var_type) => var_type.store_type(),
+            _ => None,
+        }
+    }
+}
+
+impl ParserItem for EFSType {
+    fn parse(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let (mut var_type, mut pos) = Self::parse_named(start, tokens)?;
+        // `T?` is short for `Option<T>`.
+        while tokens.get(pos).is_some_and(|holder| {
+            holder
+                .token
+                .is(TokenType::ControlCharacter(ControlCharacter::Optional))
+        }) {
+            var_type = var_type.optional();
+            pos += 1;
+        }
+        Ok((var_type, pos))
+    }
+}
+
+impl EFSType {
+    fn parse_named(start: usize, tokens: &[TokenHolder]) -> Result<(Self, usize), ParseError> {
+        let holder = TokenHolder::is_many_or_eoi(
+            tokens.first(),
+            &[TokenType::TypeName, TokenType::Identifier],
+            start,
+        )?;
+        let parameter_start = matches!(
+            tokens.get(1).map(|holder| &holder.token),
+            Some(Token::Operator(Operator::Less))
+        );
+        match &holder.token {
+            Token::TypeName(type_name @ (TypeName::List | TypeName::Option)) => {
+                TokenHolder::expect(tokens, 1, TokenType::Operator(Operator::Less))?;
+                let (parameter, len) = EFSType::parse(start + 2, &tokens[2..])?;
+                TokenHolder::expect(tokens, len + 2, TokenType::Operator(Operator::Greater))?;
+                let var_type = match type_name {
+                    TypeName::List => EFSType::List(Box::new(parameter)),
+                    _ => parameter.optional(),
+                };
+                Ok((var_type, len + 3))
+            }
+            Token::TypeName(TypeName::Dict) if parameter_start => {
+                let key = TokenHolder::expect(tokens, 2, TokenType::TypeName)?;
+                if key.token != Token::TypeName(TypeName::String) {
+                    return Err(ParseError {
+                        at: key.clone(),
+                        expected: vec![TokenType::TypeName],
+                    });
+                }
+                TokenHolder::expect(
+                    tokens,
+                    3,
+                    TokenType::ControlCharacter(ControlCharacter::Comma),
+                )?;
+                let (value_type, len) = EFSType::parse(start + 4, &tokens[4..])?;
+                TokenHolder::expect(tokens, len + 4, TokenType::Operator(Operator::Greater))?;
+                Ok((EFSType::Dict(Box::new(value_type)), len + 5))
+            }
+            Token::TypeName(type_name) => Ok((type_name.to_type(), 1)),
+            // Named types are structs until `compiler::resolve` finds the enums among them.
+            Token::Identifier(_) => {
+                let (path, len) = parse_path(tokens)?;
+                Ok((EFSType::Struct(path.join("::")), len))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum EFSValueType {
@@ -38,5 +205,466 @@ pub enum EFSValueType {
     NBTByteArray(Vec<i8>),
     NBTIntArray(Vec<i32>),
     NBTLongArray(Vec<i64>),
+    Selector(String),
+    /// The JSON of a text component.
+    Text(String),
     None,
 }
+
+impl EFSValueType {
+    pub fn get_type(&self) -> EFSType {
+        match self {
+            EFSValueType::Number(_) => EFSType::Number,
+            EFSValueType::Byte(_) => EFSType::Byte,
+            EFSValueType::Short(_) => EFSType::Short,
+            EFSValueType::Int(_) => EFSType::Int,
+            EFSValueType::Long(_) => EFSType::Long,
+            EFSValueType::Float(_) => EFSType::Float,
+            EFSValueType::Double(_) => EFSType::Double,
+            EFSValueType::String(_) => EFSType::String,
+            EFSValueType::Bool(_) => EFSType::Bool,
+            EFSValueType::Struct(name, _) => EFSType::Struct(name.clone()),
+            EFSValueType::List(list) => EFSType::List(Box::new(
+                list.first().map_or(EFSType::None, EFSValueType::get_type),
+            )),
+            EFSValueType::Dict(fields) => {
+                let mut types = fields.values().map(EFSValueType::get_type);
+                let first = types.next().unwrap_or(EFSType::None);
+                if types.all(|var_type| var_type == first) {
+                    EFSType::Dict(Box::new(first))
+                } else {
+                    EFSType::Dict(Box::new(EFSType::None))
+                }
+            }
+            EFSValueType::NBTByteArray(_) => EFSType::NBTByteArray,
+            EFSValueType::NBTIntArray(_) => EFSType::NBTIntArray,
+            EFSValueType::NBTLongArray(_) => EFSType::NBTLongArray,
+            EFSValueType::Selector(_) => EFSType::Selector,
+            EFSValueType::Text(_) => EFSType::Text,
+            EFSValueType::None => EFSType::None,
+        }
+    }
+
+    /// Converts a number to another numeric type the way `as` does at runtime:
+    /// integers wrap to the narrower type, floats round down and saturate at the
+    /// bounds of an `i32` (`i64` for `Long`) before wrapping. A `Double` too large
+    /// for a `Float` does not convert, SNBT has no infinity. Other values only
+    /// convert to their own type.
+    pub fn convert(&self, to: &EFSType) -> Option<Self> {
+        if let EFSType::Option(var_type) = to {
+            return match self {
+                EFSValueType::None => Some(EFSValueType::None),
+                _ => self.convert(var_type),
+            };
+        }
+        let saturate = |n: f64| match to {
+            EFSType::Long => n.floor() as i64,
+            _ => n.floor() as i32 as i64,
+        };
+        let (int, float) = match self {
+            EFSValueType::Number(n) | EFSValueType::Int(n) => (*n as i64, *n as f64),
+            EFSValueType::Byte(n) => (*n as i64, *n as f64),
+            EFSValueType::Short(n) => (*n as i64, *n as f64),
+            EFSValueType::Long(n) => (*n, *n as f64),
+            EFSValueType::Float(n) => (saturate(*n as f64), *n as f64),
+            EFSValueType::Double(n) => (saturate(*n), *n),
+            EFSValueType::Bool(b) => (*b as i64, *b as i64 as f64),
+            EFSValueType::List(values) => {
+                let EFSType::List(element_type) = to else {
+                    return None;
+                };
+                return values
+                    .iter()
+                    .map(|value| value.convert(element_type))
+                    .collect::<Option<_>>()
+                    .map(EFSValueType::List);
+            }
+            EFSValueType::Dict(fields) => {
+                return match to {
+                    EFSType::Dict(value_type) if **value_type == EFSType::None => {
+                        Some(self.clone())
+                    }
+                    EFSType::Dict(value_type) => fields
+                        .iter()
+                        .map(|(key, value)| Some((key.clone(), value.convert(value_type)?)))
+                        .collect::<Option<_>>()
+                        .map(EFSValueType::Dict),
+                    _ => None,
+                };
+            }
+            // A string is a text component of its own, `"hi"` in JSON.
+            EFSValueType::String(s) if to == &EFSType::Text => {
+                return serde_json::to_string(s).ok().map(EFSValueType::Text)
+            }
+            _ => return (&self.get_type() == to).then(|| self.clone()),
+        };
+        Some(match to {
+            EFSType::Number => EFSValueType::Number(int as i32),
+            EFSType::Byte => EFSValueType::Byte(int as i8),
+            EFSType::Short => EFSValueType::Short(int as i16),
+            EFSType::Int => EFSValueType::Int(int as i32),
+            EFSType::Long => EFSValueType::Long(int),
+            EFSType::Float if (float as f32).is_infinite() => return None,
+            EFSType::Float => EFSValueType::Float(float as f32),
+            EFSType::Double => EFSValueType::Double(float),
+            EFSType::Bool => EFSValueType::Bool(int != 0),
+            _ => return None,
+        })
+    }
+}
+
+impl EFSValueType {
+    /// Serializes the value as SNBT, the text form of NBT used in commands.
+    /// Compound keys are sorted so the output is deterministic.
+    pub fn to_snbt(&self) -> String {
+        match self {
+            EFSValueType::Number(n) | EFSValueType::Int(n) => n.to_string(),
+            EFSValueType::Byte(n) => format!("{}b", n),
+            EFSValueType::Short(n) => format!("{}s", n),
+            EFSValueType::Long(n) => format!("{}L", n),
+            EFSValueType::Float(n) => format!("{}f", n),
+            EFSValueType::Double(n) => format!("{}d", n),
+            EFSValueType::String(s) | EFSValueType::Selector(s) | EFSValueType::Text(s) => {
+                quote_snbt(s)
+            }
+            EFSValueType::Bool(b) => b.to_string(),
+            EFSValueType::Struct(_, fields) | EFSValueType::Dict(fields) => {
+                let mut keys = fields.keys().collect::<Vec<_>>();
+                keys.sort();
+                let entries = keys
+                    .into_iter()
+                    .map(|key| format!("{}:{}", snbt_key(key), fields[key].to_snbt()))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(","))
+            }
+            EFSValueType::List(list) => format!(
+                "[{}]",
+                list.iter()
+                    .map(EFSValueType::to_snbt)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            EFSValueType::NBTByteArray(array) => format!(
+                "[B;{}]",
+                array
+                    .iter()
+                    .map(|n| format!("{}b", n))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            EFSValueType::NBTIntArray(array) => format!(
+                "[I;{}]",
+                array
+                    .iter()
+                    .map(i32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            EFSValueType::NBTLongArray(array) => format!(
+                "[L;{}]",
+                array
+                    .iter()
+                    .map(|n| format!("{}L", n))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            EFSValueType::None => String::from("{}"),
+        }
+    }
+
+    /// Parses a complete SNBT value. Compounds are read as `Dict`s and unsuffixed
+    /// integers and decimals as `Int` and `Double`, matching Minecraft.
+    pub fn from_snbt(text: &str) -> Result<Self, SNBTError> {
+        let text = text.chars().collect::<Vec<_>>();
+        let (value, len) = Self::parse_snbt(&text)?;
+        let mut parser = SNBTParser {
+            text: &text,
+            pos: len,
+        };
+        parser.skip_whitespace();
+        if parser.pos != text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// Parses one SNBT value from the start of `text`, returning it and the number
+    /// of characters it spans.
+    pub fn parse_snbt(text: &[char]) -> Result<(Self, usize), SNBTError> {
+        let mut parser = SNBTParser { text, pos: 0 };
+        let value = parser.value()?;
+        Ok((value, parser.pos))
+    }
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+fn snbt_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(is_unquoted_char) {
+        key.to_string()
+    } else {
+        quote_snbt(key)
+    }
+}
+
+fn quote_snbt(s: &str) -> String {
+    let quote = if s.contains('"') && !s.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    let mut quoted = String::from(quote);
+    for c in s.chars() {
+        if c == quote || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push(quote);
+    quoted
+}
+
+#[derive(Debug)]
+pub struct SNBTError {
+    pub at: usize,
+    pub message: String,
+}
+
+impl Display for SNBTError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "snbt error at: {}. {}", self.at, self.message)
+    }
+}
+
+impl Error for SNBTError {}
+
+struct SNBTParser<'a> {
+    text: &'a [char],
+    pos: usize,
+}
+
+impl SNBTParser<'_> {
+    fn error(&self, message: &str) -> SNBTError {
+        SNBTError {
+            at: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SNBTError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn value(&mut self) -> Result<EFSValueType, SNBTError> {
+        match self.peek() {
+            Some('{') => self.compound(),
+            Some('[') => self.list(),
+            Some('"') | Some('\'') => Ok(EFSValueType::String(self.quoted()?)),
+            Some(_) => self.literal(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn compound(&mut self) -> Result<EFSValueType, SNBTError> {
+        self.expect('{')?;
+        let mut entries = HashMap::new();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(EFSValueType::Dict(entries));
+        }
+        loop {
+            // Only a quoted key can be empty, `{"":1}`.
+            let key = match self.peek() {
+                Some('"') | Some('\'') => self.quoted()?,
+                _ => match self.unquoted() {
+                    key if key.is_empty() => return Err(self.error("expected a key")),
+                    key => key,
+                },
+            };
+            self.expect(':')?;
+            entries.insert(key, self.value()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(EFSValueType::Dict(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn list(&mut self) -> Result<EFSValueType, SNBTError> {
+        self.expect('[')?;
+        let array_type = match (self.peek(), self.text.get(self.pos + 1)) {
+            (Some(c @ ('B' | 'I' | 'L')), Some(';')) => {
+                self.pos += 2;
+                Some(c)
+            }
+            _ => None,
+        };
+
+        let mut values = Vec::new();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                values.push(self.value()?);
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some(']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.error("expected ',' or ']'")),
+                }
+            }
+        }
+
+        let Some(array_type) = array_type else {
+            return Ok(EFSValueType::List(values));
+        };
+        let invalid = || SNBTError {
+            at: self.pos,
+            message: format!("invalid element in {} array", array_type),
+        };
+        Ok(match array_type {
+            'B' => EFSValueType::NBTByteArray(
+                values
+                    .into_iter()
+                    .map(|v| match v {
+                        EFSValueType::Byte(n) => Ok(n),
+                        EFSValueType::Bool(b) => Ok(b as i8),
+                        _ => Err(invalid()),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            'I' => EFSValueType::NBTIntArray(
+                values
+                    .into_iter()
+                    .map(|v| match v {
+                        EFSValueType::Int(n) => Ok(n),
+                        _ => Err(invalid()),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => EFSValueType::NBTLongArray(
+                values
+                    .into_iter()
+                    .map(|v| match v {
+                        EFSValueType::Long(n) => Ok(n),
+                        _ => Err(invalid()),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    fn quoted(&mut self) -> Result<String, SNBTError> {
+        let quote = self.text[self.pos];
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            match self.text.get(self.pos) {
+                Some('\\') => {
+                    match self.text.get(self.pos + 1) {
+                        Some(&c) if c == quote || c == '\\' => string.push(c),
+                        _ => return Err(self.error("invalid escape sequence")),
+                    }
+                    self.pos += 2;
+                }
+                Some(&c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some(&c) => {
+                    string.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn unquoted(&mut self) -> String {
+        self.skip_whitespace();
+        let mut string = String::new();
+        while let Some(&c) = self.text.get(self.pos).filter(|c| is_unquoted_char(**c)) {
+            string.push(c);
+            self.pos += 1;
+        }
+        string
+    }
+
+    fn literal(&mut self) -> Result<EFSValueType, SNBTError> {
+        let start = self.pos;
+        let word = self.unquoted();
+        if word.is_empty() {
+            return Err(self.error("expected a value"));
+        }
+
+        let out_of_range = || SNBTError {
+            at: start,
+            message: format!("number out of range: {}", word),
+        };
+        // Commands have no syntax for infinity, so floats too large for their type
+        // are out of range as well.
+        let float = |s: &str| match s.parse::<f32>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => Err(out_of_range()),
+        };
+        let double = |s: &str| match s.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => Err(out_of_range()),
+        };
+        let is_integer = |s: &str| {
+            let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+        };
+        let is_decimal = |s: &str| {
+            let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+            digits.chars().any(|c| c.is_ascii_digit())
+                && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && digits.chars().filter(|c| c == &'.').count() <= 1
+        };
+
+        let (body, suffix) = word.split_at(word.len() - 1);
+        let value = match suffix {
+            "b" | "B" if is_integer(body) => {
+                EFSValueType::Byte(body.parse().map_err(|_| out_of_range())?)
+            }
+            "s" | "S" if is_integer(body) => {
+                EFSValueType::Short(body.parse().map_err(|_| out_of_range())?)
+            }
+            "l" | "L" if is_integer(body) => {
+                EFSValueType::Long(body.parse().map_err(|_| out_of_range())?)
+            }
+            "f" | "F" if is_decimal(body) => EFSValueType::Float(float(body)?),
+            "d" | "D" if is_decimal(body) => EFSValueType::Double(double(body)?),
+            _ if is_integer(&word) => EFSValueType::Int(word.parse().map_err(|_| out_of_range())?),
+            _ if is_decimal(&word) => EFSValueType::Double(double(&word)?),
+            _ if word == "true" => EFSValueType::Bool(true),
+            _ if word == "false" => EFSValueType::Bool(false),
+            _ => EFSValueType::String(word),
+        };
+        Ok(value)
+    }
+}
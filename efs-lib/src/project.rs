@@ -1,18 +1,33 @@
 use std::{
     collections::HashMap,
     fs::{self, DirBuilder},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use crate::config::Config;
+use anyhow::{bail, Context};
+
+use crate::{
+    compiler::{
+        is_resource_location,
+        modules::{self, Module},
+        Compiler,
+    },
+    config::Config,
+    parser::{ast::Program, Parser},
+};
+
+/// Pack format written to `pack.mcmeta` when `datapack_format` is not set, 1.20.3.
+/// The generated functions use macros (18) and `return run` (26).
+const DEFAULT_PACK_FORMAT: u32 = 26;
 
 pub struct Project {
+    pub root: PathBuf,
     pub files: HashMap<PathBuf, String>,
     pub config: Config,
 }
 
 impl Project {
-    pub fn new(name: String, path: PathBuf) -> anyhow::Result<()> {
+    pub fn new(name: String, path: PathBuf) -> anyhow::Result<Self> {
         DirBuilder::new().recursive(true).create(path.join("src"))?;
 
         let config = Config::new(name);
@@ -21,7 +36,8 @@ impl Project {
             path.join("efs-config.toml"),
             toml::to_string_pretty(&config)?,
         )?;
-        Ok(())
+
+        Self::open(path)
     }
 
     pub fn open(path: PathBuf) -> anyhow::Result<Self> {
@@ -34,6 +50,65 @@ impl Project {
             .map(|path| (path.clone(), fs::read_to_string(path).unwrap_or_default()))
             .collect();
 
-        Ok(Self { files, config })
+        Ok(Self {
+            root: path,
+            files,
+            config,
+        })
+    }
+
+    /// Compiles every source file and writes the datapack to `to`. Each file is a
+    /// module named after its path in `src`, see `modules::link`.
+    pub fn build(&self, to: &Path) -> anyhow::Result<()> {
+        let src = self.root.join("src");
+        let mut paths = self.files.keys().collect::<Vec<_>>();
+        paths.sort();
+        let mut modules = Vec::new();
+        for path in paths {
+            let program = Parser::new(self.files[path].clone())
+                .and_then(|parser| Ok(parser.parse::<Program>()?))
+                .with_context(|| format!("in {}", path.display()))?;
+            modules.push(Module {
+                name: Module::name_of(&src, path)?,
+                file: path.clone(),
+                program,
+            });
+        }
+        let programs = modules::link(&modules)?;
+        let namespace = self.config.namespace();
+        if !is_resource_location(&format!("{}:function", namespace)) {
+            bail!(
+                "{} is not a valid namespace, set namespace in efs-config.toml",
+                namespace
+            );
+        }
+        let mut compiler = Compiler::new(namespace);
+        compiler.uninstall = self.config.uninstall;
+        compiler.compile(&programs)?;
+        for warning in &compiler.symbols.warnings {
+            eprintln!("warning at {}: {}", warning.at, warning.message);
+        }
+        let datapack = compiler.datapack;
+
+        let pack_format = if self.config.datapack_format.is_empty() {
+            DEFAULT_PACK_FORMAT
+        } else {
+            self.config
+                .datapack_format
+                .parse()
+                .context("datapack_format in efs-config.toml must be a number")?
+        };
+        let mcmeta = serde_json::json!({
+            "pack": {
+                "pack_format": pack_format,
+                "description": format!("{} {}", self.config.name, self.config.version),
+            }
+        });
+        fs::create_dir_all(to)?;
+        fs::write(
+            to.join("pack.mcmeta"),
+            serde_json::to_string_pretty(&mcmeta)?,
+        )?;
+        datapack.write(to)
     }
 }
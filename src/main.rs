@@ -60,6 +60,12 @@ fn main() {
         }
         Commands::Build { to } => {
             println!("build: {:?}", to);
+
+            let project = Project::open(env::current_dir().unwrap()).unwrap();
+            let targets = to.unwrap_or_else(|| project.config.targets.clone());
+            for target in targets {
+                project.build(&target.join(&project.config.name)).unwrap();
+            }
         }
         Commands::Check => {
             println!("check");